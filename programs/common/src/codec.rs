@@ -0,0 +1,206 @@
+// ───────────────────────────────────────────────────────────────────────
+//  Версионируемые записи в PDA
+// ───────────────────────────────────────────────────────────────────────
+//
+// Все записи программ начинаются с format_type: u32 (LE), дальше — тело
+// конкретной версии. Трейт `VersionedAccount` описывает:
+// - LATEST_FORMAT          — в каком формате запись пишется сейчас
+// - decode_version()       — чтение тела любой поддерживаемой версии
+// - encode_latest()        — запись тела в последнем формате
+// - upgrade_from()         — доводка значения, прочитанного из старой версии
+//
+// `ByteReader` / `ByteWriter` — курсоры с проверкой границ: чтение за
+// пределами данных возвращает DeserializationError, а не паникует.
+
+use anchor_lang::prelude::*;
+
+use crate::utils::ErrCode;
+
+
+/// Курсор для чтения little-endian данных с проверкой границ
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, offset: 0 }
+    }
+
+    /// Текущее смещение от начала данных
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Сколько байт ещё не прочитано
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    /// Следующий байт без сдвига курсора (None — данные закончились)
+    pub fn peek_u8(&self) -> Option<u8> {
+        self.data.get(self.offset).copied()
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.offset.checked_add(len).ok_or(ErrCode::DeserializationError)?;
+        let bytes = self.data.get(self.offset..end).ok_or(ErrCode::DeserializationError)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.read_bytes(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u128(&mut self) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_pubkey(&mut self) -> Result<Pubkey> {
+        Ok(Pubkey::new_from_array(self.read_array()?))
+    }
+
+    /// Строка в формате «длина u8 + байты UTF-8»
+    pub fn read_short_string(&mut self) -> Result<String> {
+        let len = self.read_u8()? as usize;
+        let bytes = self.read_bytes(len)?;
+        std::str::from_utf8(bytes)
+            .map(|s| s.to_string())
+            .map_err(|_| error!(ErrCode::DeserializationError))
+    }
+}
+
+
+/// Буфер для записи little-endian данных
+#[derive(Default)]
+pub struct ByteWriter {
+    buf: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        ByteWriter { buf: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ByteWriter { buf: Vec::with_capacity(capacity) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u128(&mut self, value: u128) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_pubkey(&mut self, value: &Pubkey) {
+        self.write_bytes(value.as_ref());
+    }
+
+    /// Строка в формате «длина u8 + байты»; длиннее 255 байт — обрезается
+    pub fn write_short_string(&mut self, value: &str) {
+        let bytes = value.as_bytes();
+        let len = bytes.len().min(255);
+        self.write_u8(len as u8);
+        self.write_bytes(&bytes[..len]);
+    }
+}
+
+
+/// Читает format_type (первые 4 байта) записи
+pub fn read_format_tag(data: &[u8]) -> Result<u32> {
+    ByteReader::new(data).read_u32()
+}
+
+
+/// Запись в PDA с версией формата в первых 4 байтах
+pub trait VersionedAccount: Sized {
+    /// Формат, в котором запись сохраняется сейчас
+    const LATEST_FORMAT: u32;
+
+    /// Читает тело записи версии `format` (курсор стоит сразу после format_type).
+    /// Для неизвестной версии — UnsupportedFormat.
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self>;
+
+    /// Пишет тело записи в формате LATEST_FORMAT (format_type уже записан)
+    fn encode_latest(&self, writer: &mut ByteWriter);
+
+    /// Вызывается после чтения записи старой версии, чтобы заполнить
+    /// поля, которых в ней не было. По умолчанию ничего не делает.
+    fn upgrade_from(&mut self, _from_format: u32) {}
+
+    /// Полная десериализация: format_type + тело нужной версии + upgrade_from
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(data);
+        let format = reader.read_u32()?;
+        let mut value = Self::decode_version(format, &mut reader)?;
+        if format != Self::LATEST_FORMAT {
+            value.upgrade_from(format);
+        }
+        Ok(value)
+    }
+
+    /// Полная сериализация в последнем формате
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        writer.write_u32(Self::LATEST_FORMAT);
+        self.encode_latest(&mut writer);
+        writer.into_inner()
+    }
+}
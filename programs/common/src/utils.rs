@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
+#[allow(deprecated)]
 use anchor_lang::solana_program::{
-    program::invoke_signed,
+    ed25519_program,
+    program::{invoke, invoke_signed},
     system_instruction,
-    system_program
+    sysvar::{self, instructions::load_instruction_at_checked},
 };
 
 
@@ -48,6 +50,126 @@ pub enum ErrCode {
     #[msg("Не получилось создать пользователя, система уже перегружена, попробуйте поззже!")]
     NoSuitableIdPda = 1010,
 
+    /// У пользователя уже максимальное количество устройств
+    #[msg("Достигнуто максимальное количество устройств пользователя")]
+    TooManyDevices = 1013,
+
+    #[msg("Устройство с таким ключом уже добавлено")]
+    DeviceAlreadyExists = 1014,
+
+    #[msg("Устройство не найдено")]
+    DeviceNotFound = 1015,
+
+    /// Устройство не может отозвать последнее устройство пользователя (только мастер-ключ)
+    #[msg("Нельзя отозвать последнее устройство без мастер-ключа")]
+    CannotRevokeLastDevice = 1016,
+
+    #[msg("Некорректные параметры восстановления доступа")]
+    InvalidRecoveryConfig = 1017,
+
+    /// Не набрано нужное количество подписей гарантов
+    #[msg("Недостаточно подписей для восстановления доступа")]
+    RecoveryQuorumNotReached = 1018,
+
+    #[msg("Время ожидания восстановления ещё не прошло")]
+    RecoveryDelayNotPassed = 1019,
+
+    #[msg("Некорректная инструкция проверки подписи ed25519")]
+    InvalidEd25519Instruction = 1020,
+
+    /// Логин удерживается после переименования и ещё не освобождён
+    #[msg("Логин временно удерживается после переименования")]
+    LoginOnHold = 1021,
+
+    #[msg("Пользователь удалён")]
+    UserDeleted = 1022,
+
+    /// Кошелёк уже привязан к другому пользователю (и владелец не разрешил несколько)
+    #[msg("Этот кошелёк уже привязан к пользователю")]
+    WalletAlreadyRegistered = 1023,
+
+    /// Общий счётчик переведён на шарды — регистрироваться нужно через шард
+    #[msg("Счётчик пользователей разделён на шарды, укажите PDA шарда")]
+    UserCounterSharded = 1024,
+
+    #[msg("Некорректное количество шардов счётчика")]
+    InvalidShardCount = 1025,
+
+    #[msg("Пользователь временно заблокирован")]
+    UserSuspended = 1026,
+
+    #[msg("Пользователь забанен")]
+    UserBanned = 1027,
+
+    /// Переход между статусами пользователя не разрешён
+    #[msg("Недопустимая смена статуса пользователя")]
+    InvalidStatusTransition = 1028,
+
+    /// Регистрация (или этот её вид) выключена в настройках
+    #[msg("Регистрация отключена")]
+    RegistrationDisabled = 1029,
+
+    #[msg("Некорректные настройки регистрации")]
+    InvalidRegistrationConfig = 1030,
+
+    #[msg("Логин зарезервирован")]
+    LoginReserved = 1031,
+
+    /// Поле профиля длиннее допустимого
+    #[msg("Некорректные данные профиля")]
+    InvalidProfile = 1032,
+
+    /// Пул одноразовых ключей устройства переполнен
+    #[msg("Слишком много одноразовых ключей")]
+    TooManyPrekeys = 1033,
+
+    #[msg("У устройства нет одноразовых ключей")]
+    NoPrekeysAvailable = 1034,
+
+    #[msg("Одноразовый ключ с таким id уже загружен")]
+    PrekeyAlreadyExists = 1035,
+
+    #[msg("Срок действия подписанного разрешения истёк или слишком велик")]
+    AuthorizationExpired = 1036,
+
+    #[msg("Nonce уже использован (должен быть больше последнего)")]
+    NonceAlreadyUsed = 1037,
+
+    #[msg("Слишком рано: с момента фиксации логина прошло мало слотов")]
+    CommitmentTooEarly = 1038,
+
+    #[msg("Срок действия фиксации логина истёк")]
+    CommitmentExpired = 1039,
+
+    #[msg("Фиксация логина ещё действует")]
+    CommitmentNotExpired = 1040,
+
+    #[msg("Реферер не найден или не может приглашать")]
+    InvalidReferrer = 1041,
+
+    #[msg("Не переданы аккаунты реферера (PDA по ID, счётчик приглашений или кошелёк)")]
+    ReferrerAccountNotFound = 1042,
+
+    #[msg("Оплата комиссии токеном не настроена")]
+    TokenFeeDisabled = 1043,
+
+    #[msg("Неверный токен-аккаунт или mint для оплаты комиссии")]
+    InvalidTokenAccount = 1044,
+
+    #[msg("Переданный кошелёк не совпадает с мастер-ключом пользователя")]
+    InvalidUserWallet = 1045,
+
+    #[msg("Запрос на восстановление ещё может быть выполнен или отменён владельцем")]
+    RecoveryRequestStillActive = 1046,
+
+    /// В настройках включён REG_FLAG_COMMIT_REQUIRED — логин регистрируется только через commit-reveal
+    #[msg("Логин можно зарегистрировать только через фиксацию (commit-reveal)")]
+    LoginCommitmentRequired = 1047,
+
+    /// Доля рефереру платится только в SOL — при оплате токеном с реферером её нельзя потерять молча
+    #[msg("При оплате комиссии токеном реферальная доля не выплачивается: оплатите в SOL")]
+    ReferralShareRequiresSol = 1048,
+
 
 }
 
@@ -70,9 +192,9 @@ pub enum ErrCode {
 
 
 
-///----------------------------------------------------------------------------------------------------------
-///                        Базовые функции для работы с PDA
-///----------------------------------------------------------------------------------------------------------
+//----------------------------------------------------------------------------------------------------------
+//                        Базовые функции для работы с PDA
+//----------------------------------------------------------------------------------------------------------
 
 /// Создаёт PDA аккаунт (если его ещё нет), и записывает в него массив байт.
 ///
@@ -97,7 +219,7 @@ pub fn create_and_write_pda<'info>(
     if pda_account.owner == &Pubkey::default() {
         msg!("Создаём PDA с размером {} байт", space);
 
-        let space = space; //+ 128; // Добавляется запас под метаданные
+        // let space = space + 128; // Добавляется запас под метаданные
         // Вычисляем необходимую арендную плату
         let lamports = Rent::get()?.minimum_balance(space as usize);
 
@@ -118,7 +240,7 @@ pub fn create_and_write_pda<'info>(
                 pda_account.clone(),
                 system_program.clone(),
             ],
-            &[&seeds],
+            &[seeds],
         )?;
     }
 
@@ -198,7 +320,7 @@ pub fn create_pda<'info>(
             pda_account.clone(),
             system_program.clone(),
         ],
-        &[&seeds], // PDA сиды → для подписи
+        &[seeds], // PDA сиды → для подписи
     )?;
 
     Ok(())
@@ -250,8 +372,8 @@ pub fn write_to_pda<'info>(
 ///
 /// * Принимает:   ссылку на `AccountInfo<'info>` PDA-аккаунта.
 /// * Возвращает:  `Vec<u8>` с данными аккаунта.  
-///                Если аккаунта нет или его данные пусты — возвращается `Vec::new()`
-///                длиной 0 байт.
+///   Если аккаунта нет или его данные пусты — возвращается `Vec::new()`
+///   длиной 0 байт.
 ///
 /// Как работает ───────────────────────────────────────────────────────────
 /// 1. Проверяем, что аккаунт **инициализирован**: у не-инициализированного
@@ -327,7 +449,6 @@ pub fn safe_read_pda<'info>(pda_account: &AccountInfo<'info>) -> Vec<u8> {
 ///   владелец = System Program (чисто/ожидаемо).
 /// — В следующей транзакции можно снова создать PDA с тем же сидом.
 /// ------------------------------------------------------------------------
-
 pub fn delete_pda_return_rent<'info>(
     pda_account: &AccountInfo<'info>,
     recipient: &AccountInfo<'info>,
@@ -354,11 +475,164 @@ pub fn delete_pda_return_rent<'info>(
     }
 
     // 3) Сжать до 0 байт
-    pda_account.realloc(0, false)?;
+    pda_account.resize(0)?;
 
     // Никаких assign/transfer больше не делаем — это надёжнее.
     msg!("PDA закрыт: рента отправлена на {}", recipient.key);
     Ok(())
 }
- 
 
+
+
+
+
+/// ------------------------------------------------------------------------
+/// realloc_pda — изменить размер PDA с доплатой / возвратом ренты
+/// ------------------------------------------------------------------------
+///
+/// Параметры:
+/// - `pda_account`   : PDA-аккаунт (mut), размер которого меняем (owned вашей программой)
+/// - `payer`         : кто доплачивает ренту при увеличении (signer, mut)
+///   и кто получает излишек ренты при уменьшении
+/// - `system_program`: системная программа (111...111)
+/// - `new_space`     : новый размер данных в байтах
+///
+/// Делает:
+/// 1) Считает минимальный баланс для нового размера.
+/// 2) Если лампортов не хватает — переводит разницу с `payer` через System Program.
+/// 3) Меняет размер данных (новые байты заполняются нулями).
+/// 4) Если лампортов стало больше, чем нужно — возвращает излишек на `payer`.
+/// ------------------------------------------------------------------------
+pub fn realloc_pda<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_space: usize,
+) -> Result<()> {
+    let current_space = pda_account.data_len();
+    if current_space == new_space {
+        return Ok(());
+    }
+
+    // 1) Сколько лампортов нужно для нового размера
+    let required = Rent::get()?.minimum_balance(new_space);
+    let current = pda_account.lamports();
+
+    // 2) Доплата ренты (перевод с подписанта на PDA)
+    if required > current {
+        let ix = system_instruction::transfer(payer.key, pda_account.key, required - current);
+        invoke(
+            &ix,
+            &[payer.clone(), pda_account.clone(), system_program.clone()],
+        )?;
+    }
+
+    // 3) Меняем размер (при увеличении хвост заполняется нулями)
+    pda_account.resize(new_space)?;
+
+    // 4) Возврат излишка ренты (мы владелец PDA, можем списывать напрямую)
+    if current > required {
+        let refund = current - required;
+        **pda_account.lamports.borrow_mut() = required;
+        **payer.lamports.borrow_mut() = payer
+            .lamports()
+            .checked_add(refund)
+            .ok_or(ProgramError::InsufficientFunds)?;
+    }
+
+    msg!("Размер PDA изменён: {} → {} байт", current_space, new_space);
+    Ok(())
+}
+
+
+
+
+
+/// ------------------------------------------------------------------------
+/// ed25519_signers_for_message — кто подписал сообщение в этой транзакции
+/// ------------------------------------------------------------------------
+///
+/// Подпись ed25519 программа проверить сама не может, поэтому клиент кладёт
+/// в ту же транзакцию инструкцию нативной программы Ed25519SigVerify111...
+/// Если подпись неверна — рантайм отклонит всю транзакцию. Нам остаётся
+/// только прочитать эти инструкции через sysvar Instructions и убедиться,
+/// что подписано именно наше сообщение.
+///
+/// Параметры:
+/// - `instructions_sysvar`: аккаунт Sysvar1nstructions1111111111111111111111111
+/// - `message`            : ожидаемое сообщение (байт в байт)
+///
+/// Возвращает:
+/// - список публичных ключей, подписавших `message`
+///
+/// Учитываются только подписи, у которых ключ, подпись и сообщение лежат
+/// в самой ed25519-инструкции (instruction_index = u16::MAX), иначе данные
+/// можно было бы подставить из чужой инструкции.
+/// ------------------------------------------------------------------------
+pub fn ed25519_signers_for_message(
+    instructions_sysvar: &AccountInfo,
+    message: &[u8],
+) -> Result<Vec<Pubkey>> {
+    // Размер заголовка одной подписи в данных ed25519-инструкции
+    const OFFSETS_LEN: usize = 14;
+    // «Данные в этой же инструкции»
+    const THIS_INSTRUCTION: u16 = u16::MAX;
+
+    fn read_u16(data: &[u8], at: usize) -> Result<u16> {
+        let bytes = data.get(at..at + 2).ok_or(ErrCode::InvalidEd25519Instruction)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Это действительно sysvar Instructions (иначе сообщение можно подделать)
+    require!(
+        sysvar::instructions::check_id(instructions_sysvar.key),
+        ErrCode::InvalidEd25519Instruction
+    );
+
+    let mut signers = Vec::new();
+    let mut index: usize = 0;
+
+    // Перебираем все инструкции транзакции, пока sysvar их отдаёт
+    while let Ok(ix) = load_instruction_at_checked(index, instructions_sysvar) {
+        index += 1;
+        if ix.program_id != ed25519_program::ID {
+            continue;
+        }
+
+        let data = &ix.data;
+        let count = *data.first().ok_or(ErrCode::InvalidEd25519Instruction)? as usize;
+
+        for i in 0..count {
+            let base = 2 + i * OFFSETS_LEN;
+            let pubkey_offset = read_u16(data, base + 4)? as usize;
+            let pubkey_ix = read_u16(data, base + 6)?;
+            let message_offset = read_u16(data, base + 8)? as usize;
+            let message_size = read_u16(data, base + 10)? as usize;
+            let message_ix = read_u16(data, base + 12)?;
+            let signature_ix = read_u16(data, base + 2)?;
+
+            if pubkey_ix != THIS_INSTRUCTION
+                || message_ix != THIS_INSTRUCTION
+                || signature_ix != THIS_INSTRUCTION
+            {
+                continue;
+            }
+
+            let signed = data
+                .get(message_offset..message_offset + message_size)
+                .ok_or(ErrCode::InvalidEd25519Instruction)?;
+            if signed != message {
+                continue;
+            }
+
+            let key_bytes: [u8; 32] = data
+                .get(pubkey_offset..pubkey_offset + 32)
+                .ok_or(ErrCode::InvalidEd25519Instruction)?
+                .try_into()
+                .map_err(|_| ErrCode::InvalidEd25519Instruction)?;
+            signers.push(Pubkey::new_from_array(key_bytes));
+        }
+    }
+
+    Ok(signers)
+}
@@ -0,0 +1,151 @@
+// ==============================================
+// Тесты codec: границы ByteReader и чтение версий через VersionedAccount.
+// ==============================================
+
+use anchor_lang::prelude::*;
+use common::codec::{read_format_tag, ByteReader, ByteWriter, VersionedAccount};
+use common::utils::ErrCode;
+
+#[test]
+fn reader_reads_values_in_order() {
+    let key = Pubkey::new_unique();
+    let mut writer = ByteWriter::new();
+    writer.write_u8(1);
+    writer.write_u16(2);
+    writer.write_u32(3);
+    writer.write_u64(4);
+    writer.write_i64(-5);
+    writer.write_u128(6);
+    writer.write_pubkey(&key);
+    writer.write_short_string("shine");
+    let data = writer.into_inner();
+
+    let mut reader = ByteReader::new(&data);
+    assert_eq!(reader.read_u8().unwrap(), 1);
+    assert_eq!(reader.read_u16().unwrap(), 2);
+    assert_eq!(reader.read_u32().unwrap(), 3);
+    assert_eq!(reader.read_u64().unwrap(), 4);
+    assert_eq!(reader.read_i64().unwrap(), -5);
+    assert_eq!(reader.read_u128().unwrap(), 6);
+    assert_eq!(reader.read_pubkey().unwrap(), key);
+    assert_eq!(reader.read_short_string().unwrap(), "shine");
+    assert_eq!(reader.remaining(), 0);
+    assert_eq!(reader.peek_u8(), None);
+}
+
+#[test]
+fn reader_past_end_fails_without_moving() {
+    let data = [1u8, 2, 3];
+    let mut reader = ByteReader::new(&data);
+    assert_eq!(reader.read_u8().unwrap(), 1);
+
+    // u32 не помещается в оставшиеся 2 байта — ошибка, курсор на месте
+    assert_eq!(reader.read_u32().unwrap_err(), ErrCode::DeserializationError.into());
+    assert_eq!(reader.offset(), 1);
+    assert_eq!(reader.read_u16().unwrap(), u16::from_le_bytes([2, 3]));
+    assert_eq!(reader.read_u8().unwrap_err(), ErrCode::DeserializationError.into());
+}
+
+#[test]
+fn reader_rejects_length_overflow() {
+    let data = [0u8; 4];
+    let mut reader = ByteReader::new(&data);
+    reader.read_u8().unwrap();
+    assert_eq!(reader.read_bytes(usize::MAX).unwrap_err(), ErrCode::DeserializationError.into());
+    assert_eq!(reader.remaining(), 3);
+}
+
+#[test]
+fn short_string_checks_length_and_utf8() {
+    // длина 5, а байт только 2
+    let mut reader = ByteReader::new(&[5u8, b'a', b'b']);
+    assert_eq!(reader.read_short_string().unwrap_err(), ErrCode::DeserializationError.into());
+
+    // не UTF-8
+    let mut reader = ByteReader::new(&[2u8, 0xff, 0xfe]);
+    assert_eq!(reader.read_short_string().unwrap_err(), ErrCode::DeserializationError.into());
+}
+
+#[test]
+fn writer_truncates_long_strings() {
+    let mut writer = ByteWriter::new();
+    writer.write_short_string(&"x".repeat(300));
+    let data = writer.into_inner();
+    assert_eq!(data.len(), 1 + 255);
+    assert_eq!(ByteReader::new(&data).read_short_string().unwrap().len(), 255);
+}
+
+#[test]
+fn format_tag_needs_four_bytes() {
+    assert_eq!(read_format_tag(&7u32.to_le_bytes()).unwrap(), 7);
+    assert_eq!(read_format_tag(&[7, 0, 0]).unwrap_err(), ErrCode::DeserializationError.into());
+}
+
+
+/// Тестовая запись: V1 — только `value: u32`, V2 — `value: u64` и `label: u8`
+#[derive(Debug, PartialEq)]
+struct Sample {
+    value: u64,
+    label: u8,
+}
+
+const SAMPLE_FORMAT_V1: u32 = 1;
+const SAMPLE_FORMAT_V2: u32 = 2;
+
+/// Метка, которую upgrade_from проставляет записям V1
+const SAMPLE_UPGRADED_LABEL: u8 = 9;
+
+impl VersionedAccount for Sample {
+    const LATEST_FORMAT: u32 = SAMPLE_FORMAT_V2;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            SAMPLE_FORMAT_V1 => Ok(Sample { value: reader.read_u32()? as u64, label: 0 }),
+            SAMPLE_FORMAT_V2 => Ok(Sample { value: reader.read_u64()?, label: reader.read_u8()? }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.value);
+        writer.write_u8(self.label);
+    }
+
+    fn upgrade_from(&mut self, from_format: u32) {
+        if from_format == SAMPLE_FORMAT_V1 {
+            self.label = SAMPLE_UPGRADED_LABEL;
+        }
+    }
+}
+
+#[test]
+fn decode_old_version_runs_upgrade() {
+    let mut data = SAMPLE_FORMAT_V1.to_le_bytes().to_vec();
+    data.extend_from_slice(&42u32.to_le_bytes());
+
+    let sample = Sample::decode(&data).unwrap();
+    assert_eq!(sample, Sample { value: 42, label: SAMPLE_UPGRADED_LABEL });
+
+    // повторная запись — уже в последнем формате
+    let encoded = sample.encode();
+    assert_eq!(read_format_tag(&encoded).unwrap(), SAMPLE_FORMAT_V2);
+    assert_eq!(encoded.len(), 4 + 8 + 1);
+}
+
+#[test]
+fn decode_latest_version_skips_upgrade() {
+    // метка 0 в V2 — это значение, а не «не заполнено»: upgrade_from не вызывается
+    let sample = Sample { value: u64::MAX, label: 0 };
+    assert_eq!(Sample::decode(&sample.encode()).unwrap(), sample);
+}
+
+#[test]
+fn decode_rejects_unknown_and_truncated() {
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&[0u8; 9]);
+    assert_eq!(Sample::decode(&data).unwrap_err(), ErrCode::UnsupportedFormat.into());
+
+    let data = Sample { value: 1, label: 2 }.encode();
+    assert_eq!(Sample::decode(&data[..data.len() - 1]).unwrap_err(), ErrCode::DeserializationError.into());
+    assert_eq!(Sample::decode(&data[..2]).unwrap_err(), ErrCode::DeserializationError.into());
+}
@@ -0,0 +1,120 @@
+// ==============================================
+// Тесты ed25519_signers_for_message: какие ed25519-инструкции
+// транзакции принимаются как подпись сообщения, а какие отклоняются.
+// Сама подпись проверяется рантаймом (precompile), здесь — только разбор.
+// ==============================================
+
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    ed25519_program,
+    sysvar::instructions::{construct_instructions_data, BorrowedInstruction},
+};
+use common::utils::{ed25519_signers_for_message, ErrCode};
+
+/// «Данные в этой же инструкции»
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+/// Данные ed25519-инструкции с одной подписью: заголовок, ключ, подпись, сообщение.
+fn ed25519_data(signer: &Pubkey, message: &[u8], data_ix: u16) -> Vec<u8> {
+    let pubkey_offset: u16 = 16;
+    let signature_offset: u16 = pubkey_offset + 32;
+    let message_offset: u16 = signature_offset + 64;
+
+    let mut data = vec![1u8, 0u8];
+    for value in [
+        signature_offset,
+        data_ix,
+        pubkey_offset,
+        data_ix,
+        message_offset,
+        message.len() as u16,
+        data_ix,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(&[7u8; 64]);
+    data.extend_from_slice(message);
+    data
+}
+
+/// Собирает данные sysvar Instructions из пар (программа, данные).
+fn sysvar_data(instructions: &[(Pubkey, Vec<u8>)]) -> Vec<u8> {
+    let borrowed: Vec<BorrowedInstruction> = instructions
+        .iter()
+        .map(|(program_id, data)| BorrowedInstruction {
+            program_id,
+            accounts: Vec::new(),
+            data,
+        })
+        .collect();
+    construct_instructions_data(&borrowed)
+}
+
+/// Вызывает ed25519_signers_for_message на аккаунте с ключом `key` и данными `data`.
+fn signers(key: Pubkey, mut data: Vec<u8>, message: &[u8]) -> Result<Vec<Pubkey>> {
+    let owner = Pubkey::default();
+    let mut lamports = 0u64;
+    let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+    ed25519_signers_for_message(&account, message)
+}
+
+fn sysvar_id() -> Pubkey {
+    anchor_lang::solana_program::sysvar::instructions::ID
+}
+
+#[test]
+fn accepts_signature_over_same_message() {
+    let signer = Pubkey::new_unique();
+    let data = sysvar_data(&[(ed25519_program::ID, ed25519_data(&signer, b"login", THIS_INSTRUCTION))]);
+    assert_eq!(signers(sysvar_id(), data, b"login").unwrap(), vec![signer]);
+}
+
+#[test]
+fn rejects_account_that_is_not_instructions_sysvar() {
+    let signer = Pubkey::new_unique();
+    let data = sysvar_data(&[(ed25519_program::ID, ed25519_data(&signer, b"login", THIS_INSTRUCTION))]);
+    let err = signers(Pubkey::new_unique(), data, b"login").unwrap_err();
+    assert_eq!(err, ErrCode::InvalidEd25519Instruction.into());
+}
+
+#[test]
+fn ignores_signature_over_other_message() {
+    let signer = Pubkey::new_unique();
+    let data = sysvar_data(&[(ed25519_program::ID, ed25519_data(&signer, b"other", THIS_INSTRUCTION))]);
+    assert!(signers(sysvar_id(), data, b"login").unwrap().is_empty());
+}
+
+#[test]
+fn ignores_data_taken_from_other_instruction() {
+    // ключ/сообщение из другой инструкции можно подменить — такие подписи не считаются
+    let signer = Pubkey::new_unique();
+    let data = sysvar_data(&[(ed25519_program::ID, ed25519_data(&signer, b"login", 0))]);
+    assert!(signers(sysvar_id(), data, b"login").unwrap().is_empty());
+}
+
+#[test]
+fn ignores_same_data_in_non_ed25519_program() {
+    let signer = Pubkey::new_unique();
+    let data = sysvar_data(&[(Pubkey::new_unique(), ed25519_data(&signer, b"login", THIS_INSTRUCTION))]);
+    assert!(signers(sysvar_id(), data, b"login").unwrap().is_empty());
+}
+
+#[test]
+fn rejects_truncated_ed25519_data() {
+    let signer = Pubkey::new_unique();
+    let mut ed_data = ed25519_data(&signer, b"login", THIS_INSTRUCTION);
+    ed_data.truncate(10);
+    let data = sysvar_data(&[(ed25519_program::ID, ed_data)]);
+    let err = signers(sysvar_id(), data, b"login").unwrap_err();
+    assert_eq!(err, ErrCode::InvalidEd25519Instruction.into());
+}
+
+#[test]
+fn rejects_empty_ed25519_data() {
+    let data = sysvar_data(&[(ed25519_program::ID, Vec::new())]);
+    let err = signers(sysvar_id(), data, b"login").unwrap_err();
+    assert_eq!(err, ErrCode::InvalidEd25519Instruction.into());
+}
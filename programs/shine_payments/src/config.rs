@@ -0,0 +1,136 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_and_write_pda, safe_read_pda, write_to_pda};
+use std::str::FromStr;
+
+use crate::investments::ErrCode;
+
+
+// ==============================================
+// Настройки выплат (PDA [PAYMENTS_CONFIG_SEED])
+// ==============================================
+//
+// Хранит администратора настроек и ключ, которому разрешено начислять
+// бонусы (`add_bonus`). Ключ бонусов может быть обычным кошельком,
+// мультисигом или PDA программы управления (DAO) — во всех случаях он
+// должен подписать транзакцию (PDA подписывает через invoke_signed).
+
+/// Сид PDA настроек выплат.
+pub const PAYMENTS_CONFIG_SEED: &[u8] = b"shine_payments_config";
+
+/// Версия формата настроек.
+pub const PAYMENTS_CONFIG_FORMAT_V1: u32 = 1;
+
+/// Размер V1: 4 (format) + 32 (admin) + 32 (bonus_authority) = 68 байт
+pub const PAYMENTS_CONFIG_SIZE_V1: usize = 68;
+
+/// Ключ, который может создать настройки (становится первым администратором)    key3
+pub const PAYMENTS_BOOTSTRAP_ADMIN: &str = "6bFc5Gz5qF172GQhK5HpDbWs8F6qcSxdHn5XqAstf1fY";
+
+
+/// ПОРЯДОК ПОЛЕЙ:
+///   [0..4)   format
+///   [4..36)  admin            — может менять настройки
+///   [36..68) bonus_authority  — может вызывать add_bonus
+#[derive(Clone, Copy, Debug)]
+pub struct PaymentsConfig {
+    pub admin: Pubkey,
+    pub bonus_authority: Pubkey,
+}
+
+impl VersionedAccount for PaymentsConfig {
+    const LATEST_FORMAT: u32 = PAYMENTS_CONFIG_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            PAYMENTS_CONFIG_FORMAT_V1 => Ok(PaymentsConfig {
+                admin: reader.read_pubkey()?,
+                bonus_authority: reader.read_pubkey()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.admin);
+        writer.write_pubkey(&self.bonus_authority);
+    }
+}
+
+/// Проверяет адрес PDA настроек и читает их
+pub fn read_payments_config(pda: &AccountInfo, program_id: &Pubkey) -> Result<PaymentsConfig> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(&[PAYMENTS_CONFIG_SEED], program_id);
+    require_keys_eq!(expected_pda, pda.key(), ErrCode::InvalidPdaAddress);
+    require!(pda.owner == program_id, ErrCode::EmptyPdaData);
+    PaymentsConfig::decode(&safe_read_pda(pda))
+}
+
+
+/// Аккаунты для создания и изменения настроек
+#[derive(Accounts)]
+pub struct ManagePaymentsConfig<'info> {
+    /// Администратор (при создании — PAYMENTS_BOOTSTRAP_ADMIN), платит за PDA.
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// PDA настроек выплат.
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub config_pda: UncheckedAccount<'info>,
+
+    /// Системная программа.
+    pub system_program: Program<'info, System>,
+}
+
+/// ------------------------------------------
+/// init_payments_config: однократное создание настроек.
+/// Администратор — PAYMENTS_BOOTSTRAP_ADMIN, ключ бонусов — из аргумента.
+/// ------------------------------------------
+pub fn init_payments_config(ctx: Context<ManagePaymentsConfig>, bonus_authority: Pubkey) -> Result<()> {
+    let bootstrap_admin = Pubkey::from_str(PAYMENTS_BOOTSTRAP_ADMIN).unwrap();
+    require_keys_eq!(ctx.accounts.signer.key(), bootstrap_admin, ErrCode::InvalidSigner);
+    require!(bonus_authority != Pubkey::default(), ErrCode::InvalidSigner);
+
+    let pda = ctx.accounts.config_pda.to_account_info();
+    let (expected_pda, bump) = Pubkey::find_program_address(&[PAYMENTS_CONFIG_SEED], ctx.program_id);
+    require_keys_eq!(expected_pda, pda.key(), ErrCode::InvalidPdaAddress);
+    if pda.owner != &System::id() {
+        return Err(error!(ErrCode::PdaAlreadyExists));
+    }
+
+    let config = PaymentsConfig { admin: bootstrap_admin, bonus_authority };
+    create_and_write_pda(
+        &pda,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        &[PAYMENTS_CONFIG_SEED, &[bump]],
+        config.encode(),
+        PAYMENTS_CONFIG_SIZE_V1 as u64,
+    )?;
+
+    msg!("⚙️ Настройки выплат созданы, ключ бонусов {}", bonus_authority);
+    Ok(())
+}
+
+/// ------------------------------------------
+/// update_payments_config: смена ключа бонусов и администратора (только администратор).
+/// ------------------------------------------
+pub fn update_payments_config(
+    ctx: Context<ManagePaymentsConfig>,
+    bonus_authority: Pubkey,
+    new_admin: Pubkey,
+) -> Result<()> {
+    let pda = ctx.accounts.config_pda.to_account_info();
+    let mut config = read_payments_config(&pda, ctx.program_id)?;
+    require_keys_eq!(ctx.accounts.signer.key(), config.admin, ErrCode::InvalidSigner);
+    require!(bonus_authority != Pubkey::default(), ErrCode::InvalidSigner);
+    require!(new_admin != Pubkey::default(), ErrCode::InvalidSigner);
+
+    config.bonus_authority = bonus_authority;
+    config.admin = new_admin;
+    write_to_pda(&pda, &config.encode())?;
+
+    msg!("⚙️ Ключ бонусов {}, администратор {}", bonus_authority, new_admin);
+    Ok(())
+}
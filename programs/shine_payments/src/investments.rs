@@ -1,89 +1,196 @@
 use anchor_lang::prelude::*;
 
-use anchor_lang::solana_program::{program::invoke_signed, system_instruction};
 use common::utils::*; // тянем общие PDA-хелперы из programs/common
+use common::codec::{ByteReader, ByteWriter, VersionedAccount}; // версионируемые записи
+
+use crate::config::read_payments_config; // ключ бонусов
 
 
 
 /// Утилита чтения структуры из PDA: читает байты и десериализует.
 /// Возвращает ошибку, если данных нет/пустые/неверный формат.
+/// На время перехода принимаются и V1, и V2 (поле `format` — версия в PDA).
 fn read_state_from_pda(pda: &AccountInfo) -> Result<InvestState> {
     let raw = safe_read_pda(pda);                                   // ← берём Vec<u8> (или пустой)
     require!(!raw.is_empty(), ErrCode::EmptyPdaData);               // ← пусто — ошибка
-    let st = deserialize_invest_state(&raw)?;                          // ← десериализуем по формату
-    require!(st.format == INVEST_STATE_FORMAT_V1, ErrCode::UnsupportedFormat); // ← проверяем версию
-    Ok(st)
+    deserialize_invest_state(&raw)                                  // ← десериализуем по формату
 }
 
 /// Утилита записи структуры в PDA: сериализует и пишет.
+/// Пишет в той же версии, в которой запись лежит в PDA (`s.format`):
+/// V1 не помещается в V2 без realloc, поэтому переводится только migrate_invest_state.
 /// Важно: сам аккаунт уже должен существовать и быть #[account(mut)].
 fn write_state_to_pda(pda: &AccountInfo, s: &InvestState) -> Result<()> {
-    let raw = serialize_invest_state_v1(s); // ← 28 байт
+    let raw = serialize_invest_state(s)?;
     write_to_pda(pda, &raw)              // ← записываем в начало data
 }
 
 /// ==============================================
 /// Контексты инструкций (минимально необходимые)
 /// ==============================================
-
-/// init: создаём PDA и кладём в него PayStateV1 {format=1, coef=10, ...0}
+///
+/// init: создаём PDA и кладём в него InvestState {format=2, coef=10, ...0}, плюс пустой vault
 #[derive(Accounts)]
 pub struct Init<'info> {
-    /// Плательщик аренды за PDA; подписант транзакции.
+    /// Администратор настроек выплат; платит аренду за PDA.
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    /// PDA настроек выплат (в нём — администратор).
+    /// CHECK: проверяется вручную по адресу
+    pub config_pda: UncheckedAccount<'info>,
+
     /// Наш PDA (с произвольным типом, чтобы работать через AccountInfo).
     /// Проверку адреса делаем в handler (по seed + bump), чтобы избежать подмены.
     /// CHECK: проверяется вручную по адресу
     #[account(mut)]
     pub state_pda: UncheckedAccount<'info>,
 
+    /// PDA-хранилище лампортов инвесторов (создаётся вместе с состоянием).
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub vault_pda: UncheckedAccount<'info>,
+
     /// Системная программа.
     pub system_program: Program<'info, System>,
 }
 
-/// Общие аккаунты для invest/add_bonus/claim:
-/// Везде просто читаем/пишем одно и то же состояние из того же PDA.
+/// init_vault: создаём только vault (для состояния, созданного раньше)
+#[derive(Accounts)]
+pub struct InitVault<'info> {
+    /// Администратор настроек выплат; платит аренду за vault.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// PDA настроек выплат (в нём — администратор).
+    /// CHECK: проверяется вручную по адресу
+    pub config_pda: UncheckedAccount<'info>,
+
+    /// PDA-хранилище лампортов инвесторов.
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub vault_pda: UncheckedAccount<'info>,
+
+    /// Системная программа.
+    pub system_program: Program<'info, System>,
+}
+
+/// invest: инвестор переводит лампорты в vault
+#[derive(Accounts)]
+pub struct Invest<'info> {
+    /// Инвестор — подписант, с его счёта списываются лампорты.
+    #[account(mut)]
+    pub investor: Signer<'info>,
+
+    /// PDA с состоянием (должен уже существовать).
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub state_pda: UncheckedAccount<'info>,
+
+    /// PDA-хранилище лампортов (должен уже существовать).
+    /// CHECK: проверяется вручную по адресу и владельцу
+    #[account(mut)]
+    pub vault_pda: UncheckedAccount<'info>,
+
+    /// Позиция инвестора (создаётся при первой инвестиции за его счёт).
+    /// CHECK: проверяется вручную по адресу [POSITION_SEED_PREFIX, investor]
+    #[account(mut)]
+    pub position_pda: UncheckedAccount<'info>,
+
+    /// Системная программа (перевод лампортов).
+    pub system_program: Program<'info, System>,
+}
+
+/// migrate_invest_state: перевод PDA состояния V1 → V2 на месте
+#[derive(Accounts)]
+pub struct MigrateInvestState<'info> {
+    /// Любой подписант; доплачивает ренту за увеличение PDA.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// PDA с состоянием.
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub state_pda: UncheckedAccount<'info>,
+
+    /// Системная программа (доплата ренты).
+    pub system_program: Program<'info, System>,
+}
+
+/// claim: выплата инвестору из vault
 #[derive(Accounts)]
-pub struct UseState<'info> {
-    /// Любой платящий/подписант (в реальном коде — свои проверки).
-    pub signer: Signer<'info>,
+pub struct Claim<'info> {
+    /// Инвестор — подписант и получатель выплаты.
+    #[account(mut)]
+    pub investor: Signer<'info>,
+
+    /// PDA с состоянием (должен уже существовать).
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub state_pda: UncheckedAccount<'info>,
+
+    /// PDA-хранилище, из которого платим.
+    /// CHECK: проверяется вручную по адресу и владельцу
+    #[account(mut)]
+    pub vault_pda: UncheckedAccount<'info>,
+
+    /// Позиция инвестора.
+    /// CHECK: проверяется вручную по адресу [POSITION_SEED_PREFIX, investor]
+    #[account(mut)]
+    pub position_pda: UncheckedAccount<'info>,
+}
+
+/// Аккаунты для add_bonus:
+/// читаем/пишем состояние и позицию инвестора, пополняем vault на сумму бонуса.
+#[derive(Accounts)]
+pub struct AddBonus<'info> {
+    /// Ключ бонусов из настроек выплат (DAO / мультисиг / отдельный ключ).
+    /// Оплачивает бонус: лампорты уходят в vault.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// PDA настроек выплат.
+    /// CHECK: проверяется вручную по адресу
+    pub config_pda: UncheckedAccount<'info>,
 
     /// Тот же PDA с состоянием (должен уже существовать).
     /// CHECK: проверяется вручную по адресу
     #[account(mut)]
     pub state_pda: UncheckedAccount<'info>,
 
-    /// Системная программа (на всякий случай; может не понадобиться).
+    /// PDA-хранилище, куда переводится сумма бонуса.
+    /// CHECK: проверяется вручную по адресу
+    #[account(mut)]
+    pub vault_pda: UncheckedAccount<'info>,
+
+    /// Позиция инвестора, которому начисляется бонус.
+    /// CHECK: проверяется вручную по адресу [POSITION_SEED_PREFIX, investor]
+    #[account(mut)]
+    pub position_pda: UncheckedAccount<'info>,
+
+    /// Системная программа (перевод бонуса в vault).
     pub system_program: Program<'info, System>,
 }
 
-/// ==============================================
-/// Программа
-/// ==============================================
+// ==============================================
+// Программа
+// ==============================================
 
 
 use super::*;
 
-use anchor_lang::prelude::*;
-
 
 /// ------------------------------------------
 /// init: создаёт PDA и записывает в него дефолтное состояние.
-/// format = 1, coef = 10, остальные поля = 0.
+/// format = 2, coef = 10, остальные поля = 0.
+/// Вызывает только администратор настроек выплат, и только один раз:
+/// состояние хранит счётчики очереди, которые должны сходиться с позициями.
 /// ------------------------------------------
 pub fn init(ctx: Context<Init>) -> Result<()> {
     let program_id = ctx.program_id;                              // ← адрес этой программы
 
-    // 1. Проверка что вызывает именно разрешённый ключ
-    /* todo   пока все могут вызыватьно                                         !! но в итоге будет добавленна проверка что бы только дао могло вызвать эту функцию один раз
-    require_keys_eq!(
-        ctx.accounts.payer.key(),
-        ALLOWED_INIT_CALLER,
-        ErrCode::InvalidSigner
-    );
-*/
+    // 1. Проверка что вызывает администратор настроек выплат
+    require_payments_admin(&ctx.accounts.payer, &ctx.accounts.config_pda, program_id)?;
 
     // 2. Проверка что PDA ещё не создан
     if ctx.accounts.state_pda.data_len() > 0 && ctx.accounts.state_pda.owner != &System::id() {
@@ -107,7 +214,7 @@ pub fn init(ctx: Context<Init>) -> Result<()> {
 
     // Конструируем дефолтную структуру состояния.
     let state = InvestState {
-        format: INVEST_STATE_FORMAT_V1,  // ← 1
+        format: INVEST_STATE_FORMAT_V2,  // ← 2
         coef: DEFAULT_COEF,           // ← 10
         q1_tokens: 0,                 // ← нули
         sum1_bonus: 0,
@@ -116,8 +223,8 @@ pub fn init(ctx: Context<Init>) -> Result<()> {
     };
 
 
-    // Сериализуем в 28 байт.
-    let data = serialize_invest_state_v1(&state);
+    // Сериализуем (V2, 60 байт).
+    let data = serialize_invest_state(&state)?;
 
     // Для подписи PDA нужен bump; здесь получим (ключ, bump).
     let (_pda_key, bump) = Pubkey::find_program_address(&[PDA_SEED_PREFIX], program_id);
@@ -133,71 +240,367 @@ pub fn init(ctx: Context<Init>) -> Result<()> {
         program_id,
         &seeds,
         data,
-        PAY_STATE_SPACE,                          // 28 байт
+        PAY_STATE_SPACE,                          // V2 + запас
+    )?;
+
+    // 3. Создаём vault (отдельный PDA без данных)
+    create_vault(
+        &ctx.accounts.vault_pda.to_account_info(),
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        program_id,
     )?;
 
     Ok(())
 }
 
 /// ------------------------------------------
-/// invest: «внос инвестиций».
-/// По заданию: в начале читаем состояние, в конце сохраняем.
-/// (Здесь логика модификации не задана — оставляем как заглушку.)
+/// init_vault: создаёт vault отдельно — для состояния, созданного до появления vault.
+/// Только администратор настроек выплат.
 /// ------------------------------------------
-pub fn invest(ctx: Context<UseState>, _amount: u64) -> Result<()> {
-    // 1) читаем
-    let mut st = read_state_from_pda(&ctx.accounts.state_pda.to_account_info())?; // ← PayStateV1
+pub fn init_vault(ctx: Context<InitVault>) -> Result<()> {
+    require_payments_admin(&ctx.accounts.payer, &ctx.accounts.config_pda, ctx.program_id)?;
+    create_vault(
+        &ctx.accounts.vault_pda.to_account_info(),
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+    )
+}
 
-    // --- тут можно модифицировать st по твоей бизнес-логике ---
-    // Например, ничего не меняем сейчас (заглушка).
-    let _ = &mut st; // чтоб компилятор не ругался, если пока не используем
+/// Проверяет, что подписант — администратор из настроек выплат
+fn require_payments_admin(signer: &Signer, config_pda: &UncheckedAccount, program_id: &Pubkey) -> Result<()> {
+    let config = read_payments_config(&config_pda.to_account_info(), program_id)?;
+    require_keys_eq!(signer.key(), config.admin, ErrCode::InvalidSigner);
+    Ok(())
+}
+
+/// Создаёт vault: PDA без данных, владелец — программа.
+/// Его рента (минимум для 0 байт) в средства инвесторов не входит.
+fn create_vault<'info>(
+    vault: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let (expected_vault, bump) = Pubkey::find_program_address(&[VAULT_SEED_PREFIX], program_id);
+    require_keys_eq!(expected_vault, vault.key(), ErrCode::InvalidPdaAddress);
+    create_pda(vault, payer, system_program, program_id, &[VAULT_SEED_PREFIX, &[bump]], 0)
+}
+
+/// Проверяет адрес и владельца vault
+fn check_vault_pda(vault: &AccountInfo, program_id: &Pubkey) -> Result<()> {
+    let (expected_vault, _bump) = Pubkey::find_program_address(&[VAULT_SEED_PREFIX], program_id);
+    require_keys_eq!(expected_vault, vault.key(), ErrCode::InvalidPdaAddress);
+    require!(vault.owner == program_id, ErrCode::EmptyPdaData); // vault ещё не создан
+    Ok(())
+}
 
-    // 2) сохраняем
-    write_state_to_pda(&ctx.accounts.state_pda.to_account_info(), &st)?;
+/// Проверяет адрес PDA состояния
+fn check_state_pda(state: &AccountInfo, program_id: &Pubkey) -> Result<()> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(&[PDA_SEED_PREFIX], program_id);
+    require_keys_eq!(expected_pda, state.key(), ErrCode::InvalidPdaAddress);
     Ok(())
 }
 
 /// ------------------------------------------
-/// add_bonus: «начисление бонусов» (обычно вызывать от DAO).
-/// По заданию: читаем в начале, сохраняем в конце.
+/// invest: «внос инвестиций».
+/// Лампорты переводятся в vault и конвертируются в токены очереди 1:
+/// tokens = amount / coef (coef — лампортов за токен).
+/// Списывается ровно tokens * coef, остаток от деления остаётся у инвестора.
+/// Сумма меньше coef (0 токенов) — пыль, отклоняется.
+/// Токены встают в конец очереди (см. enqueue_tokens).
 /// ------------------------------------------
-pub fn add_bonus(ctx: Context<UseState>, _investor: Pubkey, _coef: u64) -> Result<()> {
+pub fn invest(ctx: Context<Invest>, amount: u64) -> Result<()> {
+    let program_id = ctx.program_id;
+    let state_pda = ctx.accounts.state_pda.to_account_info();
+    let vault_pda = ctx.accounts.vault_pda.to_account_info();
+    check_state_pda(&state_pda, program_id)?;
+    check_vault_pda(&vault_pda, program_id)?;
+
     // 1) читаем
-    let mut st = read_state_from_pda(&ctx.accounts.state_pda.to_account_info())?;
+    let mut st = read_state_from_pda(&state_pda)?;
+    require!(st.coef > 0, ErrCode::InvalidCoef);
+
+    // 2) конвертируем лампорты в токены
+    let tokens = amount / st.coef;
+    require!(tokens > 0, ErrCode::AmountTooSmall);
+    let lamports = tokens * st.coef; // не больше amount — переполнения нет
+
+    // 3) ставим токены в очередь 1 и в позицию инвестора (или создаём новую);
+    //    для записи V1 переполнение u32 проверит write_state_to_pda
+    let investor = ctx.accounts.investor.to_account_info();
+    let position_pda = ctx.accounts.position_pda.to_account_info();
+    let now = Clock::get()?.unix_timestamp;
+    let mut position = read_or_new_position(&position_pda, investor.key, program_id, now)?;
+    enqueue_tokens(&mut st, &mut position, tokens, lamports)?;
+    position.last_invest_at = now;
+
+    // 4) переводим лампорты инвестора в vault
+    let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+        ctx.accounts.investor.key,
+        vault_pda.key,
+        lamports,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &transfer_instruction,
+        &[
+            ctx.accounts.investor.to_account_info(),
+            vault_pda.clone(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+    )?;
 
-    // --- здесь можно добавить логику корректировки sum1_bonus и т.п. ---
-    let _ = (&mut st, _investor, _coef); // заглушка, чтобы не было warning
+    // 5) сохраняем
+    write_state_to_pda(&state_pda, &st)?;
+    save_position(
+        &position_pda,
+        &investor,
+        &ctx.accounts.system_program.to_account_info(),
+        program_id,
+        &position,
+    )?;
+    msg!("💰 Инвестиция {} лампортов → {} токенов в очереди 1", lamports, tokens);
+    Ok(())
+}
 
-    // 2) сохраняем
-    write_state_to_pda(&ctx.accounts.state_pda.to_account_info(), &st)?;
+/// Ставит `tokens` токенов (вложено `lamports`) в конец очереди 1 и в позицию.
+/// Невыплаченные токены позиции должны занимать в очереди один непрерывный
+/// отрезок, поэтому докупить можно, только если позиция полностью выплачена
+/// или после её последней инвестиции в очередь никто не вставал;
+/// иначе — UnpaidTokensInQueue (сначала claim).
+pub fn enqueue_tokens(st: &mut InvestState, position: &mut InvestorPosition, tokens: u64, lamports: u64) -> Result<()> {
+    let owed_tokens = position.tokens.checked_sub(position.paid_tokens).ok_or(ErrCode::ArithmeticOverflow)?;
+    require!(owed_tokens == 0 || position.queue_end == st.q1_tokens, ErrCode::UnpaidTokensInQueue);
+
+    st.q1_tokens = st.q1_tokens.checked_add(tokens).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.principal = position.principal.checked_add(lamports).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.tokens = position.tokens.checked_add(tokens).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.queue_end = st.q1_tokens;
     Ok(())
 }
 
 /// ------------------------------------------
-/// claim: «выплата».
-/// По заданию: читаем в начале, сохраняем в конце.
+/// add_bonus: «начисление бонусов» — только ключ бонусов из настроек (DAO).
+/// `amount` лампортов добавляется к sum1_bonus и к bonus_accrued инвестора
+/// и в той же инструкции переводится с ключа бонусов в vault. Так резерв
+/// бонусов (sum1_bonus - sum1_paid_bonus) всегда обеспечен и бонусы
+/// не выплачиваются из вложений других инвесторов (см. compute_claimable).
 /// ------------------------------------------
-pub fn claim(ctx: Context<UseState>) -> Result<()> {
-    // 1) читаем
-    let mut st = read_state_from_pda(&ctx.accounts.state_pda.to_account_info())?;
+pub fn add_bonus(ctx: Context<AddBonus>, investor: Pubkey, amount: u64) -> Result<()> {
+    let program_id = ctx.program_id;
+    let state_pda = ctx.accounts.state_pda.to_account_info();
+    let position_pda = ctx.accounts.position_pda.to_account_info();
+    let vault_pda = ctx.accounts.vault_pda.to_account_info();
+
+    // 1) проверяем, что подписал ключ бонусов
+    let config = read_payments_config(&ctx.accounts.config_pda.to_account_info(), program_id)?;
+    require_keys_eq!(ctx.accounts.authority.key(), config.bonus_authority, ErrCode::InvalidSigner);
+    require!(amount > 0, ErrCode::AmountTooSmall);
+
+    // 2) читаем
+    check_state_pda(&state_pda, program_id)?;
+    check_vault_pda(&vault_pda, program_id)?;
+    let mut st = read_state_from_pda(&state_pda)?;
+    let mut position = read_position(&position_pda, &investor, program_id)?;
+
+    // 3) начисляем
+    st.sum1_bonus = st.sum1_bonus.checked_add(amount as u128).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.bonus_accrued = position.bonus_accrued.checked_add(amount).ok_or(ErrCode::ArithmeticOverflow)?;
+
+    // 4) переводим сумму бонуса с ключа бонусов в vault
+    let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+        ctx.accounts.authority.key,
+        vault_pda.key,
+        amount,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &transfer_instruction,
+        &[
+            ctx.accounts.authority.to_account_info(),
+            vault_pda.clone(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+    )?;
 
-    // --- тут твоя логика списаний/выплат ---
-    let _ = &mut st; // заглушка
+    // 5) сохраняем
+    write_state_to_pda(&state_pda, &st)?;
+    write_to_pda(&position_pda, &position.encode())?;
+    msg!("🎁 Бонус {} лампортов начислен инвестору {}", amount, investor);
+    Ok(())
+}
 
-    // 2) сохраняем
-    write_state_to_pda(&ctx.accounts.state_pda.to_account_info(), &st)?;
+/// ------------------------------------------
+/// Сколько инвестор может получить прямо сейчас.
+///
+/// Очередь 1 обслуживается по порядку: токен с номером n выплачивается,
+/// только когда денег в vault хватает на все невыплаченные токены перед ним.
+///   - бонусы: (bonus_accrued - bonus_claimed), но не больше резерва бонусов
+///     (sum1_bonus - sum1_paid_bonus) — бонусные лампорты лежат в vault отдельно;
+///   - вложения: невыплаченные токены позиции занимают в очереди номера
+///     [queue_end - owed_tokens, queue_end); перед ними стоит
+///     tokens_ahead = (queue_end - owed_tokens) - q1_paid_tokens токенов.
+///     Из средств vault сверх резерва бонусов хватает на funded = средства / coef
+///     токенов, инвестору достаётся min(owed_tokens, funded - tokens_ahead),
+///     по coef лампортов за токен.
+///
+/// `vault_available` — баланс vault сверх его ренты.
+/// ------------------------------------------
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Claimable {
+    /// Бонусы, которые можно выплатить.
+    pub bonus: u64,
+    /// Вложенные лампорты, которые можно вернуть.
+    pub principal: u64,
+    /// Токены очереди, которым соответствует `principal`.
+    pub tokens: u64,
+}
+
+pub fn compute_claimable(st: &InvestState, position: &InvestorPosition, vault_available: u64) -> Result<Claimable> {
+    require!(st.coef > 0, ErrCode::InvalidCoef);
+
+    // бонусы — из резерва бонусов
+    let bonus_reserve = st.sum1_bonus.checked_sub(st.sum1_paid_bonus).ok_or(ErrCode::ArithmeticOverflow)?;
+    let owed_bonus = position.bonus_accrued.checked_sub(position.bonus_claimed).ok_or(ErrCode::ArithmeticOverflow)?;
+    let bonus = (owed_bonus as u128).min(bonus_reserve).min(vault_available as u128) as u64;
+
+    // вложения — по месту в очереди, целыми токенами по coef лампортов
+    let owed_tokens = position.tokens.checked_sub(position.paid_tokens).ok_or(ErrCode::ArithmeticOverflow)?;
+    let principal_funds = (vault_available as u128).saturating_sub(bonus_reserve) as u64;
+    let funded_tokens = principal_funds / st.coef;
+    let queue_start = position.queue_end.saturating_sub(owed_tokens);
+    let tokens_ahead = queue_start.saturating_sub(st.q1_paid_tokens);
+    let tokens = owed_tokens.min(funded_tokens.saturating_sub(tokens_ahead));
+    let principal = tokens.checked_mul(st.coef).ok_or(ErrCode::ArithmeticOverflow)?;
+
+    Ok(Claimable { bonus, principal, tokens })
+}
+
+/// План выплаты: (бонусы, вложения, токены).
+/// `amount = None` — всё доступное, `Some(x)` — частично. Сначала бонусы,
+/// затем вложения; вложения выплачиваются только целыми токенами, поэтому
+/// часть `x` сверх бонусов должна делиться на coef (иначе ClaimNotWholeTokens) —
+/// так выплаченные лампорты и выплаченные токены очереди всегда сходятся.
+pub fn plan_claim(
+    st: &InvestState,
+    position: &InvestorPosition,
+    vault_available: u64,
+    amount: Option<u64>,
+) -> Result<(u64, u64, u64)> {
+    let claimable = compute_claimable(st, position, vault_available)?;
+    let total = claimable.bonus.checked_add(claimable.principal).ok_or(ErrCode::ArithmeticOverflow)?;
+    require!(total > 0, ErrCode::NothingToClaim);
+
+    let payout = amount.unwrap_or(total);
+    require!(payout > 0, ErrCode::NothingToClaim);
+    require!(payout <= total, ErrCode::ClaimExceedsOwed);
+
+    let bonus_part = payout.min(claimable.bonus);
+    let principal_part = payout - bonus_part;
+    require!(principal_part.is_multiple_of(st.coef), ErrCode::ClaimNotWholeTokens);
+    Ok((bonus_part, principal_part, principal_part / st.coef))
+}
+
+/// Записывает выплату в позицию и двигает счётчики выплаченного
+/// (с проверкой переполнения; выплаченное не больше поставленного в очередь).
+pub fn apply_claim(
+    st: &mut InvestState,
+    position: &mut InvestorPosition,
+    bonus_part: u64,
+    principal_part: u64,
+    tokens_part: u64,
+) -> Result<()> {
+    position.bonus_claimed = position.bonus_claimed.checked_add(bonus_part).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.paid_principal = position.paid_principal.checked_add(principal_part).ok_or(ErrCode::ArithmeticOverflow)?;
+    position.paid_tokens = position.paid_tokens.checked_add(tokens_part).ok_or(ErrCode::ArithmeticOverflow)?;
+    require!(position.bonus_claimed <= position.bonus_accrued, ErrCode::ClaimExceedsOwed);
+    require!(position.paid_principal <= position.principal, ErrCode::ClaimExceedsOwed);
+    require!(position.paid_tokens <= position.tokens, ErrCode::ClaimExceedsOwed);
+
+    st.sum1_paid_bonus = st.sum1_paid_bonus.checked_add(bonus_part as u128).ok_or(ErrCode::ArithmeticOverflow)?;
+    st.q1_paid_tokens = st.q1_paid_tokens.checked_add(tokens_part).ok_or(ErrCode::ArithmeticOverflow)?;
+    require!(st.sum1_paid_bonus <= st.sum1_bonus, ErrCode::ClaimExceedsOwed);
+    require!(st.q1_paid_tokens <= st.q1_tokens, ErrCode::ClaimExceedsOwed);
     Ok(())
 }
 
+/// ------------------------------------------
+/// claim: «выплата» по очереди 1.
+/// Сумма к выплате считается в compute_claimable из позиции, места в очереди
+/// и глобальных счётчиков. Порядок выплаты: сначала бонусы, затем вложения
+/// целыми токенами по coef лампортов (см. plan_claim).
+/// `amount = None` — всё доступное, `Some(x)` — частично, не больше доступного.
+/// Позиция и счётчики обновляются до перевода, поэтому повторный вызов
+/// (в том числе в той же транзакции) видит уже уменьшенный остаток.
+/// ------------------------------------------
+pub fn claim(ctx: Context<Claim>, amount: Option<u64>) -> Result<()> {
+    let program_id = ctx.program_id;
+    let state_pda = ctx.accounts.state_pda.to_account_info();
+    let vault_pda = ctx.accounts.vault_pda.to_account_info();
+    let investor = ctx.accounts.investor.to_account_info();
+    check_state_pda(&state_pda, program_id)?;
+    check_vault_pda(&vault_pda, program_id)?;
+
+    // 1) читаем
+    let mut st = read_state_from_pda(&state_pda)?;
+    let position_pda = ctx.accounts.position_pda.to_account_info();
+    let mut position = read_position(&position_pda, investor.key, program_id)?;
+
+    // 2) сколько доступно сейчас (средства vault сверх его ренты)
+    let vault_rent = Rent::get()?.minimum_balance(0);
+    let available = vault_pda.lamports().saturating_sub(vault_rent);
 
+    // 3) делим выплату: сначала бонусы, потом вложения целыми токенами
+    let (bonus_part, principal_part, tokens_part) = plan_claim(&st, &position, available, amount)?;
+    let payout = bonus_part + principal_part; // оба не больше доступного в vault
 
+    // 4) обновляем позицию и счётчики выплаченного
+    apply_claim(&mut st, &mut position, bonus_part, principal_part, tokens_part)?;
 
+    // 5) сохраняем
+    write_state_to_pda(&state_pda, &st)?;
+    write_to_pda(&position_pda, &position.encode())?;
 
-//todo
+    // 6) переводим лампорты: vault принадлежит программе, списываем напрямую
+    let vault_balance = vault_pda.lamports();
+    let investor_balance = investor.lamports();
+    **vault_pda.try_borrow_mut_lamports()? = vault_balance.checked_sub(payout).ok_or(ErrCode::InsufficientVaultFunds)?;
+    **investor.try_borrow_mut_lamports()? = investor_balance.checked_add(payout).ok_or(ErrCode::ArithmeticOverflow)?;
 
+    msg!("💸 Выплачено {} лампортов (бонусы {}, вложения {}, токенов {})", payout, bonus_part, principal_part, tokens_part);
+    Ok(())
+}
 
+/// ------------------------------------------
+/// migrate_invest_state: перевод состояния V1 → V2 на месте.
+/// Значения не меняются, только ширина полей; PDA увеличивается до PAY_STATE_SPACE.
+/// Для записи, уже лежащей в V2, ничего не делает.
+/// ------------------------------------------
+pub fn migrate_invest_state(ctx: Context<MigrateInvestState>) -> Result<()> {
+    let state_pda = ctx.accounts.state_pda.to_account_info();
+    check_state_pda(&state_pda, ctx.program_id)?;
+    require!(state_pda.owner == ctx.program_id, ErrCode::EmptyPdaData);
+
+    let mut st = read_state_from_pda(&state_pda)?;
+    if st.format == INVEST_STATE_FORMAT_V2 {
+        msg!("✔️ Состояние уже в формате V2");
+        return Ok(());
+    }
 
+    if state_pda.data_len() < PAY_STATE_SPACE as usize {
+        realloc_pda(
+            &state_pda,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            PAY_STATE_SPACE as usize,
+        )?;
+    }
+    st.format = INVEST_STATE_FORMAT_V2;
+    write_state_to_pda(&state_pda, &st)?;
 
+    msg!("🔁 Состояние выплат: формат V1 → V2");
+    Ok(())
+}
 
 /// ==============================================
 /// Коды ошибок (берём из твоего блока; можно расширять)
@@ -237,60 +640,89 @@ pub enum ErrCode {
     /// Не получилось создать пользователя
     #[msg("Не получилось создать пользователя, система уже перегружена, попробуйте позже!")]
     NoSuitableIdPda = 1010,
-}
 
+    #[msg("Сумма слишком мала: меньше одного токена очереди")]
+    AmountTooSmall = 1100,
+
+    #[msg("Коэффициент должен быть больше нуля")]
+    InvalidCoef = 1101,
+
+    #[msg("Арифметическое переполнение")]
+    ArithmeticOverflow = 1102,
+
+    #[msg("Нечего выплачивать")]
+    NothingToClaim = 1103,
+
+    #[msg("Запрошено больше, чем причитается")]
+    ClaimExceedsOwed = 1104,
+
+    #[msg("В хранилище недостаточно средств для выплаты")]
+    InsufficientVaultFunds = 1105,
+
+    #[msg("В позиции есть невыплаченные токены, а за ними в очереди уже стоят другие: сначала получите выплату")]
+    UnpaidTokensInQueue = 1106,
+
+    #[msg("Вложения выплачиваются только целыми токенами: сумма сверх бонусов должна делиться на коэффициент")]
+    ClaimNotWholeTokens = 1107,
+}
 
 
 
 
-use anchor_lang::prelude::*;
 
 /// ================================
 /// КОНСТАНТЫ ФОРМАТА / ДЛИНЫ ДАННЫХ
 /// ================================
-
-/// Версия формата хранения состояния.
-/// Мы жёстко фиксируем «1», чтобы код мог отличать будущие версии.
+///
+/// Версия формата хранения состояния V1: все поля u32.
+/// Переполняется примерно на 4.29 SOL в лампортах — только для чтения старых PDA.
 pub const INVEST_STATE_FORMAT_V1: u32 = 1;
 
 /// Сырые данные состояния V1 занимают ровно 6 * 4 = 24 байта.
 /// Почему 6? Потому что у нас 6 полей по 4 байта (u32).
 pub const INVEST_STATE_RAW_LEN_V1: usize = 24; // байт
 
+/// Версия формата V2: счётчики токенов u64, суммы бонусов u128.
+pub const INVEST_STATE_FORMAT_V2: u32 = 2;
+
+/// Сырые данные V2: 4 (format) + 8 (coef) + 8 + 16 + 8 + 16 = 60 байт.
+pub const INVEST_STATE_RAW_LEN_V2: usize = 60; // байт
+
+/// Место под PDA состояния V2: 60 байт данных + запас под новые поля.
+pub const INVEST_STATE_SPACE_V2: usize = 128; // байт
+
 /// ================================
-/// ОПИСАНИЕ СТРУКТУРЫ СОСТОЯНИЯ (V1)
+/// ОПИСАНИЕ СТРУКТУРЫ СОСТОЯНИЯ
 /// ================================
 /// Мы храним глобальные агрегаты по выплатам в одном PDA.
-/// Каждый элемент — 32-битное беззнаковое число (u32), Little Endian.
+/// В памяти поля всегда широкие (как в V2); при чтении V1 значения расширяются.
 ///
 /// ПОЛЯ:
-///  1) format          — версия формата (всегда 1 для этого кода)
-///  2) coef            — коэффициент (по умолчанию 10, но можно менять)
-///  3) q1_tokens       — сколько токенов стоит в очереди на выплату (1-я очередь)
-///  4) sum1_bonus      — общая сумма «бонусов», которые нужно выплатить по 1-й очереди
+///  1) format          — версия формата, в которой запись лежит в PDA (1 или 2)
+///  2) coef            — коэффициент: лампортов за один токен очереди (по умолчанию 10)
+///  3) q1_tokens       — сколько токенов поставлено в 1-ю очередь (накопительно)
+///  4) sum1_bonus      — общая сумма «бонусов», начисленных по 1-й очереди (накопительно)
 ///  5) q1_paid_tokens  — сколько токенов уже выплачено по 1-й очереди (счётчик выполненного)
 ///  6) sum1_paid_bonus — какая сумма «бонусов» уже выплачена по 1-й очереди
-///
-/// Итого: 6 полей * 4 байта = 24 байта.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct InvestState {
-    /// Версия формата, должна быть INVEST_STATE_FORMAT_V1 (то есть 1).
+    /// Версия формата в PDA: INVEST_STATE_FORMAT_V1 или INVEST_STATE_FORMAT_V2.
     pub format: u32,
 
-    /// Текущий коэффициент (ваша бизнес-логика; например, 10 при инициализации).
-    pub coef: u32,
+    /// Текущий коэффициент (лампортов за токен; 10 при инициализации).
+    pub coef: u64,
 
-    /// Кол-во токенов в 1-й очереди, ожидающих выплаты.
-    pub q1_tokens: u32,
+    /// Кол-во токенов, поставленных в 1-ю очередь.
+    pub q1_tokens: u64,
 
-    /// Сумма бонусов, подлежащая выплате по 1-й очереди (ещё не выплачено).
-    pub sum1_bonus: u32,
+    /// Сумма бонусов, начисленных по 1-й очереди.
+    pub sum1_bonus: u128,
 
     /// Сколько токенов уже выплачено по 1-й очереди (накопительный счётчик).
-    pub q1_paid_tokens: u32,
+    pub q1_paid_tokens: u64,
 
     /// Какая сумма бонусов уже выплачена по 1-й очереди (накопительный счётчик).
-    pub sum1_paid_bonus: u32,
+    pub sum1_paid_bonus: u128,
 }
 
 
@@ -302,84 +734,233 @@ pub struct InvestState {
 
 
 /// ========================================
-/// СЕРИАЛИЗАЦИЯ (структура -> массив байт)
+/// СЕРИАЛИЗАЦИЯ / ДЕСЕРИАЛИЗАЦИЯ (через VersionedAccount)
 /// ========================================
-/// Мы вручную упаковываем каждое поле в 4 байта в порядке Little Endian.
-/// ПОЛНЫЙ РАЗМЕР: ровно 24 байта.
-/// ПОРЯДОК ПОЛЕЙ (по 4 байта каждое):
-///   [0..4)  format
-///   [4..8)  coef
-///   [8..12) q1_tokens
+/// Все числа — Little Endian.
+///
+/// V1 (24 байта, по 4 байта каждое):
+///   [0..4)   format
+///   [4..8)   coef
+///   [8..12)  q1_tokens
 ///   [12..16) sum1_bonus
 ///   [16..20) q1_paid_tokens
 ///   [20..24) sum1_paid_bonus
-pub fn serialize_invest_state_v1(s: &InvestState) -> Vec<u8> {
-    // Для людей без опыта в Rust:
-    // Vec<u8> — это "динамический массив байт".
-    // Мы заранее резервируем 24 байта, чтобы не делать лишних перераспределений.
-    let mut out = Vec::with_capacity(INVEST_STATE_RAW_LEN_V1);
-
-    // Нормируем версию: даже если в поле format «что-то другое»,
-    // мы пишем именно константу версии, чтобы на чейне хранилась корректная метка формата.
-    out.extend_from_slice(&INVEST_STATE_FORMAT_V1.to_le_bytes()); // [0..4)
-
-    // Далее — остальные поля как есть, по 4 байта LE каждое.
-    out.extend_from_slice(&s.coef.to_le_bytes());            // [4..8)
-    out.extend_from_slice(&s.q1_tokens.to_le_bytes());       // [8..12)
-    out.extend_from_slice(&s.sum1_bonus.to_le_bytes());      // [12..16)
-    out.extend_from_slice(&s.q1_paid_tokens.to_le_bytes());  // [16..20)
-    out.extend_from_slice(&s.sum1_paid_bonus.to_le_bytes()); // [20..24)
-
-    // Итоговая длина должна быть ровно 24 байта.
-    debug_assert_eq!(out.len(), INVEST_STATE_RAW_LEN_V1);
-    out
-}
-
-/// ===========================================
-/// ДЕСЕРИАЛИЗАЦИЯ (массив байт -> структура)
-/// ===========================================
-/// На вход подаём срез байт `data`, ожидаем минимум 24 байта.
-/// 1) Проверяем, что данных хватает.
-/// 2) Считываем первые 4 байта как `format` и убеждаемся, что это версия 1.
-/// 3) Последовательно читаем остальные 5 чисел по 4 байта (LE) каждое.
-/// 4) Возвращаем заполненную структуру.
+///
+/// V2 (60 байт):
+///   [0..4)   format
+///   [4..12)  coef             (u64)
+///   [12..20) q1_tokens        (u64)
+///   [20..36) sum1_bonus       (u128)
+///   [36..44) q1_paid_tokens   (u64)
+///   [44..60) sum1_paid_bonus  (u128)
+///
+/// `encode` пишет всегда V2; запись в V1 — только через serialize_invest_state
+/// для PDA, которые ещё не мигрированы.
+impl VersionedAccount for InvestState {
+    const LATEST_FORMAT: u32 = INVEST_STATE_FORMAT_V2;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            // Читаем остальные 5 чисел по 4 байта; курсор сам проверяет,
+            // что данных хватает (иначе — DeserializationError, без паники).
+            INVEST_STATE_FORMAT_V1 => Ok(InvestState {
+                format,
+                coef: reader.read_u32()? as u64,
+                q1_tokens: reader.read_u32()? as u64,
+                sum1_bonus: reader.read_u32()? as u128,
+                q1_paid_tokens: reader.read_u32()? as u64,
+                sum1_paid_bonus: reader.read_u32()? as u128,
+            }),
+            INVEST_STATE_FORMAT_V2 => Ok(InvestState {
+                format,
+                coef: reader.read_u64()?,
+                q1_tokens: reader.read_u64()?,
+                sum1_bonus: reader.read_u128()?,
+                q1_paid_tokens: reader.read_u64()?,
+                sum1_paid_bonus: reader.read_u128()?,
+            }),
+            // Если формат другой — значит это не поддерживаемая версия.
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.coef);             // [4..12)
+        writer.write_u64(self.q1_tokens);        // [12..20)
+        writer.write_u128(self.sum1_bonus);      // [20..36)
+        writer.write_u64(self.q1_paid_tokens);   // [36..44)
+        writer.write_u128(self.sum1_paid_bonus); // [44..60)
+    }
+
+    // upgrade_from не нужен: при чтении V1 поле `format` остаётся 1,
+    // чтобы запись в PDA шла в том же формате до миграции.
+}
+
+/// Структура -> байты в формате `s.format`.
+/// Для V1 значения сужаются до u32; не помещаются — ArithmeticOverflow (нужна миграция).
+pub fn serialize_invest_state(s: &InvestState) -> Result<Vec<u8>> {
+    match s.format {
+        INVEST_STATE_FORMAT_V1 => serialize_invest_state_v1(s),
+        INVEST_STATE_FORMAT_V2 => {
+            let out = s.encode();
+            debug_assert_eq!(out.len(), INVEST_STATE_RAW_LEN_V2);
+            Ok(out)
+        }
+        _ => Err(error!(ErrCode::UnsupportedFormat)),
+    }
+}
+
+/// Структура -> 24 байта (формат V1)
+pub fn serialize_invest_state_v1(s: &InvestState) -> Result<Vec<u8>> {
+    let narrow = |value: u128| u32::try_from(value).map_err(|_| error!(ErrCode::ArithmeticOverflow));
+    let mut writer = ByteWriter::with_capacity(INVEST_STATE_RAW_LEN_V1);
+    writer.write_u32(INVEST_STATE_FORMAT_V1);
+    writer.write_u32(narrow(s.coef as u128)?);
+    writer.write_u32(narrow(s.q1_tokens as u128)?);
+    writer.write_u32(narrow(s.sum1_bonus)?);
+    writer.write_u32(narrow(s.q1_paid_tokens as u128)?);
+    writer.write_u32(narrow(s.sum1_paid_bonus)?);
+    Ok(writer.into_inner())
+}
+
+/// Массив байт -> структура (версия определяется по первым 4 байтам)
 pub fn deserialize_invest_state(data: &[u8]) -> Result<InvestState> {
-    // 1) Проверяем длину. Если меньше 24 байт — данных недостаточно.
-    if data.len() < INVEST_STATE_RAW_LEN_V1 {
-        return Err(error!(ErrCode::DeserializationError));
+    InvestState::decode(data)
+}
+
+
+
+
+
+
+
+
+
+/// ================================
+/// ПОЗИЦИЯ ИНВЕСТОРА (PDA [POSITION_SEED_PREFIX, investor])
+/// ================================
+///
+/// Версия формата позиции инвестора.
+pub const INVESTOR_POSITION_FORMAT_V1: u32 = 1;
+
+/// Размер V1: 4 (format) + 32 (investor) + 7 * 8 (u64) + 2 * 8 (i64) = 108 байт
+pub const INVESTOR_POSITION_SIZE_V1: usize = 108;
+
+/// Что вложил и что получил конкретный инвестор.
+/// Суммы по всем позициям сходятся с глобальными счётчиками InvestState:
+/// Σ tokens = q1_tokens, Σ paid_tokens = q1_paid_tokens,
+/// Σ bonus_accrued = sum1_bonus, Σ bonus_claimed = sum1_paid_bonus.
+///
+/// ПОРЯДОК ПОЛЕЙ:
+///   [0..4)    format
+///   [4..36)   investor
+///   [36..44)  principal        — сколько лампортов вложено (накопительно)
+///   [44..52)  tokens           — сколько токенов куплено в очередь 1 (накопительно)
+///   [52..60)  paid_principal   — сколько вложенных лампортов уже возвращено
+///   [60..68)  paid_tokens      — сколько токенов уже выплачено
+///   [68..76)  bonus_accrued    — начислено бонусов (накопительно)
+///   [76..84)  bonus_claimed    — выплачено бонусов (накопительно)
+///   [84..92)  first_invest_at  — время первой инвестиции (unix)
+///   [92..100) last_invest_at   — время последней инвестиции (unix)
+///   [100..108) queue_end       — q1_tokens сразу после последней инвестиции:
+///                                невыплаченные токены позиции стоят в очереди
+///                                перед этим номером
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InvestorPosition {
+    pub investor: Pubkey,
+    pub principal: u64,
+    pub tokens: u64,
+    pub paid_principal: u64,
+    pub paid_tokens: u64,
+    pub bonus_accrued: u64,
+    pub bonus_claimed: u64,
+    pub first_invest_at: i64,
+    pub last_invest_at: i64,
+    pub queue_end: u64,
+}
+
+impl VersionedAccount for InvestorPosition {
+    const LATEST_FORMAT: u32 = INVESTOR_POSITION_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            INVESTOR_POSITION_FORMAT_V1 => Ok(InvestorPosition {
+                investor: reader.read_pubkey()?,
+                principal: reader.read_u64()?,
+                tokens: reader.read_u64()?,
+                paid_principal: reader.read_u64()?,
+                paid_tokens: reader.read_u64()?,
+                bonus_accrued: reader.read_u64()?,
+                bonus_claimed: reader.read_u64()?,
+                first_invest_at: reader.read_i64()?,
+                last_invest_at: reader.read_i64()?,
+                queue_end: reader.read_u64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
     }
 
-    // Вспомогательная функция: безопасно читает 4 байта как u32 в Little Endian
-    // из указанного диапазона [start..start+4).
-    fn read_u32_le(slice: &[u8], start: usize) -> u32 {
-        // Здесь используем get(..) + try_into(), чтобы не паниковать при неверных индексах.
-        let bytes: [u8; 4] = slice[start..start + 4]
-            .try_into()
-            .expect("slice has enough length due to pre-check");
-        u32::from_le_bytes(bytes)
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.investor);
+        writer.write_u64(self.principal);
+        writer.write_u64(self.tokens);
+        writer.write_u64(self.paid_principal);
+        writer.write_u64(self.paid_tokens);
+        writer.write_u64(self.bonus_accrued);
+        writer.write_u64(self.bonus_claimed);
+        writer.write_i64(self.first_invest_at);
+        writer.write_i64(self.last_invest_at);
+        writer.write_u64(self.queue_end);
     }
+}
 
-    // 2) Читаем и проверяем версию формата.
-    let format = read_u32_le(data, 0);
-    if format != INVEST_STATE_FORMAT_V1 {
-        // Если формат другой — значит это не поддерживаемая версия.
-        return Err(error!(ErrCode::UnsupportedFormat));
+/// Адрес и bump PDA позиции инвестора
+pub fn position_pda_address(investor: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POSITION_SEED_PREFIX, investor.as_ref()], program_id)
+}
+
+/// Читает существующую позицию (адрес проверяется по инвестору)
+fn read_position(pda: &AccountInfo, investor: &Pubkey, program_id: &Pubkey) -> Result<InvestorPosition> {
+    require_keys_eq!(position_pda_address(investor, program_id).0, pda.key(), ErrCode::InvalidPdaAddress);
+    require!(pda.owner == program_id, ErrCode::EmptyPdaData);
+    let position = InvestorPosition::decode(&safe_read_pda(pda))?;
+    require_keys_eq!(position.investor, *investor, ErrCode::InvalidPdaAddress);
+    Ok(position)
+}
+
+/// Читает позицию или возвращает новую (PDA ещё не создан)
+fn read_or_new_position(pda: &AccountInfo, investor: &Pubkey, program_id: &Pubkey, now: i64) -> Result<InvestorPosition> {
+    if pda.owner == &Pubkey::default() {
+        require_keys_eq!(position_pda_address(investor, program_id).0, pda.key(), ErrCode::InvalidPdaAddress);
+        return Ok(InvestorPosition {
+            investor: *investor,
+            first_invest_at: now,
+            ..Default::default()
+        });
     }
+    read_position(pda, investor, program_id)
+}
 
-    // 3) Читаем остальные поля по 4 байта.
-    let coef            = read_u32_le(data, 4);
-    let q1_tokens       = read_u32_le(data, 8);
-    let sum1_bonus      = read_u32_le(data, 12);
-    let q1_paid_tokens  = read_u32_le(data, 16);
-    let sum1_paid_bonus = read_u32_le(data, 20);
-
-    // 4) Собираем структуру и возвращаем её.
-    Ok(InvestState {
-        format,
-        coef,
-        q1_tokens,
-        sum1_bonus,
-        q1_paid_tokens,
-        sum1_paid_bonus,
-    })
+/// Сохраняет позицию; при первой инвестиции создаёт PDA (ренту платит инвестор)
+fn save_position<'info>(
+    pda: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    position: &InvestorPosition,
+) -> Result<()> {
+    let data = position.encode();
+    if pda.owner == &Pubkey::default() {
+        let (_key, bump) = position_pda_address(&position.investor, program_id);
+        return create_and_write_pda(
+            pda,
+            payer,
+            system_program,
+            program_id,
+            &[POSITION_SEED_PREFIX, position.investor.as_ref(), &[bump]],
+            data,
+            INVESTOR_POSITION_SIZE_V1 as u64,
+        );
+    }
+    write_to_pda(pda, &data)
 }
@@ -1,3 +1,6 @@
+// Anchor 0.31 внутри #[program] вызывает устаревший AccountInfo::realloc
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
 
 declare_id!("92sgkgx7KHpbhQu81mNGHaKa7skJB7esArVdPM7paDSW");
@@ -7,27 +10,35 @@ declare_id!("92sgkgx7KHpbhQu81mNGHaKa7skJB7esArVdPM7paDSW");
 pub mod investments;
 use investments::*; // импортируем всё в корень
 
+/// Настройки выплат (ключ бонусов DAO).
+pub mod config;
+use config::*;
+
 /// ==============================================
 /// Константы формата / сидов / размеров
 /// ==============================================
-
+///
 /// Префикс (seed) для PDA, где храним глобальное состояние выплат.
 /// Важно: сид — это просто набор байт; здесь он фиксированный.
 pub const PDA_SEED_PREFIX: &[u8] = b"shine_investments_state";
 
-/// Версия формата сериализации нашей структуры состояния.
+/// Сид PDA-хранилища (vault), где лежат лампорты инвесторов.
+/// Отдельно от PDA состояния, чтобы рента состояния и деньги инвесторов не смешивались.
+pub const VAULT_SEED_PREFIX: &[u8] = b"shine_investments_vault";
+
+/// Сид PDA позиции инвестора: [POSITION_SEED_PREFIX, investor pubkey].
+pub const POSITION_SEED_PREFIX: &[u8] = b"shine_investor_position";
+
+// Версия формата сериализации нашей структуры состояния.
 // pub const INVEST_STATE_FORMAT_V1: u32 = 1; // ← «формат» = 1
 
 /// Значение коэффициента «по умолчанию» при инициализации.
-pub const DEFAULT_COEF: u32 = 10; // ← «коэффициент» = 10 при init
+pub const DEFAULT_COEF: u64 = 10; // ← «коэффициент» = 10 при init
 
-/// Кол-во 4-байтовых чисел в состоянии = 7 (см. структуру ниже),
-/// значит «голые» данные занимают 7 * 4 = 28 байт.
-// pub const PAY_STATE_RAW_LEN_V1: usize = 7 * 4; // 28 байт
-
-/// Ровно столько байт резервируем под PDA-данные.
-/// (Можно добавить запас на будущее, но по заданию — только 28.)
-pub const PAY_STATE_SPACE: u64 = 50; // просто сделал с запасом PAY_STATE_RAW_LEN_V1 as u64;
+/// Столько байт резервируем под PDA состояния (формат V2 — 60 байт, остальное — запас
+/// под новые поля без realloc). Записи V1 создавались на 50 байт и увеличиваются
+/// инструкцией migrate_invest_state.
+pub const PAY_STATE_SPACE: u64 = INVEST_STATE_SPACE_V2 as u64;
 
 
 
@@ -39,81 +50,53 @@ pub mod shine_payments {
     // use crate::payments;
 
 
-    /// init — создаёт PDA и кладёт дефолтное состояние.
+    /// init — создаёт PDA и кладёт дефолтное состояние, а также создаёт vault.
+    /// Только администратор настроек выплат (init_payments_config вызывается раньше).
     pub fn init(ctx: Context<Init>) -> Result<()> {
         investments::init(ctx) // делегируем в модуль payments
     }
 
-    // TODO: пока только шаблоны вызова основных функций
-
-    /// invest — в начале читает состояние, в конце сохраняет (логика внутри модуля).
-    pub fn invest(ctx: Context<UseState>, amount: u64) -> Result<()> {
-        investments::invest(ctx, amount) // делегируем
+    /// init_payments_config — однократное создание настроек выплат (ключ бонусов).
+    pub fn init_payments_config(ctx: Context<ManagePaymentsConfig>, bonus_authority: Pubkey) -> Result<()> {
+        config::init_payments_config(ctx, bonus_authority) // делегируем
     }
 
-    /// add_bonus — начисление бонусов (обычно от DAO).
-    pub fn add_bonus(ctx: Context<UseState>, investor: Pubkey, amount: u64) -> Result<()> {
-        investments::add_bonus(ctx, investor, amount) // делегируем
+    /// update_payments_config — смена ключа бонусов / администратора.
+    pub fn update_payments_config(
+        ctx: Context<ManagePaymentsConfig>,
+        bonus_authority: Pubkey,
+        new_admin: Pubkey,
+    ) -> Result<()> {
+        config::update_payments_config(ctx, bonus_authority, new_admin) // делегируем
     }
 
-    /// claim — выплата.
-    pub fn claim(ctx: Context<UseState>) -> Result<()> {
-        investments::claim(ctx) // делегируем
+    /// init_vault — создаёт vault, если состояние было создано до его появления.
+    /// Только администратор настроек выплат.
+    pub fn init_vault(ctx: Context<InitVault>) -> Result<()> {
+        investments::init_vault(ctx) // делегируем
     }
 
+    /// invest — переводит `amount` лампортов в vault и ставит токены в очередь 1.
+    pub fn invest(ctx: Context<Invest>, amount: u64) -> Result<()> {
+        investments::invest(ctx, amount) // делегируем
+    }
 
+    /// add_bonus — начисление бонусов, только ключ бонусов из настроек (DAO);
+    /// сумма бонуса переводится с ключа бонусов в vault.
+    pub fn add_bonus(ctx: Context<AddBonus>, investor: Pubkey, amount: u64) -> Result<()> {
+        investments::add_bonus(ctx, investor, amount) // делегируем
+    }
 
+    /// migrate_invest_state — перевод состояния V1 (u32) в V2 (u64/u128) на месте.
+    /// Вызвать может кто угодно, подписант доплачивает ренту за увеличение PDA.
+    pub fn migrate_invest_state(ctx: Context<MigrateInvestState>) -> Result<()> {
+        investments::migrate_invest_state(ctx) // делегируем
+    }
 
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-    ///     ВРЕМЕННАЯ ФУНКЦИЯ      только для тестов и в итоговой версии её не будет
-    ///
-    /// ===============================
-    /// deleteInit — удалить PDA из init и вернуть ренту подписанту
-    /// ===============================
-    pub fn delete_init(ctx: Context<DeleteInit>) -> Result<()> {
-        let program_id = ctx.program_id;
-
-        // PDA по тем же сиду/бампу, что и в init
-        let (expected_pda, bump) = Pubkey::find_program_address(&[PDA_SEED_PREFIX], program_id);
-        require_keys_eq!(expected_pda, ctx.accounts.state_pda.key(), ErrCode::InvalidPdaAddress);
-
-        // сиды для подписи PDA при assign()
-        let seeds: [&[u8]; 2] = [PDA_SEED_PREFIX, &[bump]];
-
-        // Вызов общего утилити-метода: рента уйдёт на счёт подписанта (signer)
-        common::utils::delete_pda_return_rent(
-            &ctx.accounts.state_pda.to_account_info(),
-            &ctx.accounts.signer.to_account_info(),
-            program_id,
-        )
+    /// claim — выплата из vault по очереди 1: сначала бонусы, затем вложения
+    /// за токены, до которых дошла очередь.
+    /// `amount = None` — всё доступное, `Some(x)` — частично, не больше доступного.
+    pub fn claim(ctx: Context<Claim>, amount: Option<u64>) -> Result<()> {
+        investments::claim(ctx, amount) // делегируем
     }
 }
-    /// Контекст для deleteInit                         этого тоже в итоге не будет
-    #[derive(Accounts)]
-    pub struct DeleteInit<'info> {
-        /// Подписант транзакции — ПОЛУЧАТЕЛЬ ренты
-        #[account(mut)]
-        pub signer: Signer<'info>,
-
-        /// Тот самый PDA из init
-        /// CHECK: адрес валидируем в хендлере по сид-у
-        #[account(mut)]
-        pub state_pda: UncheckedAccount<'info>,
-
-        /// Системная программа
-        pub system_program: Program<'info, System>,
-    }
-
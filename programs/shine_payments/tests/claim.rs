@@ -0,0 +1,261 @@
+// ==============================================
+// Тесты расчёта выплат очереди 1: compute_claimable / plan_claim / apply_claim.
+// Лампорты vault моделируются числом (баланс сверх ренты).
+// ==============================================
+
+use anchor_lang::prelude::*;
+use shine_payments::investments::*;
+
+const COEF: u64 = 10;
+
+/// Состояние и vault после инвестиций: каждый инвестор ставит `tokens` токенов по очереди.
+struct Queue {
+    st: InvestState,
+    vault: u64,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Queue {
+            st: InvestState { format: INVEST_STATE_FORMAT_V2, coef: COEF, ..Default::default() },
+            vault: 0,
+        }
+    }
+
+    /// То же, что invest: токены встают в конец очереди, лампорты — в vault.
+    fn invest(&mut self, position: &mut InvestorPosition, tokens: u64) {
+        enqueue_tokens(&mut self.st, position, tokens, tokens * COEF).unwrap();
+        self.vault += tokens * COEF;
+    }
+
+    /// То же, что add_bonus: бонус начисляется и переводится в vault.
+    fn add_bonus(&mut self, position: &mut InvestorPosition, amount: u64) {
+        self.st.sum1_bonus += amount as u128;
+        self.vault += amount;
+        position.bonus_accrued += amount;
+    }
+
+    /// То же, что claim: план выплаты, запись в позицию и счётчики, списание с vault.
+    fn claim(&mut self, position: &mut InvestorPosition, amount: Option<u64>) -> Result<(u64, u64, u64)> {
+        let (bonus, principal, tokens) = plan_claim(&self.st, position, self.vault, amount)?;
+        apply_claim(&mut self.st, position, bonus, principal, tokens)?;
+        self.vault -= bonus + principal;
+        Ok((bonus, principal, tokens))
+    }
+}
+
+#[test]
+fn full_claim_pays_bonus_then_principal() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 5);
+    q.add_bonus(&mut a, 7);
+
+    assert_eq!(q.claim(&mut a, None).unwrap(), (7, 50, 5));
+    assert_eq!(q.vault, 0);
+    assert_eq!(q.st.q1_paid_tokens, 5);
+    assert_eq!(q.st.sum1_paid_bonus, 7);
+}
+
+#[test]
+fn partial_claim_takes_bonus_first() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 4);
+    q.add_bonus(&mut a, 6);
+
+    // 6 — весь бонус, вложения не трогаются
+    assert_eq!(q.claim(&mut a, Some(6)).unwrap(), (6, 0, 0));
+    // дальше — вложения целыми токенами
+    assert_eq!(q.claim(&mut a, Some(20)).unwrap(), (0, 20, 2));
+    assert_eq!(q.claim(&mut a, None).unwrap(), (0, 20, 2));
+    assert_eq!(a.paid_tokens, a.tokens);
+}
+
+#[test]
+fn claim_more_than_owed_is_rejected() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 3);
+
+    let err = q.claim(&mut a, Some(31)).unwrap_err();
+    assert_eq!(err, ErrCode::ClaimExceedsOwed.into());
+    // ничего не изменилось
+    assert_eq!(a.paid_principal, 0);
+    assert_eq!(q.st.q1_paid_tokens, 0);
+}
+
+#[test]
+fn zero_claim_is_rejected() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 3);
+
+    let err = q.claim(&mut a, Some(0)).unwrap_err();
+    assert_eq!(err, ErrCode::NothingToClaim.into());
+}
+
+#[test]
+fn repeated_claims_never_exceed_owed() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    let mut b = InvestorPosition::default();
+    q.invest(&mut a, 7);
+    q.invest(&mut b, 3);
+    q.add_bonus(&mut a, 5);
+
+    // как несколько claim в одной транзакции: каждый видит уже уменьшенный остаток
+    let mut paid = 0;
+    assert_eq!(q.claim(&mut a, Some(5)).unwrap(), (5, 0, 0));
+    paid += 5;
+    while let Ok((bonus, principal, _)) = q.claim(&mut a, Some(2 * COEF)) {
+        paid += bonus + principal;
+    }
+    let rest = q.claim(&mut a, None).map(|(bonus, principal, _)| bonus + principal).unwrap_or(0);
+    assert_eq!(paid + rest, 7 * COEF + 5);
+    assert_eq!(a.paid_tokens, 7);
+    assert_eq!(q.claim(&mut a, None).unwrap_err(), ErrCode::NothingToClaim.into());
+
+    // вложения второго инвестора остались в vault
+    assert_eq!(q.vault, 3 * COEF);
+    assert_eq!(q.claim(&mut b, None).unwrap(), (0, 30, 3));
+}
+
+#[test]
+fn claim_smaller_than_coef_is_rejected() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    let mut b = InvestorPosition::default();
+    q.invest(&mut a, 4);
+    q.invest(&mut b, 1);
+
+    // 5 лампортов — полтокена: отказ, ничего не записано
+    for _ in 0..8 {
+        let err = q.claim(&mut a, Some(5)).unwrap_err();
+        assert_eq!(err, ErrCode::ClaimNotWholeTokens.into());
+    }
+    assert_eq!((a.paid_principal, a.paid_tokens, q.st.q1_paid_tokens), (0, 0, 0));
+
+    // целыми токенами выплаченные лампорты и токены сходятся
+    assert_eq!(q.claim(&mut a, Some(COEF)).unwrap(), (0, 10, 1));
+    assert_eq!(q.claim(&mut a, None).unwrap(), (0, 30, 3));
+    assert_eq!(a.paid_principal, a.paid_tokens * COEF);
+    assert_eq!(q.st.q1_paid_tokens, 4);
+
+    // перед B никого не осталось
+    assert_eq!(q.claim(&mut b, None).unwrap(), (0, 10, 1));
+}
+
+#[test]
+fn partial_claim_mixes_bonus_and_whole_tokens() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 3);
+    q.add_bonus(&mut a, 4);
+
+    // 4 бонуса + 2 токена
+    assert_eq!(q.claim(&mut a, Some(24)).unwrap(), (4, 20, 2));
+    // 4 бонуса + полтокена — отказ
+    let mut b = InvestorPosition::default();
+    q.invest(&mut b, 3);
+    q.add_bonus(&mut b, 4);
+    let err = q.claim(&mut b, Some(9)).unwrap_err();
+    assert_eq!(err, ErrCode::ClaimNotWholeTokens.into());
+}
+
+#[test]
+fn queue_pays_investors_ahead_first() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    let mut b = InvestorPosition::default();
+    q.invest(&mut a, 10);
+    q.invest(&mut b, 10);
+
+    // в vault только на 15 токенов: первым 10 токенам A, B — только 5
+    q.vault = 15 * COEF;
+    let b_now = compute_claimable(&q.st, &b, q.vault).unwrap();
+    assert_eq!(b_now, Claimable { bonus: 0, principal: 50, tokens: 5 });
+
+    // в vault только на 10 токенов — всё A, B ничего
+    q.vault = 10 * COEF;
+    let b_now = compute_claimable(&q.st, &b, q.vault).unwrap();
+    assert_eq!(b_now, Claimable::default());
+    assert_eq!(q.claim(&mut a, None).unwrap(), (0, 100, 10));
+
+    // после выплаты A перед B никого нет
+    q.vault = 10 * COEF;
+    assert_eq!(q.claim(&mut b, None).unwrap(), (0, 100, 10));
+}
+
+#[test]
+fn top_up_extends_position_only_at_queue_end() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    let mut b = InvestorPosition::default();
+    q.invest(&mut a, 5);
+    // за A никто не встал — докупка продолжает тот же отрезок
+    q.invest(&mut a, 5);
+    assert_eq!((a.tokens, a.queue_end), (10, 10));
+
+    q.invest(&mut b, 5);
+    // за A уже стоит B — докупать нельзя, пока есть невыплаченные токены
+    let err = enqueue_tokens(&mut q.st, &mut a, 5, 5 * COEF).unwrap_err();
+    assert_eq!(err, ErrCode::UnpaidTokensInQueue.into());
+    assert_eq!(q.st.q1_tokens, 15);
+
+    // после полной выплаты A снова встаёт в конец очереди
+    assert_eq!(q.claim(&mut a, None).unwrap(), (0, 100, 10));
+    q.invest(&mut a, 5);
+    assert_eq!(a.queue_end, 20);
+
+    // денег на 5 токенов: B впереди A
+    q.vault = 5 * COEF;
+    assert_eq!(compute_claimable(&q.st, &b, q.vault).unwrap().tokens, 5);
+    assert_eq!(compute_claimable(&q.st, &a, q.vault).unwrap().tokens, 0);
+}
+
+#[test]
+fn bonus_reserve_is_not_paid_as_principal() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    let mut b = InvestorPosition::default();
+    q.invest(&mut a, 2);
+    q.add_bonus(&mut b, 15);
+    q.invest(&mut b, 1);
+
+    // A получает только свои вложения, бонус B остаётся в резерве
+    assert_eq!(q.claim(&mut a, None).unwrap(), (0, 20, 2));
+    assert_eq!(q.vault, 15 + COEF);
+    assert_eq!(q.claim(&mut b, None).unwrap(), (15, 10, 1));
+    assert_eq!(q.vault, 0);
+}
+
+#[test]
+fn bonus_is_capped_by_reserve() {
+    let mut q = Queue::new();
+    let mut a = InvestorPosition::default();
+    q.invest(&mut a, 1);
+    // бонус в позиции без резерва (например, начисленный до пополнения vault)
+    a.bonus_accrued = 50;
+
+    let claimable = compute_claimable(&q.st, &a, q.vault).unwrap();
+    assert_eq!(claimable, Claimable { bonus: 0, principal: 10, tokens: 1 });
+}
+
+#[test]
+fn apply_claim_rejects_overpayment() {
+    let mut st = InvestState { format: INVEST_STATE_FORMAT_V2, coef: COEF, q1_tokens: 2, ..Default::default() };
+    let mut a = InvestorPosition { principal: 20, tokens: 2, queue_end: 2, ..Default::default() };
+
+    let err = apply_claim(&mut st, &mut a, 0, 30, 3).unwrap_err();
+    assert_eq!(err, ErrCode::ClaimExceedsOwed.into());
+    let err = apply_claim(&mut st, &mut a, 1, 0, 0).unwrap_err();
+    assert_eq!(err, ErrCode::ClaimExceedsOwed.into());
+}
+
+#[test]
+fn zero_coef_is_rejected() {
+    let st = InvestState { format: INVEST_STATE_FORMAT_V2, coef: 0, ..Default::default() };
+    let err = compute_claimable(&st, &InvestorPosition::default(), 100).unwrap_err();
+    assert_eq!(err, ErrCode::InvalidCoef.into());
+}
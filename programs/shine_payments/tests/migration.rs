@@ -0,0 +1,111 @@
+// ==============================================
+// Тесты форматов: InvestState V1 → V2 и запись InvestorPosition.
+// ==============================================
+
+use anchor_lang::prelude::*;
+use common::codec::VersionedAccount;
+use shine_payments::investments::*;
+
+/// Сырые 24 байта V1: format + 5 чисел u32.
+fn invest_state_v1_bytes(values: [u32; 5]) -> Vec<u8> {
+    let mut data = INVEST_STATE_FORMAT_V1.to_le_bytes().to_vec();
+    for value in values {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn invest_state_v1_decodes_and_keeps_format() {
+    let st = deserialize_invest_state(&invest_state_v1_bytes([10, 20, 30, 4, 5])).unwrap();
+    assert_eq!(st.format, INVEST_STATE_FORMAT_V1);
+    assert_eq!((st.coef, st.q1_tokens, st.sum1_bonus), (10, 20, 30));
+    assert_eq!((st.q1_paid_tokens, st.sum1_paid_bonus), (4, 5));
+
+    // до миграции запись идёт в том же формате, байт в байт
+    assert_eq!(serialize_invest_state(&st).unwrap(), invest_state_v1_bytes([10, 20, 30, 4, 5]));
+}
+
+#[test]
+fn invest_state_v1_migrates_to_v2() {
+    let mut st = deserialize_invest_state(&invest_state_v1_bytes([10, 20, 30, 4, 5])).unwrap();
+    st.format = INVEST_STATE_FORMAT_V2;
+
+    let data = serialize_invest_state(&st).unwrap();
+    assert_eq!(data.len(), INVEST_STATE_RAW_LEN_V2);
+    assert!(data.len() <= INVEST_STATE_SPACE_V2);
+
+    let migrated = deserialize_invest_state(&data).unwrap();
+    assert_eq!(migrated.format, INVEST_STATE_FORMAT_V2);
+    assert_eq!((migrated.coef, migrated.q1_tokens, migrated.sum1_bonus), (10, 20, 30));
+    assert_eq!((migrated.q1_paid_tokens, migrated.sum1_paid_bonus), (4, 5));
+}
+
+#[test]
+fn invest_state_v2_holds_values_above_u32() {
+    let st = InvestState {
+        format: INVEST_STATE_FORMAT_V2,
+        coef: 10,
+        q1_tokens: u64::MAX,
+        sum1_bonus: u128::MAX,
+        q1_paid_tokens: 1 << 40,
+        sum1_paid_bonus: 1 << 100,
+    };
+    let back = deserialize_invest_state(&serialize_invest_state(&st).unwrap()).unwrap();
+    assert_eq!(back.q1_tokens, u64::MAX);
+    assert_eq!(back.sum1_bonus, u128::MAX);
+    assert_eq!(back.sum1_paid_bonus, 1 << 100);
+}
+
+#[test]
+fn invest_state_v1_overflow_requires_migration() {
+    let st = InvestState {
+        format: INVEST_STATE_FORMAT_V1,
+        coef: 10,
+        q1_tokens: u32::MAX as u64 + 1,
+        ..Default::default()
+    };
+    let err = serialize_invest_state(&st).unwrap_err();
+    assert_eq!(err, ErrCode::ArithmeticOverflow.into());
+}
+
+#[test]
+fn invest_state_rejects_truncated_and_unknown_formats() {
+    assert!(deserialize_invest_state(&invest_state_v1_bytes([10, 20, 30, 4, 5])[..20]).is_err());
+
+    let mut data = invest_state_v1_bytes([10, 20, 30, 4, 5]);
+    data[..4].copy_from_slice(&7u32.to_le_bytes());
+    let err = deserialize_invest_state(&data).unwrap_err();
+    assert_eq!(err, ErrCode::UnsupportedFormat.into());
+}
+
+#[test]
+fn investor_position_round_trips() {
+    let position = InvestorPosition {
+        investor: Pubkey::new_unique(),
+        principal: 100,
+        tokens: 10,
+        paid_principal: 20,
+        paid_tokens: 2,
+        bonus_accrued: 5,
+        bonus_claimed: 1,
+        first_invest_at: 1_700_000_000,
+        last_invest_at: 1_700_000_100,
+        queue_end: 77,
+    };
+
+    let data = position.encode();
+    assert_eq!(data.len(), INVESTOR_POSITION_SIZE_V1);
+    assert_eq!(data[..4], INVESTOR_POSITION_FORMAT_V1.to_le_bytes());
+
+    let back = InvestorPosition::decode(&data).unwrap();
+    assert_eq!(back.investor, position.investor);
+    assert_eq!((back.principal, back.tokens), (100, 10));
+    assert_eq!((back.paid_principal, back.paid_tokens), (20, 2));
+    assert_eq!((back.bonus_accrued, back.bonus_claimed), (5, 1));
+    assert_eq!((back.first_invest_at, back.last_invest_at), (1_700_000_000, 1_700_000_100));
+    assert_eq!(back.queue_end, 77);
+
+    // обрезанная запись не читается
+    assert!(InvestorPosition::decode(&data[..INVESTOR_POSITION_SIZE_V1 - 8]).is_err());
+}
@@ -0,0 +1,420 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke, system_instruction};
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_and_write_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+use std::str::FromStr;
+
+use crate::reserved_logins::{create_reserved_login_pda, DEFAULT_RESERVED_LOGINS};
+use crate::token_fees::{read_mint_decimals, read_token_account, require_classic_token_owner, SPL_TOKEN_PROGRAM_ID};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Настройки регистрации (PDA ["registration_config"])
+// ───────────────────────────────────────────────────────────────────────
+//
+// Комиссия, её получатель, ключ администратора и переключатели функций.
+// Меняются инструкцией `update_registration_config` без передеплоя программы.
+// Все инструкции регистрации читают комиссию отсюда.
+
+/// Сид PDA настроек регистрации
+pub const REGISTRATION_CONFIG_SEED: &str = "registration_config";
+
+/// Версия формата сериализации RegistrationConfig
+pub const REGISTRATION_CONFIG_FORMAT_V1: u32 = 1;
+
+/// Размер записи V1: 4 + 32 + 32 + 8 + 4 + 2 + 32 + 8 + 32 + 32 + 8
+pub const REGISTRATION_CONFIG_SIZE_V1: usize = 194;
+
+/// 100% в базисных пунктах
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Ключ, который может создать настройки (становится первым администратором)    key3
+pub const BOOTSTRAP_ADMIN: &str = "6bFc5Gz5qF172GQhK5HpDbWs8F6qcSxdHn5XqAstf1fY";
+
+/// Комиссия по умолчанию — 0.01 SOL в лампортах
+pub const DEFAULT_REGISTRATION_FEE: u64 = 10_000_000;
+
+/// Переключатель: регистрация открыта
+pub const REG_FLAG_REGISTRATION_OPEN: u32 = 1 << 0;
+/// Переключатель: разрешён упрощённый первый шаг регистрации (`register_user_step_one`)
+pub const REG_FLAG_STEP_ONE_ENABLED: u32 = 1 << 1;
+
+/// Переключатель: логин регистрируется только через commit-reveal (`reveal_and_register`),
+/// остальные способы регистрации отказывают — логин из них можно перехватить
+pub const REG_FLAG_COMMIT_REQUIRED: u32 = 1 << 2;
+
+/// Все переключатели, известные программе
+pub const REG_FLAGS_ALL: u32 =
+    REG_FLAG_REGISTRATION_OPEN | REG_FLAG_STEP_ONE_ENABLED | REG_FLAG_COMMIT_REQUIRED;
+
+/// Переключатели при создании настроек (commit-reveal не обязателен)
+pub const REG_FLAGS_DEFAULT: u32 = REG_FLAG_REGISTRATION_OPEN | REG_FLAG_STEP_ONE_ENABLED;
+
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (REGISTRATION_CONFIG_FORMAT_V1)
+/// [4..36]     = admin: [u8; 32]
+/// [36..68]    = fee_receiver: [u8; 32]
+/// [68..76]    = fee_lamports: u64
+/// [76..80]    = flags: u32
+/// [80..82]    = referral_fee_bps: u16 (доля комиссии, которая уходит рефереру)
+/// [82..114]   = fee_mint: [u8; 32] (Pubkey::default() — оплата токеном выключена)
+/// [114..122]  = fee_token_amount: u64 (в минимальных единицах токена)
+/// [122..154]  = fee_token_account: [u8; 32] (токен-аккаунт получателя)
+/// [154..186]  = moderator: [u8; 32] (смена статусов пользователей)
+/// [186..194]  = prekey_claim_fee_lamports: u64 (плата за одноразовый ключ, 0 — бесплатно)
+pub struct RegistrationConfig {
+    pub admin: Pubkey,
+    pub fee_receiver: Pubkey,
+    pub fee_lamports: u64,
+    pub flags: u32,
+    pub referral_fee_bps: u16,
+    pub fee_mint: Pubkey,
+    pub fee_token_amount: u64,
+    pub fee_token_account: Pubkey,
+    pub moderator: Pubkey,
+    pub prekey_claim_fee_lamports: u64,
+}
+
+/// Способ оплаты комиссии за регистрацию (выбирается в каждой транзакции)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FeePayment {
+    /// Перевод `fee_lamports` системной программой
+    Sol,
+    /// Перевод `fee_token_amount` токена `fee_mint` (см. модуль token_fees)
+    Token,
+}
+
+impl RegistrationConfig {
+    pub fn is_enabled(&self, flag: u32) -> bool {
+        self.flags & flag == flag
+    }
+
+    /// Включена ли оплата комиссии токеном
+    pub fn token_fee_enabled(&self) -> bool {
+        self.fee_mint != Pubkey::default()
+    }
+
+    /// Доля рефереру от суммы `amount` (округляется вниз)
+    pub fn referral_share(&self, amount: u64) -> u64 {
+        (amount as u128 * self.referral_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+}
+
+impl VersionedAccount for RegistrationConfig {
+    const LATEST_FORMAT: u32 = REGISTRATION_CONFIG_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            REGISTRATION_CONFIG_FORMAT_V1 => Ok(RegistrationConfig {
+                admin: reader.read_pubkey()?,
+                fee_receiver: reader.read_pubkey()?,
+                fee_lamports: reader.read_u64()?,
+                flags: reader.read_u32()?,
+                referral_fee_bps: reader.read_u16()?,
+                fee_mint: reader.read_pubkey()?,
+                fee_token_amount: reader.read_u64()?,
+                fee_token_account: reader.read_pubkey()?,
+                moderator: reader.read_pubkey()?,
+                prekey_claim_fee_lamports: reader.read_u64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.admin);
+        writer.write_pubkey(&self.fee_receiver);
+        writer.write_u64(self.fee_lamports);
+        writer.write_u32(self.flags);
+        writer.write_u16(self.referral_fee_bps);
+        writer.write_pubkey(&self.fee_mint);
+        writer.write_u64(self.fee_token_amount);
+        writer.write_pubkey(&self.fee_token_account);
+        writer.write_pubkey(&self.moderator);
+        writer.write_u64(self.prekey_claim_fee_lamports);
+    }
+}
+
+/// 🔧 Сериализация RegistrationConfig (последний формат)
+pub fn serialize_registration_config(cfg: &RegistrationConfig) -> Vec<u8> {
+    cfg.encode()
+}
+
+/// 🔄 Десериализация RegistrationConfig
+pub fn deserialize_registration_config(data: &[u8]) -> Result<RegistrationConfig> {
+    RegistrationConfig::decode(data)
+}
+
+
+/// Проверяет адрес PDA настроек, возвращает bump
+pub fn check_registration_config_pda(pda_account: &AccountInfo, program_id: &Pubkey) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[REGISTRATION_CONFIG_SEED.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверяет адрес PDA настроек и читает их
+pub fn read_registration_config(pda_account: &AccountInfo, program_id: &Pubkey) -> Result<RegistrationConfig> {
+    check_registration_config_pda(pda_account, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    deserialize_registration_config(&raw)
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Списание комиссии за регистрацию по настройкам
+/// ─────────────────────────────────────────────────────────────
+///
+/// Проверяет, что регистрация открыта и `fee_receiver` совпадает с настройками,
+/// затем переводит `fee_lamports` (если комиссия не нулевая).
+/// Если передан кошелёк реферера, ему уходит `referral_fee_bps` от комиссии.
+pub fn charge_registration_fee<'info>(
+    config: &RegistrationConfig,
+    payer: &AccountInfo<'info>,
+    fee_receiver: &AccountInfo<'info>,
+    referrer_wallet: Option<&AccountInfo<'info>>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    require!(config.is_enabled(REG_FLAG_REGISTRATION_OPEN), ErrCode::RegistrationDisabled);
+    require!(fee_receiver.key == &config.fee_receiver, ErrCode::InvalidPdaAddress);
+
+    let referral_share = match referrer_wallet {
+        Some(_) => config.referral_share(config.fee_lamports),
+        None => 0,
+    };
+    let transfers = [
+        (fee_receiver, config.fee_lamports - referral_share),
+        (referrer_wallet.unwrap_or(fee_receiver), referral_share),
+    ];
+    for (receiver, lamports) in transfers {
+        if lamports == 0 {
+            continue;
+        }
+        let transfer_instruction = system_instruction::transfer(payer.key, receiver.key, lamports);
+        invoke(
+            &transfer_instruction,
+            &[payer.clone(), receiver.clone(), system_program.clone()],
+        )?;
+    }
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Создание и изменение настроек регистрации
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов (общая для создания и изменения)
+#[derive(Accounts)]
+pub struct ManageRegistrationConfig<'info> {
+    /// CHECK: Администратор (при создании — BOOTSTRAP_ADMIN). Проверяется вручную, платит за PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["registration_config"]. Проверяется вручную
+    #[account(mut)]
+    pub config_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Однократное создание настроек со значениями по умолчанию:
+/// комиссия 0.01 SOL, получатель, администратор и модератор — BOOTSTRAP_ADMIN,
+/// переключатели REG_FLAGS_DEFAULT.
+///
+/// В той же инструкции в реестр "reserved=" заносятся DEFAULT_RESERVED_LOGINS:
+/// их PDA ["reserved=", login] передаются в `remaining_accounts` (mut) в том же порядке.
+/// Остальные служебные логины добавляются инструкцией `add_reserved_login`.
+pub fn initialize_registration_config<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ManageRegistrationConfig<'info>>,
+) -> Result<()> {
+    let bootstrap_admin = Pubkey::from_str(BOOTSTRAP_ADMIN).unwrap();
+    require!(ctx.accounts.signer.key == &bootstrap_admin, ErrCode::InvalidSigner);
+
+    let pda = &ctx.accounts.config_pda;
+    let bump = check_registration_config_pda(pda, ctx.program_id)?;
+    if pda.owner != &Pubkey::default() {
+        return Err(error!(ErrCode::SystemAlreadyInitialized));
+    }
+
+    let config = RegistrationConfig {
+        admin: bootstrap_admin,
+        fee_receiver: bootstrap_admin,
+        fee_lamports: DEFAULT_REGISTRATION_FEE,
+        flags: REG_FLAGS_DEFAULT,
+        referral_fee_bps: 0,
+        fee_mint: Pubkey::default(),
+        fee_token_amount: 0,
+        fee_token_account: Pubkey::default(),
+        moderator: bootstrap_admin,
+        prekey_claim_fee_lamports: 0,
+    };
+    create_and_write_pda(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        &[REGISTRATION_CONFIG_SEED.as_bytes(), &[bump]],
+        serialize_registration_config(&config),
+        REGISTRATION_CONFIG_SIZE_V1 as u64,
+    )?;
+
+    // Служебные логины резервируются сразу
+    require!(
+        ctx.remaining_accounts.len() == DEFAULT_RESERVED_LOGINS.len(),
+        ErrCode::InvalidPdaAddress
+    );
+    let system_program = ctx.accounts.system_program.to_account_info();
+    for (reserved_pda, login) in ctx.remaining_accounts.iter().zip(DEFAULT_RESERVED_LOGINS) {
+        create_reserved_login_pda(reserved_pda, &ctx.accounts.signer, &system_program, ctx.program_id, login)?;
+    }
+
+    msg!("⚙️ Настройки регистрации созданы");
+    Ok(())
+}
+
+/// Изменение настроек (только текущий администратор).
+/// Передача `new_admin` отдаёт права другому ключу.
+pub fn update_registration_config(
+    ctx: Context<ManageRegistrationConfig>,
+    fee_lamports: u64,
+    fee_receiver: Pubkey,
+    new_admin: Pubkey,
+    flags: u32,
+) -> Result<()> {
+    let pda = &ctx.accounts.config_pda;
+    let mut config = read_registration_config(pda, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+    require!(flags & !REG_FLAGS_ALL == 0, ErrCode::InvalidRegistrationConfig);
+    require!(new_admin != Pubkey::default(), ErrCode::InvalidRegistrationConfig);
+    require!(fee_receiver != Pubkey::default(), ErrCode::InvalidRegistrationConfig);
+
+    config.fee_lamports = fee_lamports;
+    config.fee_receiver = fee_receiver;
+    config.admin = new_admin;
+    config.flags = flags;
+    write_registration_config(pda, &config)?;
+
+    msg!(
+        "⚙️ Настройки регистрации: комиссия {} → {}, флаги {:#b}, администратор {}",
+        fee_lamports, fee_receiver, flags, new_admin
+    );
+    Ok(())
+}
+
+/// Изменение доли комиссии, которая уходит рефереру (только администратор).
+/// `referral_fee_bps` — в базисных пунктах, 0 — рефереру ничего не платится.
+pub fn set_referral_fee_share(ctx: Context<ManageRegistrationConfig>, referral_fee_bps: u16) -> Result<()> {
+    let pda = &ctx.accounts.config_pda;
+    let mut config = read_registration_config(pda, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+    require!(referral_fee_bps <= BPS_DENOMINATOR, ErrCode::InvalidRegistrationConfig);
+
+    config.referral_fee_bps = referral_fee_bps;
+    write_registration_config(pda, &config)?;
+
+    msg!("⚙️ Доля комиссии рефереру: {} bps", referral_fee_bps);
+    Ok(())
+}
+
+/// Смена модератора (только администратор).
+/// Модератор меняет статусы пользователей (suspend / reinstate / ban).
+pub fn set_moderator(ctx: Context<ManageRegistrationConfig>, moderator: Pubkey) -> Result<()> {
+    let pda = &ctx.accounts.config_pda;
+    let mut config = read_registration_config(pda, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+    require!(moderator != Pubkey::default(), ErrCode::InvalidRegistrationConfig);
+
+    config.moderator = moderator;
+    write_registration_config(pda, &config)?;
+
+    msg!("⚙️ Модератор: {}", moderator);
+    Ok(())
+}
+
+/// Изменение платы за одноразовый ключ устройства (только администратор).
+/// Плата уходит владельцу ключа при `claim_one_time_prekey`, 0 — бесплатно.
+pub fn set_prekey_claim_fee(ctx: Context<ManageRegistrationConfig>, fee_lamports: u64) -> Result<()> {
+    let pda = &ctx.accounts.config_pda;
+    let mut config = read_registration_config(pda, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+
+    config.prekey_claim_fee_lamports = fee_lamports;
+    write_registration_config(pda, &config)?;
+
+    msg!("⚙️ Плата за одноразовый ключ: {} лампортов", fee_lamports);
+    Ok(())
+}
+
+/// Структура аккаунтов для настройки оплаты комиссии токеном
+#[derive(Accounts)]
+pub struct SetTokenRegistrationFee<'info> {
+    /// CHECK: Администратор из настроек. Проверяется вручную
+    #[account(signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["registration_config"]. Проверяется вручную
+    #[account(mut)]
+    pub config_pda: AccountInfo<'info>,
+
+    /// CHECK: Mint `fee_mint`. Проверяется вручную; не нужен, если оплата токеном выключается
+    pub mint: Option<AccountInfo<'info>>,
+
+    /// CHECK: Токен-аккаунт `fee_token_account`. Проверяется вручную; не нужен, если оплата токеном выключается
+    pub token_account: Option<AccountInfo<'info>>,
+}
+
+/// Настройка оплаты комиссии в SPL-токене (только администратор).
+/// `fee_mint = Pubkey::default()` выключает оплату токеном.
+/// `fee_token_account` — токен-аккаунт получателя для этого mint.
+///
+/// Mint и токен-аккаунт передаются и проверяются сразу: принимается только
+/// классическая программа Token без расширений, так что mint Token-2022
+/// отклоняется здесь, а не при каждой регистрации.
+pub fn set_token_registration_fee(
+    ctx: Context<SetTokenRegistrationFee>,
+    fee_mint: Pubkey,
+    fee_token_amount: u64,
+    fee_token_account: Pubkey,
+) -> Result<()> {
+    let pda = &ctx.accounts.config_pda;
+    let mut config = read_registration_config(pda, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+    if fee_mint != Pubkey::default() {
+        require!(fee_token_account != Pubkey::default(), ErrCode::InvalidRegistrationConfig);
+
+        let token_program_id = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+        let mint = ctx.accounts.mint.as_ref().ok_or(ErrCode::InvalidTokenAccount)?;
+        require!(mint.key == &fee_mint, ErrCode::InvalidTokenAccount);
+        require_classic_token_owner(mint)?;
+        read_mint_decimals(mint, &token_program_id)?;
+
+        let token_account = ctx.accounts.token_account.as_ref().ok_or(ErrCode::InvalidTokenAccount)?;
+        require!(token_account.key == &fee_token_account, ErrCode::InvalidTokenAccount);
+        require_classic_token_owner(token_account)?;
+        require!(
+            read_token_account(token_account, &token_program_id)?.mint == fee_mint,
+            ErrCode::InvalidTokenAccount
+        );
+    }
+
+    config.fee_mint = fee_mint;
+    config.fee_token_amount = fee_token_amount;
+    config.fee_token_account = fee_token_account;
+    write_registration_config(pda, &config)?;
+
+    msg!("⚙️ Комиссия токеном: {} единиц {} → {}", fee_token_amount, fee_mint, fee_token_account);
+    Ok(())
+}
+
+/// Записывает настройки в PDA (размер записи фиксированный)
+fn write_registration_config(pda: &AccountInfo, config: &RegistrationConfig) -> Result<()> {
+    write_to_pda(pda, &serialize_registration_config(config))
+}
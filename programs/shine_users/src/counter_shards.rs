@@ -0,0 +1,318 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_and_write_pda, create_pda, realloc_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::config::read_registration_config;
+use crate::users::{read_user_counter_pda, write_user_counter_pda, USER_COUNTER_SEED, USER_ID_SEED_PREFIX};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Шардированный счётчик пользователей
+// ───────────────────────────────────────────────────────────────────────
+//
+// Один PDA "user_counter" берёт write-lock при каждой регистрации, поэтому
+// все регистрации в слоте идут строго по очереди. Шарды снимают это
+// ограничение: N независимых счётчиков, каждый выдаёт ID из своего
+// «чередующегося» подмножества:
+//
+//     id = base + allocated * shard_count + shard_index + 1
+//
+// Шард 0 выдаёт base+1, base+1+N, ...; шард 1 — base+2, base+2+N, ... —
+// пересечений нет, ID глобально уникальны.
+//
+// При включении шардов PDA "user_counter" превращается в корневую запись
+// (base + количество шардов), старый 8-байтовый формат больше не читается,
+// так что выдать ID в обход шардов нельзя.
+
+/// Версия формата корневой записи "user_counter" после включения шардов
+pub const USER_COUNTER_ROOT_FORMAT_V1: u32 = 1;
+
+/// Версия формата шарда
+pub const USER_COUNTER_SHARD_FORMAT_V1: u32 = 1;
+
+/// Максимальное количество шардов
+pub const MAX_USER_COUNTER_SHARDS: u16 = 16;
+
+/// Размеры записей в байтах
+pub const USER_COUNTER_ROOT_SPACE: usize = 14;
+pub const USER_COUNTER_SHARD_SPACE: u64 = 24;
+
+
+
+
+/// ───────────────────────────────────────────────────────────────────────
+///  Корневая запись и шард
+/// ───────────────────────────────────────────────────────────────────────
+///
+/// Корень (PDA ["user_counter"]), 14 байт:
+/// [0..4]      = format_type: u32 (USER_COUNTER_ROOT_FORMAT_V1)
+/// [4..12]     = base: u64 (сколько ID успел выдать общий счётчик)
+/// [12..14]    = shard_count: u16
+pub struct UserCounterRoot {
+    pub base: u64,
+    pub shard_count: u16,
+}
+
+/// Шард (PDA ["user_counter", shard_index: u16 LE]), 24 байта:
+/// [0..4]      = format_type: u32 (USER_COUNTER_SHARD_FORMAT_V1)
+/// [4..6]      = shard_index: u16
+/// [6..8]      = shard_count: u16
+/// [8..16]     = base: u64
+/// [16..24]    = allocated: u64 (сколько ID уже выдал этот шард)
+pub struct UserCounterShard {
+    pub shard_index: u16,
+    pub shard_count: u16,
+    pub base: u64,
+    pub allocated: u64,
+}
+
+impl UserCounterShard {
+    /// ID, который шард выдаст после `ahead` следующих выдач (0 — ближайший)
+    pub fn id_at(&self, ahead: u64) -> Option<u64> {
+        self.allocated
+            .checked_add(ahead)?
+            .checked_mul(self.shard_count as u64)?
+            .checked_add(self.base)?
+            .checked_add(self.shard_index as u64 + 1)
+    }
+}
+
+/// 🔧 Сериализация / 🔄 десериализация корня (через VersionedAccount)
+impl VersionedAccount for UserCounterRoot {
+    const LATEST_FORMAT: u32 = USER_COUNTER_ROOT_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            USER_COUNTER_ROOT_FORMAT_V1 => Ok(UserCounterRoot {
+                base: reader.read_u64()?,
+                shard_count: reader.read_u16()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.base);
+        writer.write_u16(self.shard_count);
+    }
+}
+
+/// 🔧 Сериализация / 🔄 десериализация шарда (через VersionedAccount)
+impl VersionedAccount for UserCounterShard {
+    const LATEST_FORMAT: u32 = USER_COUNTER_SHARD_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            USER_COUNTER_SHARD_FORMAT_V1 => Ok(UserCounterShard {
+                shard_index: reader.read_u16()?,
+                shard_count: reader.read_u16()?,
+                base: reader.read_u64()?,
+                allocated: reader.read_u64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u16(self.shard_index);
+        writer.write_u16(self.shard_count);
+        writer.write_u64(self.base);
+        writer.write_u64(self.allocated);
+    }
+}
+
+/// Сериализует корень в последнем формате
+pub fn serialize_user_counter_root(root: &UserCounterRoot) -> Vec<u8> {
+    root.encode()
+}
+
+/// Десериализует корень: определяет формат и читает нужную версию
+pub fn deserialize_user_counter_root(data: &[u8]) -> Result<UserCounterRoot> {
+    UserCounterRoot::decode(data)
+}
+
+/// Сериализует шард в последнем формате
+pub fn serialize_user_counter_shard(shard: &UserCounterShard) -> Vec<u8> {
+    shard.encode()
+}
+
+/// Десериализует шард: определяет формат и читает нужную версию
+pub fn deserialize_user_counter_shard(data: &[u8]) -> Result<UserCounterShard> {
+    UserCounterShard::decode(data)
+}
+
+/// Адрес шарда ["user_counter", shard_index (u16 LE)]
+pub fn user_counter_shard_address(shard_index: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_COUNTER_SEED.as_bytes(), &shard_index.to_le_bytes()], program_id)
+}
+
+
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Выдача нового ID пользователя
+/// ─────────────────────────────────────────────────────────────
+///
+/// Принимает либо общий счётчик ["user_counter"] (пока шарды не включены),
+/// либо любой шард. Сразу сохраняет новое состояние счётчика.
+pub fn allocate_user_id(counter_pda: &AccountInfo, program_id: &Pubkey) -> Result<u64> {
+    let (root_pda, _) = Pubkey::find_program_address(&[USER_COUNTER_SEED.as_bytes()], program_id);
+
+    // ───────────── общий счётчик ─────────────
+    if counter_pda.key == &root_pda {
+        if counter_pda.data_len() != 8 {
+            // Корень уже переведён на шарды
+            return Err(error!(ErrCode::UserCounterSharded));
+        }
+        let current_id = read_user_counter_pda(counter_pda, program_id)?;
+        let new_id = current_id + 1;
+        write_user_counter_pda(counter_pda, program_id, new_id)?;
+        return Ok(new_id);
+    }
+
+    // ───────────── шард ─────────────
+    let raw = safe_read_pda(counter_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let mut shard = deserialize_user_counter_shard(&raw)?;
+
+    let (expected_pda, _) = user_counter_shard_address(shard.shard_index, program_id);
+    require!(counter_pda.key == &expected_pda, ErrCode::InvalidPdaAddress);
+
+    let new_id = shard.id_at(0).ok_or(ErrCode::InvalidShardCount)?;
+    shard.allocated += 1;
+    write_to_pda(counter_pda, &serialize_user_counter_shard(&shard))?;
+    Ok(new_id)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Включение шардов (однократно)
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов.
+///
+/// В `remaining_accounts` (mut) передаются ВСЕ шарды по порядку:
+/// ["user_counter", 0u16], ["user_counter", 1u16], ... — они создаются здесь.
+#[derive(Accounts)]
+pub struct InitUserCounterShards<'info> {
+    /// CHECK: Администратор регистрации. Проверяется вручную, платит за создание PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA общего счётчика ["user_counter"]. Проверяется вручную
+    #[account(mut)]
+    pub counter_pda: AccountInfo<'info>,
+
+    /// Системная программа Solana
+    pub system_program: Program<'info, System>,
+}
+
+/// Если общий счётчик уже работает — его значение становится base,
+/// если его ещё нет — корень создаётся с base = 0.
+///
+/// Перевод необратим и фиксирует `shard_count`, поэтому вызвать может
+/// только администратор из настроек регистрации.
+pub fn initialize_user_counter_shards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitUserCounterShards<'info>>,
+    shard_count: u16,
+) -> Result<()> {
+    let config = read_registration_config(&ctx.accounts.registration_config, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &config.admin, ErrCode::InvalidSigner);
+
+    require!(
+        shard_count > 0 && shard_count <= MAX_USER_COUNTER_SHARDS,
+        ErrCode::InvalidShardCount
+    );
+    require!(ctx.remaining_accounts.len() == shard_count as usize, ErrCode::InvalidShardCount);
+
+    // ───────────── ШАГ 1 ─────────────
+    // Корень: берём base из общего счётчика или создаём с нуля
+    let counter_pda = &ctx.accounts.counter_pda;
+    let (root_pda, root_bump) = Pubkey::find_program_address(
+        &[USER_COUNTER_SEED.as_bytes()], ctx.program_id);
+    require!(counter_pda.key == &root_pda, ErrCode::InvalidPdaAddress);
+
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let base = if counter_pda.owner == &Pubkey::default() {
+        0
+    } else {
+        if counter_pda.data_len() != 8 {
+            msg!("Шарды счётчика уже включены!");
+            return Err(error!(ErrCode::SystemAlreadyInitialized));
+        }
+        read_user_counter_pda(counter_pda, ctx.program_id)?
+    };
+
+    let root = serialize_user_counter_root(&UserCounterRoot { base, shard_count });
+    if counter_pda.owner == &Pubkey::default() {
+        create_and_write_pda(
+            counter_pda,
+            &ctx.accounts.signer,
+            &system_program,
+            ctx.program_id,
+            &[USER_COUNTER_SEED.as_bytes(), &[root_bump]],
+            root.clone(),
+            USER_COUNTER_ROOT_SPACE as u64,
+        )?;
+    } else {
+        realloc_pda(counter_pda, &ctx.accounts.signer, &system_program, USER_COUNTER_ROOT_SPACE)?;
+        write_to_pda(counter_pda, &root)?;
+    }
+
+    // ───────────── ШАГ 2 ─────────────
+    // Создаём шарды
+    for (i, shard_pda) in ctx.remaining_accounts.iter().enumerate() {
+        let shard_index = i as u16;
+        let (expected_pda, bump) = user_counter_shard_address(shard_index, ctx.program_id);
+        require!(shard_pda.key == &expected_pda, ErrCode::InvalidPdaAddress);
+
+        let shard_index_bytes = shard_index.to_le_bytes();
+        create_pda(
+            shard_pda,
+            &ctx.accounts.signer,
+            &system_program,
+            ctx.program_id,
+            &[USER_COUNTER_SEED.as_bytes(), &shard_index_bytes, &[bump]],
+            USER_COUNTER_SHARD_SPACE,
+        )?;
+        write_to_pda(shard_pda, &serialize_user_counter_shard(&UserCounterShard {
+            shard_index,
+            shard_count,
+            base,
+            allocated: 0,
+        }))?;
+    }
+
+    msg!("Счётчик пользователей разделён на {} шардов, base = {}", shard_count, base);
+    Ok(())
+}
+
+
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Помощник для клиента: кандидаты на PDA по ID для шарда
+/// ─────────────────────────────────────────────────────────────
+///
+/// Аналог `users::id_pda_candidates`, но ID идут с шагом shard_count.
+pub fn shard_id_pda_candidates(
+    program_id: &Pubkey,
+    shard: &UserCounterShard,
+    count: u64,
+) -> Vec<Pubkey> {
+    (0..count)
+        .filter_map(|ahead| shard.id_at(ahead))
+        .map(|id| {
+            let id_string = id.to_string();
+            Pubkey::find_program_address(
+                &[USER_ID_SEED_PREFIX.as_bytes(), id_string.as_bytes()], program_id).0
+        })
+        .collect()
+}
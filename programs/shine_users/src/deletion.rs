@@ -0,0 +1,220 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{delete_pda_return_rent, realloc_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::prekeys::prekeys_pda_address;
+use crate::recovery::{
+    check_recovery_pda, deserialize_recovery_request, RECOVERY_CONFIG_SEED_PREFIX,
+    RECOVERY_REQUEST_SEED_PREFIX,
+};
+use crate::sponsored::{check_sponsor_nonce_pda, SponsorNonce};
+use crate::status::{require_user_can_act, UserStatus};
+use crate::users::{read_user_by_id_pda, read_user_by_login_pda, USER_BY_ID_TOMBSTONE_FORMAT};
+use crate::wallet_index::wallet_index_remove;
+
+
+/// ───────────────────────────────────────────────────────────────────────
+///  Надгробие UserById
+/// ───────────────────────────────────────────────────────────────────────
+///
+/// Формат сериализованных данных (всего 24 байта):
+/// [0..4]      = format_type: u32 (USER_BY_ID_TOMBSTONE_FORMAT)
+/// [4..12]     = id: u64
+/// [12..16]    = status: u32 (всегда UserStatus::Deleted)
+/// [16..24]    = deleted_at: i64 (unix time)
+pub struct UserTombstone {
+    pub id: u64,
+    pub deleted_at: i64,
+}
+
+/// 🔧 Сериализация / 🔄 десериализация надгробия (через VersionedAccount).
+/// Статус в надгробии — всегда Deleted, другое значение считается повреждением.
+impl VersionedAccount for UserTombstone {
+    const LATEST_FORMAT: u32 = USER_BY_ID_TOMBSTONE_FORMAT;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            USER_BY_ID_TOMBSTONE_FORMAT => {
+                let id = reader.read_u64()?;
+                let status = reader.read_u32()?;
+                require!(status == UserStatus::Deleted.as_u32(), ErrCode::DeserializationError);
+                Ok(UserTombstone { id, deleted_at: reader.read_i64()? })
+            }
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.id);
+        writer.write_u32(UserStatus::Deleted.as_u32());
+        writer.write_i64(self.deleted_at);
+    }
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Удаление пользователя
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов.
+///
+/// Все PDA, привязанные к пользователю, передаются по фиксированным адресам,
+/// даже если их нет, — существующие закрываются, чтобы рента не осталась
+/// заблокированной. В `remaining_accounts` передаются PDA ключей
+/// ["prekeys=", id, device_pubkey] — по одному на каждое устройство пользователя.
+#[derive(Accounts)]
+pub struct DeleteUser<'info> {
+    /// CHECK: Мастер-ключ пользователя. Проверяется вручную, получает ренту
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserByLogin по сиду ["login=", login]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub user_by_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную, сжимается до надгробия
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: PDA настроек восстановления ["recovery=", id]. Проверяется вручную, закрывается, если есть
+    #[account(mut)]
+    pub recovery_config_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA запроса на восстановление ["recoveryReq=", id]. Проверяется вручную, закрывается, если есть
+    #[account(mut)]
+    pub recovery_request_pda: AccountInfo<'info>,
+
+    /// CHECK: Плательщик запроса на восстановление (получает его ренту). Проверяется по записи в запросе;
+    /// если запроса нет — не используется
+    #[account(mut)]
+    pub recovery_request_rent_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA последнего nonce спонсируемой регистрации ["sponsorNonce=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub sponsor_nonce_pda: AccountInfo<'info>,
+}
+
+
+/// Закрывает PDA программы, если он существует (рента — `recipient`)
+fn close_if_exists<'info>(
+    pda: &AccountInfo<'info>,
+    recipient: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    if pda.owner == program_id {
+        delete_pda_return_rent(pda, recipient, program_id)?;
+    }
+    Ok(())
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция удаления пользователя
+/// ─────────────────────────────────────────────────────────────
+///
+/// - PDA "login=" закрывается полностью (логин снова свободен)
+/// - PDA "userId=" сжимается до 24-байтового надгробия со статусом Deleted,
+///   излишек ренты возвращается пользователю; ID больше никогда не будет выдан
+/// - закрываются ключи устройств, настройки и запрос восстановления;
+///   nonce спонсируемой регистрации — если срок последнего разрешения истёк
+///   (иначе старую подпись можно было бы повторить, PDA остаётся)
+///
+/// Заблокированный (Suspended) и забаненный (Banned) пользователь удалиться
+/// не может: удаление освобождает логин и кошелёк, и так можно было бы уйти
+/// от модерации, зарегистрировавшись заново. Блокировку сначала снимает модератор,
+/// бан окончательный.
+pub fn delete_user<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DeleteUser<'info>>,
+    user_id: u64,
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем пользователя, подписать должен мастер-ключ
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+    // Suspended / Banned — отказ (см. описание выше)
+    require_user_can_act(user.status)?;
+    require!(user.status.can_transition_to(UserStatus::Deleted), ErrCode::InvalidStatusTransition);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Закрываем PDA по логину
+    let login_pda = &ctx.accounts.user_by_login_pda;
+    let user_login = read_user_by_login_pda(login_pda, &user.login, ctx.program_id)?;
+    require!(user_login.id == user_id, ErrCode::InvalidPdaAddress);
+    delete_pda_return_rent(login_pda, &ctx.accounts.signer, ctx.program_id)?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Закрываем настройки и запрос восстановления
+    let config_pda = &ctx.accounts.recovery_config_pda;
+    check_recovery_pda(config_pda, RECOVERY_CONFIG_SEED_PREFIX, user_id, ctx.program_id)?;
+    close_if_exists(config_pda, &ctx.accounts.signer, ctx.program_id)?;
+
+    let request_pda = &ctx.accounts.recovery_request_pda;
+    check_recovery_pda(request_pda, RECOVERY_REQUEST_SEED_PREFIX, user_id, ctx.program_id)?;
+    let raw_request = safe_read_pda(request_pda);
+    if !raw_request.is_empty() {
+        let request = deserialize_recovery_request(&raw_request)?;
+        let receiver = &ctx.accounts.recovery_request_rent_receiver;
+        require!(receiver.key == &request.payer, ErrCode::InvalidPdaAddress);
+        delete_pda_return_rent(request_pda, receiver, ctx.program_id)?;
+    }
+
+    // ───────────── ШАГ 4 ─────────────
+    // Закрываем ключи устройств: PDA каждого устройства должен быть передан
+    for device in &user.devices {
+        let (expected_pda, _) = prekeys_pda_address(user_id, &device.device_pubkey, ctx.program_id);
+        let prekeys_pda = ctx
+            .remaining_accounts
+            .iter()
+            .find(|acc| acc.key == &expected_pda)
+            .ok_or_else(|| error!(ErrCode::InvalidPdaAddress))?;
+        close_if_exists(prekeys_pda, &ctx.accounts.signer, ctx.program_id)?;
+    }
+
+    // ───────────── ШАГ 5 ─────────────
+    // Nonce спонсируемой регистрации: закрываем, только если старые разрешения истекли.
+    // PDA привязан к кошельку, а не к id: если он остаётся, его продолжит
+    // следующая спонсируемая регистрация с этим ключом
+    let nonce_pda = &ctx.accounts.sponsor_nonce_pda;
+    check_sponsor_nonce_pda(nonce_pda, &user.pubkey, ctx.program_id)?;
+    let raw_nonce = safe_read_pda(nonce_pda);
+    if nonce_pda.owner == ctx.program_id && !raw_nonce.is_empty() {
+        let record = SponsorNonce::decode(&raw_nonce)?;
+        if record.is_replay_safe(Clock::get()?.unix_timestamp) {
+            delete_pda_return_rent(nonce_pda, &ctx.accounts.signer, ctx.program_id)?;
+        } else {
+            msg!("Nonce спонсируемой регистрации сохранён до истечения последнего разрешения");
+        }
+    }
+
+    // ───────────── ШАГ 6 ─────────────
+    // Убираем пользователя из обратного индекса
+    let system_program = ctx.accounts.system_program.to_account_info();
+    wallet_index_remove(
+        &ctx.accounts.wallet_index_pda,
+        &ctx.accounts.signer,
+        &system_program,
+        ctx.program_id,
+        &user.pubkey,
+        user_id,
+    )?;
+
+    // ───────────── ШАГ 7 ─────────────
+    // Оставляем надгробие по ID
+    let id_pda = &ctx.accounts.user_by_id_pda;
+    let tombstone = UserTombstone { id: user_id, deleted_at: Clock::get()?.unix_timestamp }.encode();
+    write_to_pda(id_pda, &tombstone)?;
+    realloc_pda(id_pda, &ctx.accounts.signer, &system_program, tombstone.len())?;
+
+    msg!("🪦 Пользователь login={} id={} удалён", user.login, user_id);
+    Ok(())
+}
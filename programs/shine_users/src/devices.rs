@@ -0,0 +1,261 @@
+use anchor_lang::prelude::*;
+use common::utils::{realloc_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::prekeys::close_prekeys_pda;
+use crate::status::require_user_can_act;
+use crate::users::{read_user_by_id_pda, serialize_user_by_id, write_user_by_id_pda, DeviceInfo, UserById};
+
+
+/// Максимальное количество устройств у одного пользователя
+/// (меняется только здесь, при обновлении программы)
+pub const MAX_DEVICES_PER_USER: u8 = 10;
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Добавление устройства существующему пользователю
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов для добавления устройства
+#[derive(Accounts)]
+pub struct AddDevice<'info> {
+    /// CHECK: Мастер-ключ пользователя (UserById.pubkey). Проверяется вручную, платит за увеличение PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция добавления нового устройства пользователю
+/// ─────────────────────────────────────────────────────────────
+pub fn add_device(
+    ctx: Context<AddDevice>,
+    user_id: u64,                 // ID пользователя (сид PDA)
+    device_type: u8,              // тип устройства (1 = телефон, 2 = ПК, ...)
+    device_sign_pubkey: Pubkey,   // подпись устройства
+    device_x25519_pubkey: Pubkey, // ключ шифрования устройства (X25519)
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Проверка адреса PDA по ID
+    let pda = &ctx.accounts.user_by_id_pda;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Читаем пользователя
+    let mut user = read_user_by_id_pda(pda, user_id, ctx.program_id)?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Подписать должен мастер-ключ пользователя
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 4 ─────────────
+    // Проверка лимита и дубликатов ключей
+    require!(user.device_count < MAX_DEVICES_PER_USER, ErrCode::TooManyDevices);
+    let duplicate = user.devices.iter().any(|d| {
+        d.device_pubkey == device_sign_pubkey || d.x25519_pubkey == device_x25519_pubkey
+    });
+    require!(!duplicate, ErrCode::DeviceAlreadyExists);
+
+    // ───────────── ШАГ 5 ─────────────
+    // Добавляем устройство
+    user.devices.push(DeviceInfo {
+        device_type,
+        device_pubkey: device_sign_pubkey,
+        x25519_pubkey: device_x25519_pubkey,
+    });
+    user.device_count += 1;
+
+    // ───────────── ШАГ 6 ─────────────
+    // Сохраняем; PDA увеличивается (если не хватает изначальных 200 байт) с доплатой ренты
+    write_user_by_id_pda(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &user,
+    )?;
+
+    msg!("✅ Пользователю id={} добавлено устройство, всего {}", user_id, user.device_count);
+    Ok(())
+}
+
+
+
+
+
+/// Проверяет, может ли подписант управлять устройствами пользователя:
+/// это либо мастер-ключ, либо ключ одного из его устройств.
+pub(crate) fn is_user_or_device_signer(user: &UserById, signer: &Pubkey) -> bool {
+    &user.pubkey == signer || user.devices.iter().any(|d| &d.device_pubkey == signer)
+}
+
+
+/// Структура аккаунтов для отзыва устройства и смены его ключей
+#[derive(Accounts)]
+pub struct ManageDevice<'info> {
+    /// CHECK: Мастер-ключ пользователя или ключ любого из его устройств. Проверяется вручную
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: Кошелёк мастер-ключа (UserById.pubkey) — получает возвращаемую ренту. Проверяется вручную
+    #[account(mut)]
+    pub master_wallet: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: PDA ключей устройства ["prekeys=", id, device_sign_pubkey] (текущий ключ).
+    /// Проверяется вручную, закрывается, если есть (рента — на мастер-ключ)
+    #[account(mut)]
+    pub prekeys_pda: AccountInfo<'info>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Отзыв устройства (например, потерянный телефон)
+/// ─────────────────────────────────────────────────────────────
+///
+/// - Подписать может мастер-ключ или любое из оставшихся устройств пользователя
+/// - Удаляет устройство из списка (список уплотняется)
+/// - Уменьшает PDA и возвращает освободившуюся ренту на мастер-ключ
+/// - Закрывает PDA ключей (prekeys) устройства, рента — на мастер-ключ
+/// - Последнее устройство устройством не отзывается,
+///   это может сделать только мастер-ключ
+pub fn revoke_device(
+    ctx: Context<ManageDevice>,
+    user_id: u64,               // ID пользователя (сид PDA)
+    device_sign_pubkey: Pubkey, // ключ подписи отзываемого устройства
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Проверка адреса и чтение пользователя
+    let pda = &ctx.accounts.user_by_id_pda;
+    let mut user = read_user_by_id_pda(pda, user_id, ctx.program_id)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Подписать может мастер-ключ или любое устройство, рента уходит мастер-ключу
+    let signer = ctx.accounts.signer.key;
+    require!(is_user_or_device_signer(&user, signer), ErrCode::InvalidSigner);
+    require!(ctx.accounts.master_wallet.key == &user.pubkey, ErrCode::InvalidUserWallet);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Ищем устройство
+    let index = user
+        .devices
+        .iter()
+        .position(|d| d.device_pubkey == device_sign_pubkey)
+        .ok_or(ErrCode::DeviceNotFound)?;
+
+    // Без мастер-ключа последнее устройство не отзывается
+    if signer != &user.pubkey {
+        require!(user.devices.len() > 1, ErrCode::CannotRevokeLastDevice);
+    }
+
+    // ───────────── ШАГ 4 ─────────────
+    // Удаляем устройство (Vec::remove сдвигает остальные — список остаётся плотным)
+    user.devices.remove(index);
+    user.device_count -= 1;
+    let serialized = serialize_user_by_id(&user);
+
+    // ───────────── ШАГ 5 ─────────────
+    // Пишем и уменьшаем PDA, излишек ренты уходит на мастер-ключ
+    write_to_pda(pda, &serialized)?;
+    realloc_pda(
+        pda,
+        &ctx.accounts.master_wallet,
+        &ctx.accounts.system_program.to_account_info(),
+        serialized.len(),
+    )?;
+
+    // ───────────── ШАГ 6 ─────────────
+    // Ключи отозванного устройства больше не должны выдаваться собеседникам
+    close_prekeys_pda(
+        &ctx.accounts.prekeys_pda,
+        &ctx.accounts.master_wallet,
+        user_id,
+        &device_sign_pubkey,
+        ctx.program_id,
+    )?;
+
+    msg!("🗑 У пользователя id={} отозвано устройство {}, осталось {}", user_id, device_sign_pubkey, user.device_count);
+    Ok(())
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Смена ключей устройства (device_pubkey и/или x25519_pubkey)
+/// ─────────────────────────────────────────────────────────────
+///
+/// Подписать может мастер-ключ или любое устройство пользователя.
+/// Размер записи не меняется (кроме перехода старой записи на последний формат) — перезаписываем ключи на месте.
+/// PDA ключей (prekeys) под старым ключом устройства закрывается (рента — на мастер-ключ):
+/// устройство загружает новые ключи заново.
+pub fn rotate_device_keys(
+    ctx: Context<ManageDevice>,
+    user_id: u64,                     // ID пользователя (сид PDA)
+    device_sign_pubkey: Pubkey,       // текущий ключ подписи устройства
+    new_device_sign_pubkey: Pubkey,   // новый ключ подписи
+    new_device_x25519_pubkey: Pubkey, // новый ключ шифрования
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Проверка адреса и чтение пользователя
+    let pda = &ctx.accounts.user_by_id_pda;
+    let mut user = read_user_by_id_pda(pda, user_id, ctx.program_id)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Подписать может мастер-ключ или любое устройство
+    require!(is_user_or_device_signer(&user, ctx.accounts.signer.key), ErrCode::InvalidSigner);
+    require!(ctx.accounts.master_wallet.key == &user.pubkey, ErrCode::InvalidUserWallet);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Ищем устройство и проверяем, что новые ключи не заняты другими устройствами
+    let index = user
+        .devices
+        .iter()
+        .position(|d| d.device_pubkey == device_sign_pubkey)
+        .ok_or(ErrCode::DeviceNotFound)?;
+
+    let duplicate = user.devices.iter().enumerate().any(|(i, d)| {
+        i != index
+            && (d.device_pubkey == new_device_sign_pubkey
+                || d.x25519_pubkey == new_device_x25519_pubkey)
+    });
+    require!(!duplicate, ErrCode::DeviceAlreadyExists);
+
+    // ───────────── ШАГ 4 ─────────────
+    // Заменяем ключи и сохраняем
+    user.devices[index].device_pubkey = new_device_sign_pubkey;
+    user.devices[index].x25519_pubkey = new_device_x25519_pubkey;
+    write_user_by_id_pda(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &user,
+    )?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Старые prekeys подписаны прежним ключом устройства — закрываем
+    close_prekeys_pda(
+        &ctx.accounts.prekeys_pda,
+        &ctx.accounts.master_wallet,
+        user_id,
+        &device_sign_pubkey,
+        ctx.program_id,
+    )?;
+
+    msg!("🔄 У пользователя id={} обновлены ключи устройства {}", user_id, new_device_sign_pubkey);
+    Ok(())
+}
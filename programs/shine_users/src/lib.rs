@@ -1,9 +1,42 @@
+// Anchor 0.31 внутри #[program] вызывает устаревший AccountInfo::realloc
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
 
 pub mod users;
+pub mod devices;
+pub mod recovery;
+pub mod logins;
+pub mod deletion;
+pub mod wallet_index;
+pub mod counter_shards;
+pub mod status;
+pub mod config;
+pub mod reserved_logins;
+pub mod profile;
+pub mod migration;
+pub mod prekeys;
+pub mod sponsored;
+pub mod login_commits;
+pub mod referrals;
+pub mod token_fees;
 
 // Подключаем модуль users
 use users::*;        // Импортируем все функции и структуры из него
+use devices::*;      // Управление устройствами пользователя
+use recovery::*;     // Восстановление мастер-ключа
+use logins::*;       // Переименование логина
+use deletion::*;     // Удаление пользователя
+use wallet_index::*; // Обратный индекс кошелёк → пользователь
+use counter_shards::*; // Шардированный счётчик пользователей
+use status::*;       // Статус пользователя и модерация
+use config::*;       // Настройки регистрации
+use reserved_logins::*; // Реестр зарезервированных логинов
+use profile::*;      // Профиль пользователя
+use migration::*;    // Миграция записей на последний формат
+use prekeys::*;      // Предварительные ключи устройств (X3DH)
+use sponsored::*;    // Спонсируемая регистрация (релеер)
+use login_commits::*; // Регистрация логина через commit-reveal
 
 // use common::utils::*;
 
@@ -40,28 +73,57 @@ pub mod shine {
         )
     }
     
+    /// Однократное включение шардов счётчика пользователей
+    ///
+    /// Создаёт `shard_count` шардов (передаются в remaining_accounts по порядку)
+    /// и переводит общий счётчик в корневую запись. После этого регистрация
+    /// идёт через любой шард, и регистрации в разных шардах не блокируют друг друга.
+    /// Подписать должен администратор из настроек регистрации.
+    pub fn initialize_user_counter_shards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitUserCounterShards<'info>>,
+        shard_count: u16,
+    ) -> Result<()> {
+        counter_shards::initialize_user_counter_shards(ctx, shard_count)
+    }
+
     /// Регистрация пользователя с одним устройством
     ///
     /// Выполняет регистрацию нового пользователя:
     /// - Проверяет логин, валидность PDA и уникальность
-    /// - Переводит комиссию 0.01 SOL
-    /// - Увеличивает счётчик пользователей
+    /// - Переводит комиссию (SOL или SPL-токен, по настройкам регистрации)
+    /// - Увеличивает счётчик пользователей (общий или шард)
     /// - Создаёт два PDA:
     ///     1. по логину (UserByLogin)
-    ///     2. по ID (UserById), выбирая один из пяти возможных адресов
+    ///     2. по ID (UserById), выбирая подходящий из кандидатов в remaining_accounts
+    /// - Добавляет запись в обратный индекс ["wallet=", pubkey]
     ///
     /// Требует:
     /// - signer: аккаунт-подписант, равный переданному pubkey
-    /// - user_counter: PDA со счётчиком
+    /// - user_counter: PDA со счётчиком или любой шард счётчика
     /// - user_by_login_pda: PDA по логину
-    /// - id_pda_1..5: возможные PDA по ID (из которых будет выбран подходящий)
+    /// - remaining_accounts: кандидаты на PDA по ID в любом количестве
+    ///   (см. users::recommended_id_pda_candidates и users::id_pda_candidates)
     /// - system_program и fee_receiver — стандартные
-    pub fn register_user_with_one_dev(
-        ctx: Context<RegisterUserWithOneDev>,
+    /// - wallet_index_pda: PDA обратного индекса кошелька
+    /// - registration_config: PDA настроек регистрации (комиссия и её получатель)
+    /// - reserved_login_pda: PDA реестра ["reserved=", login] (должен быть пуст)
+    /// - login_hold_pda: PDA удержания ["loginHold=", login] (должен быть пуст)
+    /// - instructions_sysvar: Sysvar Instructions; в транзакции должна быть ed25519-инструкция,
+    ///   где device_sign_pubkey подписал users::device_registration_message(login, pubkey, x25519)
+    /// - referrer_id (необязательно): тогда в referrer передаются PDA ["userId=", referrer_id],
+    ///   PDA ["referrals=", referrer_id] и, если задана доля рефереру, его кошелёк (см. referrals);
+    ///   доля платится только в SOL, с fee_payment = Token такая регистрация отклоняется
+    /// - fee_payment: Sol — перевод fee_lamports на fee_receiver;
+    ///   Token — в token_fee передаются mint, токен-аккаунт получателя из настроек,
+    ///   токен-аккаунт плательщика и программа Token (см. token_fees)
+    pub fn register_user_with_one_dev<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterUserWithOneDev<'info>>,
         login: String,
         pubkey: Pubkey,              // подпись пользователя (должна быть signer)
         device_sign_pubkey: Pubkey, // подпись устройства
         device_x25519_pubkey: Pubkey, // X25519 ключ для шифрования
+        referrer_id: Option<u64>,   // id пригласившего (необязательно)
+        fee_payment: FeePayment,    // SOL или токен
     ) -> Result<()> {
         users::register_user_with_one_dev(
             ctx,
@@ -69,6 +131,369 @@ pub mod shine {
             pubkey,
             device_sign_pubkey,
             device_x25519_pubkey,
+            referrer_id,
+            fee_payment,
         )
     }
+
+    /// Добавление устройства существующему пользователю
+    ///
+    /// - Подписывает мастер-ключ пользователя (UserById.pubkey)
+    /// - Проверяет лимит MAX_DEVICES_PER_USER и дубликаты ключей
+    /// - При необходимости увеличивает PDA "userId=" с доплатой ренты
+    pub fn add_device(
+        ctx: Context<AddDevice>,
+        user_id: u64,
+        device_type: u8,
+        device_sign_pubkey: Pubkey,
+        device_x25519_pubkey: Pubkey,
+    ) -> Result<()> {
+        devices::add_device(
+            ctx,
+            user_id,
+            device_type,
+            device_sign_pubkey,
+            device_x25519_pubkey,
+        )
+    }
+
+    /// Отзыв устройства пользователя
+    ///
+    /// Подписать может мастер-ключ или любое устройство пользователя;
+    /// последнее устройство отзывает только мастер-ключ.
+    /// Список устройств уплотняется, освободившаяся рента возвращается на мастер-ключ.
+    /// PDA ключей (prekeys) устройства закрывается.
+    pub fn revoke_device(
+        ctx: Context<ManageDevice>,
+        user_id: u64,
+        device_sign_pubkey: Pubkey,
+    ) -> Result<()> {
+        devices::revoke_device(ctx, user_id, device_sign_pubkey)
+    }
+
+    /// Замена ключей устройства (подпись и X25519)
+    ///
+    /// Подписать может мастер-ключ или любое устройство пользователя.
+    /// PDA ключей (prekeys) под старым ключом устройства закрывается.
+    pub fn rotate_device_keys(
+        ctx: Context<ManageDevice>,
+        user_id: u64,
+        device_sign_pubkey: Pubkey,
+        new_device_sign_pubkey: Pubkey,
+        new_device_x25519_pubkey: Pubkey,
+    ) -> Result<()> {
+        devices::rotate_device_keys(
+            ctx,
+            user_id,
+            device_sign_pubkey,
+            new_device_sign_pubkey,
+            new_device_x25519_pubkey,
+        )
+    }
+
+    /// Настройка гарантов восстановления мастер-ключа (подписывает мастер-ключ)
+    pub fn set_recovery_config(
+        ctx: Context<SetRecoveryConfig>,
+        user_id: u64,
+        threshold: u8,
+        delay_seconds: i64,
+        guardian_keys: Vec<Pubkey>,
+        guardian_user_ids: Vec<u64>,
+    ) -> Result<()> {
+        recovery::set_recovery_config(
+            ctx,
+            user_id,
+            threshold,
+            delay_seconds,
+            guardian_keys,
+            guardian_user_ids,
+        )
+    }
+
+    /// Запрос на смену мастер-ключа по подписям гарантов
+    ///
+    /// Гаранты подтверждают подписью транзакции (remaining_accounts) или
+    /// ed25519-инструкцией в той же транзакции.
+    pub fn initiate_recovery<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateRecovery<'info>>,
+        user_id: u64,
+        new_pubkey: Pubkey,
+    ) -> Result<()> {
+        recovery::initiate_recovery(ctx, user_id, new_pubkey)
+    }
+
+    /// Отмена запроса на восстановление старым мастер-ключом
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>, user_id: u64) -> Result<()> {
+        recovery::cancel_recovery(ctx, user_id)
+    }
+
+    /// Выполнение запроса на восстановление после задержки (может вызвать кто угодно)
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>, user_id: u64) -> Result<()> {
+        recovery::execute_recovery(ctx, user_id)
+    }
+
+    /// Закрытие запроса на восстановление, который уже нельзя выполнить или отменить
+    /// (пользователь удалён или новый ключ занят). Может вызвать кто угодно
+    pub fn close_orphaned_recovery_request(
+        ctx: Context<CloseOrphanedRecoveryRequest>,
+        user_id: u64,
+    ) -> Result<()> {
+        recovery::close_orphaned_recovery_request(ctx, user_id)
+    }
+
+    /// Переименование логина
+    ///
+    /// Создаёт PDA нового логина, закрывает старый (и при `cooldown_seconds > 0`
+    /// удерживает его в PDA ["loginHold=", старый логин]) и обновляет логин
+    /// в записи "userId=" — атомарно.
+    pub fn rename_login(
+        ctx: Context<RenameLogin>,
+        user_id: u64,
+        new_login: String,
+        cooldown_seconds: i64,
+    ) -> Result<()> {
+        logins::rename_login(ctx, user_id, new_login, cooldown_seconds)
+    }
+
+    /// Освобождение логина, удерживаемого после переименования
+    pub fn release_login_hold(ctx: Context<ReleaseLoginHold>, login: String) -> Result<()> {
+        logins::release_login_hold(ctx, login)
+    }
+
+    /// Удаление пользователя (подписывает мастер-ключ)
+    ///
+    /// Закрывает PDA "login=" и сжимает PDA "userId=" до надгробия, закрывает
+    /// ключи устройств, настройки/запрос восстановления и nonce спонсируемой
+    /// регистрации; рента возвращается пользователю (рента запроса — его плательщику).
+    /// Заблокированный или забаненный пользователь удалиться не может.
+    pub fn delete_user<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DeleteUser<'info>>,
+        user_id: u64,
+    ) -> Result<()> {
+        deletion::delete_user(ctx, user_id)
+    }
+
+    /// Разрешить / запретить привязку нескольких пользователей к одному кошельку
+    pub fn set_wallet_multi_user(ctx: Context<SetWalletMultiUser>, allow: bool) -> Result<()> {
+        wallet_index::set_wallet_multi_user(ctx, allow)
+    }
+
+    /// Временная блокировка пользователя (подписывает модератор из настроек регистрации)
+    pub fn suspend_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+        status::suspend_user(ctx, user_id)
+    }
+
+    /// Снятие блокировки (подписывает модератор; бан окончательный)
+    pub fn reinstate_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+        status::reinstate_user(ctx, user_id)
+    }
+
+    /// Бан пользователя (подписывает модератор)
+    pub fn ban_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+        status::ban_user(ctx, user_id)
+    }
+
+    /// Однократное создание настроек регистрации (подписывает BOOTSTRAP_ADMIN)
+    ///
+    /// Заодно резервирует служебные логины DEFAULT_RESERVED_LOGINS
+    /// (их PDA "reserved=" передаются в remaining_accounts по порядку).
+    pub fn initialize_registration_config<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ManageRegistrationConfig<'info>>,
+    ) -> Result<()> {
+        config::initialize_registration_config(ctx)
+    }
+
+    /// Изменение комиссии, получателя, администратора и переключателей (только администратор)
+    pub fn update_registration_config(
+        ctx: Context<ManageRegistrationConfig>,
+        fee_lamports: u64,
+        fee_receiver: Pubkey,
+        new_admin: Pubkey,
+        flags: u32,
+    ) -> Result<()> {
+        config::update_registration_config(ctx, fee_lamports, fee_receiver, new_admin, flags)
+    }
+
+    /// Добавить логин в реестр зарезервированных (только администратор)
+    pub fn add_reserved_login(ctx: Context<ManageReservedLogin>, login: String) -> Result<()> {
+        reserved_logins::add_reserved_login(ctx, login)
+    }
+
+    /// Убрать логин из реестра зарезервированных (только администратор)
+    pub fn remove_reserved_login(ctx: Context<ManageReservedLogin>, login: String) -> Result<()> {
+        reserved_logins::remove_reserved_login(ctx, login)
+    }
+
+    /// Выдать зарезервированный логин существующему пользователю
+    /// (подписывают администратор и мастер-ключ пользователя)
+    pub fn grant_reserved_login(
+        ctx: Context<GrantReservedLogin>,
+        user_id: u64,
+        login: String,
+    ) -> Result<()> {
+        reserved_logins::grant_reserved_login(ctx, user_id, login)
+    }
+
+    /// Изменение профиля пользователя (подписывает мастер-ключ)
+    ///
+    /// `None` — поле не меняется, пустое значение — поле очищается.
+    pub fn update_profile(
+        ctx: Context<UpdateProfile>,
+        user_id: u64,
+        display_name: Option<String>,
+        avatar_uri: Option<String>,
+        bio: Option<String>,
+        content_hash: Option<[u8; 32]>,
+    ) -> Result<()> {
+        profile::update_profile(ctx, user_id, display_name, avatar_uri, bio, content_hash)
+    }
+
+    /// Миграция записи "userId=" / "login=" на последний формат (вызвать может любой)
+    pub fn migrate_user_account(ctx: Context<MigrateUserAccount>) -> Result<()> {
+        migration::migrate_user_account(ctx)
+    }
+
+    /// Пакетная миграция: `user_account` + все записи из remaining_accounts
+    pub fn migrate_user_accounts<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateUserAccount<'info>>,
+    ) -> Result<()> {
+        migration::migrate_user_accounts(ctx)
+    }
+
+    /// Установка signed prekey устройства (нужна ed25519-подпись ключом устройства)
+    pub fn set_signed_prekey(
+        ctx: Context<UploadPrekeys>,
+        user_id: u64,
+        device_pubkey: Pubkey,
+        prekey_id: u32,
+        prekey: Pubkey,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        prekeys::set_signed_prekey(ctx, user_id, device_pubkey, prekey_id, prekey, signature)
+    }
+
+    /// Пополнение пула одноразовых ключей устройства (подписывает само устройство)
+    pub fn add_one_time_prekeys(
+        ctx: Context<UploadPrekeys>,
+        user_id: u64,
+        device_pubkey: Pubkey,
+        prekeys: Vec<OneTimePrekey>,
+    ) -> Result<()> {
+        prekeys::add_one_time_prekeys(ctx, user_id, device_pubkey, prekeys)
+    }
+
+    /// Атомарная выдача одного одноразового ключа устройства
+    /// (вызвать может любой, платит владельцу плату из настроек регистрации)
+    pub fn claim_one_time_prekey(
+        ctx: Context<ClaimOneTimePrekey>,
+        user_id: u64,
+        device_pubkey: Pubkey,
+    ) -> Result<()> {
+        prekeys::claim_one_time_prekey(ctx, user_id, device_pubkey)
+    }
+
+    /// Спонсируемая регистрация пользователя с одним устройством (режим релеера)
+    ///
+    /// Аккаунты как у register_user_with_one_dev, но:
+    /// - payer: релеер, платит комиссию и ренту (не обязан совпадать с pubkey)
+    /// - sponsor_nonce_pda: PDA ["sponsorNonce=", pubkey] с последним nonce
+    /// - в транзакции нужна ed25519-инструкция, где pubkey подписал
+    ///   sponsored::sponsored_registration_message(login, pubkey, device_sign_pubkey,
+    ///   device_x25519_pubkey, payer, nonce, expires_at, referrer_id), плюс подпись устройства
+    /// - nonce строго больше последнего, expires_at — не позже чем через час
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_user_sponsored<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterUserSponsored<'info>>,
+        login: String,
+        pubkey: Pubkey,
+        device_sign_pubkey: Pubkey,
+        device_x25519_pubkey: Pubkey,
+        nonce: u64,
+        expires_at: i64,
+        referrer_id: Option<u64>,
+        fee_payment: FeePayment,
+    ) -> Result<()> {
+        sponsored::register_user_sponsored(
+            ctx,
+            login,
+            pubkey,
+            device_sign_pubkey,
+            device_x25519_pubkey,
+            nonce,
+            expires_at,
+            referrer_id,
+            fee_payment,
+        )
+    }
+
+    /// Шаг 1 регистрации через commit-reveal: фиксация хеша логина
+    ///
+    /// commitment = login_commits::login_commitment_hash(login, signer, salt);
+    /// PDA ["loginCommit=", commitment], рента — за счёт signer
+    pub fn commit_login(ctx: Context<CommitLogin>, commitment: [u8; 32]) -> Result<()> {
+        login_commits::commit_login(ctx, commitment)
+    }
+
+    /// Шаг 2 регистрации через commit-reveal: раскрытие логина и регистрация
+    ///
+    /// Аккаунты как у register_user_with_one_dev + commitment_pda.
+    /// Не раньше MIN_REVEAL_DELAY_SLOTS и не позже LOGIN_COMMITMENT_TTL_SLOTS
+    /// слотов после фиксации; фиксация закрывается, рента возвращается
+    #[allow(clippy::too_many_arguments)]
+    pub fn reveal_and_register<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevealAndRegister<'info>>,
+        login: String,
+        pubkey: Pubkey,
+        device_sign_pubkey: Pubkey,
+        device_x25519_pubkey: Pubkey,
+        salt: [u8; 32],
+        referrer_id: Option<u64>,
+        fee_payment: FeePayment,
+    ) -> Result<()> {
+        login_commits::reveal_and_register(
+            ctx,
+            login,
+            pubkey,
+            device_sign_pubkey,
+            device_x25519_pubkey,
+            salt,
+            referrer_id,
+            fee_payment,
+        )
+    }
+
+    /// Закрытие просроченной фиксации логина (вызвать может кто угодно,
+    /// рента возвращается плательщику фиксации)
+    pub fn close_expired_login_commitment(ctx: Context<CloseLoginCommitment>) -> Result<()> {
+        login_commits::close_expired_login_commitment(ctx)
+    }
+
+    /// Изменение доли комиссии за регистрацию, которая уходит рефереру
+    /// (в базисных пунктах, только администратор настроек)
+    pub fn set_referral_fee_share(ctx: Context<ManageRegistrationConfig>, referral_fee_bps: u16) -> Result<()> {
+        config::set_referral_fee_share(ctx, referral_fee_bps)
+    }
+
+    /// Смена модератора, который меняет статусы пользователей
+    /// (только администратор настроек)
+    pub fn set_moderator(ctx: Context<ManageRegistrationConfig>, moderator: Pubkey) -> Result<()> {
+        config::set_moderator(ctx, moderator)
+    }
+
+    /// Плата за одноразовый ключ устройства (только администратор настроек; 0 — бесплатно)
+    pub fn set_prekey_claim_fee(ctx: Context<ManageRegistrationConfig>, fee_lamports: u64) -> Result<()> {
+        config::set_prekey_claim_fee(ctx, fee_lamports)
+    }
+
+    /// Настройка оплаты комиссии за регистрацию в SPL-токене
+    /// (только администратор настроек; fee_mint = Pubkey::default() — выключить).
+    /// Mint и токен-аккаунт должны принадлежать классической программе Token
+    pub fn set_token_registration_fee(
+        ctx: Context<SetTokenRegistrationFee>,
+        fee_mint: Pubkey,
+        fee_token_amount: u64,
+        fee_token_account: Pubkey,
+    ) -> Result<()> {
+        config::set_token_registration_fee(ctx, fee_mint, fee_token_amount, fee_token_account)
+    }
 }
@@ -0,0 +1,340 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_and_write_pda, delete_pda_return_rent, safe_read_pda};
+use common::utils::ErrCode;
+
+use crate::config::FeePayment;
+use crate::referrals::*; // ReferrerAccounts вместе с модулями, сгенерированными #[derive(Accounts)]
+use crate::token_fees::*; // TokenFeeAccounts — аналогично
+use crate::users::{register_one_dev_user, OneDevRegistrationAccounts};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Регистрация логина в два шага (commit-reveal)
+// ───────────────────────────────────────────────────────────────────────
+//
+// Логин в `register_user_with_one_dev` виден в открытом виде, и наблюдатель
+// может успеть занять его раньше. Поэтому:
+// 1. `commit_login` — сохраняет только хеш (логин, pubkey, соль)
+//    в PDA ["loginCommit=", хеш];
+// 2. `reveal_and_register` — не раньше чем через MIN_REVEAL_DELAY_SLOTS
+//    слотов раскрывает логин и соль, сверяет хеш и регистрирует пользователя.
+//
+// Чтобы логин нельзя было перехватить из раскрывающей транзакции через
+// прямую регистрацию, администратор включает REG_FLAG_COMMIT_REQUIRED:
+// тогда остальные способы регистрации отказывают.
+//
+// Фиксация действует LOGIN_COMMITMENT_TTL_SLOTS слотов. Просроченную
+// фиксацию может закрыть кто угодно, рента возвращается её плательщику.
+
+/// Префикс сида PDA фиксации логина
+pub const LOGIN_COMMIT_SEED_PREFIX: &str = "loginCommit=";
+
+/// Версия формата сериализации LoginCommitment
+pub const LOGIN_COMMITMENT_FORMAT_V1: u32 = 1;
+
+/// Размер записи V1: 4 + 32 + 32 + 32 + 8
+pub const LOGIN_COMMITMENT_SIZE_V1: usize = 108;
+
+/// Минимальная задержка между фиксацией и раскрытием (в слотах)
+pub const MIN_REVEAL_DELAY_SLOTS: u64 = 2;
+
+/// Сколько слотов фиксация действует (~10 минут)
+pub const LOGIN_COMMITMENT_TTL_SLOTS: u64 = 1_500;
+
+/// Домен хеша фиксации логина
+pub const LOGIN_COMMITMENT_DOMAIN: &[u8] = b"shine_users:login_commit:v1";
+
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (LOGIN_COMMITMENT_FORMAT_V1)
+/// [4..36]     = user_pubkey: [u8; 32]  — кто может раскрыть
+/// [36..68]    = payer: [u8; 32]        — кому вернуть ренту
+/// [68..100]   = commitment: [u8; 32]
+/// [100..108]  = committed_slot: u64
+pub struct LoginCommitment {
+    pub user_pubkey: Pubkey,
+    pub payer: Pubkey,
+    pub commitment: [u8; 32],
+    pub committed_slot: u64,
+}
+
+impl LoginCommitment {
+    /// Последний слот, в котором фиксацию ещё можно раскрыть
+    pub fn expires_at_slot(&self) -> u64 {
+        self.committed_slot.saturating_add(LOGIN_COMMITMENT_TTL_SLOTS)
+    }
+}
+
+impl VersionedAccount for LoginCommitment {
+    const LATEST_FORMAT: u32 = LOGIN_COMMITMENT_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        if format != LOGIN_COMMITMENT_FORMAT_V1 {
+            return Err(error!(ErrCode::UnsupportedFormat));
+        }
+        Ok(LoginCommitment {
+            user_pubkey: reader.read_pubkey()?,
+            payer: reader.read_pubkey()?,
+            commitment: reader.read_array()?,
+            committed_slot: reader.read_u64()?,
+        })
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.user_pubkey);
+        writer.write_pubkey(&self.payer);
+        writer.write_bytes(&self.commitment);
+        writer.write_u64(self.committed_slot);
+    }
+}
+
+
+/// Хеш фиксации: sha256(домен + длина логина (u8) + логин + pubkey пользователя + соль)
+pub fn login_commitment_hash(login: &str, user_pubkey: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+    let login_bytes = login.as_bytes();
+    let login_len = login_bytes.len().min(255);
+    hashv(&[
+        LOGIN_COMMITMENT_DOMAIN,
+        &[login_len as u8],
+        &login_bytes[..login_len],
+        user_pubkey.as_ref(),
+        salt,
+    ])
+    .to_bytes()
+}
+
+/// Проверяет адрес PDA фиксации, возвращает bump
+pub fn check_login_commitment_pda(pda_account: &AccountInfo, commitment: &[u8; 32], program_id: &Pubkey) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[LOGIN_COMMIT_SEED_PREFIX.as_bytes(), commitment], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Читает фиксацию из PDA (адрес проверяется по сохранённому хешу)
+pub fn read_login_commitment_pda(pda_account: &AccountInfo, program_id: &Pubkey) -> Result<LoginCommitment> {
+    require!(pda_account.owner == program_id, ErrCode::EmptyPdaData);
+    let record = LoginCommitment::decode(&safe_read_pda(pda_account))?;
+    check_login_commitment_pda(pda_account, &record.commitment, program_id)?;
+    Ok(record)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Фиксация логина
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct CommitLogin<'info> {
+    /// CHECK: Будущий владелец логина (мастер-ключ). Платит ренту фиксации
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["loginCommit=", commitment]. Проверяется вручную (должен быть пуст)
+    #[account(mut)]
+    pub commitment_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Сохраняет хеш login_commitment_hash(login, signer, salt).
+/// Логин в транзакции не передаётся.
+pub fn commit_login(ctx: Context<CommitLogin>, commitment: [u8; 32]) -> Result<()> {
+    let pda = &ctx.accounts.commitment_pda;
+    let bump = check_login_commitment_pda(pda, &commitment, ctx.program_id)?;
+    if pda.owner != &Pubkey::default() {
+        return Err(error!(ErrCode::UserAlreadyExists));
+    }
+
+    let record = LoginCommitment {
+        user_pubkey: *ctx.accounts.signer.key,
+        payer: *ctx.accounts.signer.key,
+        commitment,
+        committed_slot: Clock::get()?.slot,
+    };
+    create_and_write_pda(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        &[LOGIN_COMMIT_SEED_PREFIX.as_bytes(), &commitment, &[bump]],
+        record.encode(),
+        LOGIN_COMMITMENT_SIZE_V1 as u64,
+    )?;
+
+    msg!("🔒 Логин зафиксирован в слоте {}", record.committed_slot);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Раскрытие логина и регистрация
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов (как у `RegisterUserWithOneDev` + PDA фиксации)
+#[derive(Accounts)]
+pub struct RevealAndRegister<'info> {
+    /// CHECK: Подписант (владелец логина и устройства). Проверяется вручную через `.key == &user_pubkey`
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA-счётчик количества пользователей (общий или любой из шардов). Проверяется вручную по сиду внутри функции
+    #[account(mut)]
+    pub user_counter: AccountInfo<'info>,
+
+    /// CHECK: PDA для UserByLogin: должен быть по сиду ["login=", login]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_login_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Получатель комиссии. Проверяется вручную по адресу из настроек регистрации
+    #[account(mut)]
+    pub fee_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA реестра ["reserved=", login]. Проверяется вручную (должен быть пуст)
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA удержания ["loginHold=", login]. Проверяется вручную (должен быть пуст)
+    pub login_hold_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA фиксации ["loginCommit=", commitment]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub commitment_pda: AccountInfo<'info>,
+
+    /// CHECK: Sysvar Instructions — для проверки подписи ключом устройства. Адрес проверяется вручную
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Необязательные аккаунты реферера (нужны, если передан referrer_id)
+    pub referrer: ReferrerAccounts<'info>,
+
+    /// Необязательные аккаунты для оплаты комиссии токеном (нужны при fee_payment = Token)
+    pub token_fee: TokenFeeAccounts<'info>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция раскрытия логина и регистрации
+/// ─────────────────────────────────────────────────────────────
+///
+/// Требования как у `register_user_with_one_dev`, плюс действующая
+/// фиксация хеша (login, user_pubkey, salt). Рента фиксации возвращается.
+#[allow(clippy::too_many_arguments)]
+pub fn reveal_and_register<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RevealAndRegister<'info>>,
+    login: String,
+    user_pubkey: Pubkey,
+    device_sign_pubkey: Pubkey,
+    device_x25519_pubkey: Pubkey,
+    salt: [u8; 32],
+    referrer_id: Option<u64>,
+    fee_payment: FeePayment,
+) -> Result<()> {
+    msg!("🔓 Раскрываем логин: {}", login);
+
+    // ───────────── ШАГ 1 ─────────────
+    // Подписант — сам пользователь
+    require!(ctx.accounts.signer.key == &user_pubkey, ErrCode::InvalidSigner);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Хеш (login, pubkey, salt) должен совпасть с зафиксированным
+    let commitment = login_commitment_hash(&login, &user_pubkey, &salt);
+    let commitment_pda = &ctx.accounts.commitment_pda;
+    check_login_commitment_pda(commitment_pda, &commitment, ctx.program_id)?;
+    let record = read_login_commitment_pda(commitment_pda, ctx.program_id)?;
+    require!(record.user_pubkey == user_pubkey, ErrCode::InvalidSigner);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Окно раскрытия: [committed_slot + MIN_REVEAL_DELAY_SLOTS, committed_slot + TTL]
+    let slot = Clock::get()?.slot;
+    require!(
+        slot >= record.committed_slot.saturating_add(MIN_REVEAL_DELAY_SLOTS),
+        ErrCode::CommitmentTooEarly
+    );
+    require!(slot <= record.expires_at_slot(), ErrCode::CommitmentExpired);
+
+    // ───────────── ШАГ 4 ─────────────
+    // Общая часть регистрации
+    let accounts = OneDevRegistrationAccounts {
+        payer: &ctx.accounts.signer,
+        user_counter: &ctx.accounts.user_counter,
+        user_by_login_pda: &ctx.accounts.user_by_login_pda,
+        system_program: ctx.accounts.system_program.to_account_info(),
+        fee_receiver: &ctx.accounts.fee_receiver,
+        wallet_index_pda: &ctx.accounts.wallet_index_pda,
+        registration_config: &ctx.accounts.registration_config,
+        reserved_login_pda: &ctx.accounts.reserved_login_pda,
+        login_hold_pda: &ctx.accounts.login_hold_pda,
+        instructions_sysvar: &ctx.accounts.instructions_sysvar,
+        referrer: &ctx.accounts.referrer,
+        token_fee: &ctx.accounts.token_fee,
+        id_pda_candidates: ctx.remaining_accounts,
+    };
+    register_one_dev_user(
+        &accounts,
+        ctx.program_id,
+        &login,
+        user_pubkey,
+        device_sign_pubkey,
+        device_x25519_pubkey,
+        referrer_id,
+        fee_payment,
+        true,
+    )?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Фиксация больше не нужна — рента возвращается подписанту (он же плательщик)
+    delete_pda_return_rent(commitment_pda, &ctx.accounts.signer, ctx.program_id)?;
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Закрытие просроченной фиксации
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct CloseLoginCommitment<'info> {
+    /// CHECK: Любой подписант
+    #[account(signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["loginCommit=", commitment]. Проверяется вручную
+    #[account(mut)]
+    pub commitment_pda: AccountInfo<'info>,
+
+    /// CHECK: Плательщик ренты фиксации. Проверяется вручную по записи
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+/// Закрывает фиксацию, срок которой истёк; рента уходит её плательщику
+pub fn close_expired_login_commitment(ctx: Context<CloseLoginCommitment>) -> Result<()> {
+    let pda = &ctx.accounts.commitment_pda;
+    let record = read_login_commitment_pda(pda, ctx.program_id)?;
+    require!(ctx.accounts.rent_receiver.key == &record.payer, ErrCode::InvalidPdaAddress);
+    require!(Clock::get()?.slot > record.expires_at_slot(), ErrCode::CommitmentNotExpired);
+
+    delete_pda_return_rent(pda, &ctx.accounts.rent_receiver, ctx.program_id)?;
+    msg!("🧹 Просроченная фиксация логина закрыта, рента возвращена {}", record.payer);
+    Ok(())
+}
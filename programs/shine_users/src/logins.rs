@@ -0,0 +1,329 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_pda, delete_pda_return_rent, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::users::{
+    check_user_by_login_pda, read_user_by_id_pda, read_user_by_login_pda, serialize_user_by_login,
+    validate_login, write_user_by_id_pda, UserByLogin, LOGIN_SEED_PREFIX,
+};
+use crate::reserved_logins::require_login_not_reserved;
+use crate::status::require_user_can_act;
+use crate::wallet_index::wallet_index_rename;
+
+
+/// Префикс PDA удержания логина: ["loginHold=", login].
+/// Удержание хранится отдельно от "login=", чтобы PDA по логину содержал
+/// только записи UserByLogin и его читатели не встречали чужой формат.
+pub const LOGIN_HOLD_SEED_PREFIX: &str = "loginHold=";
+
+/// Версия формата сериализации LoginHold
+pub const LOGIN_HOLD_FORMAT_V1: u32 = 1;
+
+/// Максимальное время удержания старого логина — 90 суток
+pub const MAX_LOGIN_HOLD_SECONDS: i64 = 90 * 24 * 60 * 60;
+
+
+
+
+/// ───────────────────────────────────────────────────────────────────────
+///  Структура LoginHold
+/// ───────────────────────────────────────────────────────────────────────
+///
+/// Лежит в PDA ["loginHold=", старый логин] после переименования, пока не
+/// пройдёт `hold_until`. Пока PDA существует — логин никто не зарегистрирует.
+/// Вернуть логин себе может только тот же пользователь (по id).
+///
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (LOGIN_HOLD_FORMAT_V1)
+/// [4..5]      = длина логина: u8
+/// [5..(5+len)] = логин
+/// [...]       = id: u64
+/// [...]       = pubkey: [u8; 32] (ему вернётся рента при освобождении)
+/// [...]       = hold_until: i64
+pub struct LoginHold {
+    pub login: String,
+    pub id: u64,
+    pub pubkey: Pubkey,
+    pub hold_until: i64,
+}
+
+/// 🔧 Сериализация / 🔄 десериализация LoginHold (через VersionedAccount)
+impl VersionedAccount for LoginHold {
+    const LATEST_FORMAT: u32 = LOGIN_HOLD_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            LOGIN_HOLD_FORMAT_V1 => Ok(LoginHold {
+                login: reader.read_short_string()?,
+                id: reader.read_u64()?,
+                pubkey: reader.read_pubkey()?,
+                hold_until: reader.read_i64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_short_string(&self.login);
+        writer.write_u64(self.id);
+        writer.write_pubkey(&self.pubkey);
+        writer.write_i64(self.hold_until);
+    }
+}
+
+/// Сериализует `LoginHold` в последнем формате
+pub fn serialize_login_hold(hold: &LoginHold) -> Vec<u8> {
+    hold.encode()
+}
+
+/// Десериализует `LoginHold`: определяет формат и читает нужную версию
+pub fn deserialize_login_hold(data: &[u8]) -> Result<LoginHold> {
+    LoginHold::decode(data)
+}
+
+
+/// Проверяет адрес PDA ["loginHold=", login], возвращает bump
+pub fn check_login_hold_pda(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[LOGIN_HOLD_SEED_PREFIX.as_bytes(), login.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверка при регистрации: PDA удержания для логина передан и пуст.
+/// Истёкшее удержание тоже мешает — его сначала закрывают `release_login_hold`.
+pub fn require_login_not_held(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<()> {
+    check_login_hold_pda(pda_account, login, program_id)?;
+    require!(pda_account.owner != program_id || safe_read_pda(pda_account).is_empty(), ErrCode::LoginOnHold);
+    Ok(())
+}
+
+/// Проверка при смене логина существующим пользователем: удержания нет,
+/// либо логин удерживается за этим же пользователем — тогда удержание
+/// закрывается, рента уходит `rent_receiver`.
+pub fn take_over_login_hold<'info>(
+    pda_account: &AccountInfo<'info>,
+    rent_receiver: &AccountInfo<'info>,
+    login: &str,
+    user_id: u64,
+    program_id: &Pubkey,
+) -> Result<()> {
+    check_login_hold_pda(pda_account, login, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    if pda_account.owner != program_id || raw.is_empty() {
+        return Ok(());
+    }
+    let hold = deserialize_login_hold(&raw)?;
+    require!(hold.id == user_id, ErrCode::LoginOnHold);
+    delete_pda_return_rent(pda_account, rent_receiver, program_id)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Переименование логина
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct RenameLogin<'info> {
+    /// CHECK: Мастер-ключ пользователя. Проверяется вручную, платит за новый PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA текущего логина ["login=", old_login]. Проверяется вручную
+    #[account(mut)]
+    pub old_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA нового логина ["login=", new_login]. Проверяется вручную
+    #[account(mut)]
+    pub new_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: PDA реестра ["reserved=", new_login]. Проверяется вручную (должен быть пуст)
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA удержания старого логина ["loginHold=", old_login]. Проверяется вручную, создаётся при cooldown > 0
+    #[account(mut)]
+    pub old_login_hold_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA удержания нового логина ["loginHold=", new_login]. Проверяется вручную,
+    /// закрывается, если это удержание самого пользователя
+    #[account(mut)]
+    pub new_login_hold_pda: AccountInfo<'info>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция переименования логина (всё в одной транзакции)
+/// ─────────────────────────────────────────────────────────────
+///
+/// - создаёт PDA нового логина (своё удержание нового логина при этом закрывается)
+/// - старый PDA закрывает (рента подписанту); если `cooldown_seconds > 0`,
+///   создаёт LoginHold в PDA ["loginHold=", старый логин] до истечения срока
+/// - обновляет логин в записи "userId=" и в обратном индексе "wallet="
+pub fn rename_login(
+    ctx: Context<RenameLogin>,
+    user_id: u64,
+    new_login: String,
+    cooldown_seconds: i64, // 0 — старый логин освобождается сразу
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем пользователя, подписать должен мастер-ключ
+    let mut user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Проверка нового логина
+    validate_login(&new_login)?;
+    require_login_not_reserved(&ctx.accounts.reserved_login_pda, &new_login, ctx.program_id)?;
+    require!(new_login != user.login, ErrCode::InvalidLogin);
+    require!(
+        (0..=MAX_LOGIN_HOLD_SECONDS).contains(&cooldown_seconds),
+        ErrCode::InvalidLogin
+    );
+
+    // ───────────── ШАГ 3 ─────────────
+    // Старый PDA по логину
+    let old_pda = &ctx.accounts.old_login_pda;
+    let old_record = read_user_by_login_pda(old_pda, &user.login, ctx.program_id)?;
+    require!(old_record.id == user_id, ErrCode::InvalidPdaAddress);
+
+    // ───────────── ШАГ 4 ─────────────
+    // Новый логин: PDA по логину пуст, удержания нет или оно этого же пользователя
+    let new_pda = &ctx.accounts.new_login_pda;
+    let bump_new = check_user_by_login_pda(new_pda, &new_login, ctx.program_id)?;
+    require!(new_pda.owner == &Pubkey::default(), ErrCode::UserAlreadyExists);
+    take_over_login_hold(
+        &ctx.accounts.new_login_hold_pda,
+        &ctx.accounts.signer,
+        &new_login,
+        user_id,
+        ctx.program_id,
+    )?;
+
+    let new_record = UserByLogin {
+        login: new_login.clone(),
+        id: user_id,
+        pubkey: user.pubkey,
+        status: old_record.status,
+    };
+    let serialized_new = serialize_user_by_login(&new_record);
+    let system_program = ctx.accounts.system_program.to_account_info();
+
+    create_pda(
+        new_pda,
+        &ctx.accounts.signer,
+        &system_program,
+        ctx.program_id,
+        &[LOGIN_SEED_PREFIX.as_bytes(), new_login.as_bytes(), &[bump_new]],
+        serialized_new.len() as u64,
+    )?;
+    write_to_pda(new_pda, &serialized_new)?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Старый логин: PDA закрывается, при cooldown — удерживается отдельным PDA
+    let old_login = std::mem::replace(&mut user.login, new_login.clone());
+    delete_pda_return_rent(old_pda, &ctx.accounts.signer, ctx.program_id)?;
+    if cooldown_seconds > 0 {
+        let hold_pda = &ctx.accounts.old_login_hold_pda;
+        let bump_hold = check_login_hold_pda(hold_pda, &old_login, ctx.program_id)?;
+        let hold = LoginHold {
+            login: old_login.clone(),
+            id: user_id,
+            pubkey: user.pubkey,
+            hold_until: Clock::get()?.unix_timestamp + cooldown_seconds,
+        };
+        let serialized_hold = serialize_login_hold(&hold);
+        create_pda(
+            hold_pda,
+            &ctx.accounts.signer,
+            &system_program,
+            ctx.program_id,
+            &[LOGIN_HOLD_SEED_PREFIX.as_bytes(), old_login.as_bytes(), &[bump_hold]],
+            serialized_hold.len() as u64,
+        )?;
+        write_to_pda(hold_pda, &serialized_hold)?;
+    }
+
+    // ───────────── ШАГ 6 ─────────────
+    // Обновляем логин в записи по ID (увеличиваем PDA, если не хватает места)
+    write_user_by_id_pda(&ctx.accounts.user_by_id_pda, &ctx.accounts.signer, &system_program, &user)?;
+
+    // ───────────── ШАГ 7 ─────────────
+    // Обновляем логин в обратном индексе
+    wallet_index_rename(
+        &ctx.accounts.wallet_index_pda,
+        &ctx.accounts.signer,
+        &system_program,
+        ctx.program_id,
+        &user.pubkey,
+        user_id,
+        &new_login,
+    )?;
+
+    msg!("✏️ Логин пользователя id={} изменён: {} → {}", user_id, old_login, new_login);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Освобождение удерживаемого логина
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct ReleaseLoginHold<'info> {
+    /// CHECK: Любой подписант (после истечения срока освобождать может кто угодно)
+    #[account(signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["loginHold=", login] с LoginHold. Проверяется вручную
+    #[account(mut)]
+    pub login_hold_pda: AccountInfo<'info>,
+
+    /// CHECK: Получатель ренты — ключ из LoginHold. Проверяется вручную
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+/// Закрывает PDA с LoginHold после `hold_until`, рента — бывшему владельцу
+pub fn release_login_hold(ctx: Context<ReleaseLoginHold>, login: String) -> Result<()> {
+    let pda = &ctx.accounts.login_hold_pda;
+    check_login_hold_pda(pda, &login, ctx.program_id)?;
+
+    let raw = safe_read_pda(pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let hold = deserialize_login_hold(&raw)?;
+
+    require!(ctx.accounts.rent_receiver.key == &hold.pubkey, ErrCode::InvalidPdaAddress);
+    require!(Clock::get()?.unix_timestamp >= hold.hold_until, ErrCode::LoginOnHold);
+
+    delete_pda_return_rent(pda, &ctx.accounts.rent_receiver, ctx.program_id)?;
+
+    msg!("🔓 Логин {} освобождён", login);
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use anchor_lang::prelude::*;
+use common::codec::read_format_tag;
+use common::utils::{realloc_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::users::{
+    check_user_by_id_pda, check_user_by_login_pda, deserialize_user_by_id,
+    deserialize_user_by_login, serialize_user_by_id, serialize_user_by_login,
+    USER_BY_ID_FORMAT_LATEST, USER_BY_ID_TOMBSTONE_FORMAT, USER_BY_LOGIN_FORMAT_LATEST,
+};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Миграция записей пользователей на последний формат
+// ───────────────────────────────────────────────────────────────────────
+//
+// Вызвать может кто угодно: данные не меняются, только формат.
+// Подписант доплачивает ренту, если запись в новом формате длиннее.
+// Повторный вызов для уже мигрированной записи ничего не делает.
+//
+// Тип записи определяется по адресу: UserById V1 и UserByLogin V1
+// начинаются с одного и того же format_type = 1, поэтому запись считается
+// записью "userId=" / "login=" только если её адрес совпадает с PDA,
+// вычисленным из прочитанных id / логина.
+
+
+/// Результат миграции одного аккаунта
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationOutcome {
+    Migrated,
+    AlreadyLatest,
+}
+
+/// Переписывает данные в аккаунт, увеличивая его при необходимости
+fn rewrite_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    serialized: &[u8],
+) -> Result<()> {
+    if serialized.len() > account.data_len() {
+        realloc_pda(account, payer, system_program, serialized.len())?;
+    }
+    write_to_pda(account, serialized)
+}
+
+/// Мигрирует один аккаунт "userId=" или "login=".
+///
+/// Надгробие — не версия пользовательской записи, его формат не меняется:
+/// для него возвращается AlreadyLatest.
+pub fn migrate_one_user_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<MigrationOutcome> {
+    require!(account.owner == program_id, ErrCode::InvalidPdaAddress);
+    require!(account.is_writable, ErrCode::InvalidPdaAddress);
+
+    let raw = safe_read_pda(account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let format_type = read_format_tag(&raw)?;
+
+    // ───────────── Запись по ID ─────────────
+    if let Ok(user) = deserialize_user_by_id(&raw) {
+        if check_user_by_id_pda(account, user.id, program_id).is_ok() {
+            if format_type == USER_BY_ID_FORMAT_LATEST {
+                return Ok(MigrationOutcome::AlreadyLatest);
+            }
+            rewrite_account(account, payer, system_program, &serialize_user_by_id(&user))?;
+            msg!("🔁 userId={}: формат {} → {}", user.id, format_type, USER_BY_ID_FORMAT_LATEST);
+            return Ok(MigrationOutcome::Migrated);
+        }
+    }
+
+    // ───────────── Запись по логину ─────────────
+    if let Ok(user) = deserialize_user_by_login(&raw) {
+        if check_user_by_login_pda(account, &user.login, program_id).is_ok() {
+            if format_type == USER_BY_LOGIN_FORMAT_LATEST {
+                return Ok(MigrationOutcome::AlreadyLatest);
+            }
+            rewrite_account(account, payer, system_program, &serialize_user_by_login(&user))?;
+            msg!("🔁 login={}: формат {} → {}", user.login, format_type, USER_BY_LOGIN_FORMAT_LATEST);
+            return Ok(MigrationOutcome::Migrated);
+        }
+    }
+
+    // ───────────── Надгробие ─────────────
+    // Мигрировать нечего
+    if format_type == USER_BY_ID_TOMBSTONE_FORMAT {
+        return Ok(MigrationOutcome::AlreadyLatest);
+    }
+
+    Err(error!(ErrCode::UnsupportedFormat))
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Миграция записи пользователя
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов. В пакетном варианте аккаунты передаются
+/// через `remaining_accounts` (mut), `user_account` тоже мигрируется.
+#[derive(Accounts)]
+pub struct MigrateUserAccount<'info> {
+    /// CHECK: Любой подписант, доплачивает ренту при увеличении записи
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+
+    /// CHECK: PDA "userId=" или "login=". Проверяется вручную по адресу
+    #[account(mut)]
+    pub user_account: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Миграция одной записи
+pub fn migrate_user_account(ctx: Context<MigrateUserAccount>) -> Result<()> {
+    let outcome = migrate_one_user_account(
+        &ctx.accounts.user_account,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+    )?;
+    if outcome == MigrationOutcome::AlreadyLatest {
+        msg!("✔️ Запись уже в последнем формате");
+    }
+    Ok(())
+}
+
+/// Пакетная миграция: `user_account` + все `remaining_accounts`
+pub fn migrate_user_accounts<'info>(
+    ctx: Context<'_, '_, 'info, 'info, MigrateUserAccount<'info>>,
+) -> Result<()> {
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let mut migrated: usize = 0;
+    let mut total: usize = 0;
+
+    for account in std::iter::once(&ctx.accounts.user_account).chain(ctx.remaining_accounts.iter()) {
+        let outcome = migrate_one_user_account(account, &ctx.accounts.payer, &system_program, ctx.program_id)?;
+        if outcome == MigrationOutcome::Migrated {
+            migrated += 1;
+        }
+        total += 1;
+    }
+
+    msg!("🔁 Мигрировано записей: {} из {}", migrated, total);
+    Ok(())
+}
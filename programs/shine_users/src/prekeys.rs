@@ -0,0 +1,463 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{invoke, set_return_data};
+use anchor_lang::solana_program::system_instruction;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{
+    create_pda, delete_pda_return_rent, ed25519_signers_for_message, realloc_pda, safe_read_pda,
+    write_to_pda,
+};
+use common::utils::ErrCode;
+
+use crate::config::read_registration_config;
+use crate::devices::is_user_or_device_signer;
+use crate::status::require_user_can_act;
+use crate::users::read_user_by_id_pda;
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Предварительные ключи устройств (prekeys) для X3DH
+// ───────────────────────────────────────────────────────────────────────
+//
+// Для каждого устройства — отдельный PDA ["prekeys=", id as string, device_pubkey]:
+// - signed prekey: X25519-ключ, подписанный ключом устройства (device_pubkey).
+//   Подпись проверяется при загрузке через ed25519-инструкцию в той же
+//   транзакции и хранится, чтобы собеседник мог проверить её сам.
+// - пул одноразовых ключей (one-time prekeys), пополняет только само устройство.
+//   `claim_one_time_prekey` атомарно выдаёт один ключ и удаляет его из пула;
+//   получатель платит владельцу `prekey_claim_fee_lamports` из настроек
+//   регистрации (администратор может поднять плату, если пулы опустошают).
+//
+// При отзыве устройства и смене его ключей PDA закрывается.
+//
+// Собеседник по логину находит id ("login="), список устройств ("userId=")
+// и для каждого устройства — его PDA с ключами.
+
+/// Префикс PDA ключей устройства
+pub const PREKEYS_SEED_PREFIX: &str = "prekeys=";
+
+/// Версия формата сериализации DevicePrekeys
+pub const PREKEYS_FORMAT_V1: u32 = 1;
+
+/// Максимальный размер пула одноразовых ключей на устройство
+pub const MAX_ONE_TIME_PREKEYS: usize = 100;
+
+/// Домен подписи signed prekey
+pub const SIGNED_PREKEY_MESSAGE_DOMAIN: &[u8] = b"shine_users:signed_prekey:v1";
+
+
+/// Одноразовый ключ (аргумент инструкции и элемент пула)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct OneTimePrekey {
+    pub id: u32,
+    pub key: Pubkey, // X25519
+}
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (PREKEYS_FORMAT_V1)
+/// [4..12]     = user_id: u64
+/// [12..44]    = device_pubkey: [u8; 32]
+/// [44..48]    = signed_prekey_id: u32
+/// [48..80]    = signed_prekey: [u8; 32]
+/// [80..144]   = signed_prekey_signature: [u8; 64]
+/// [144..152]  = signed_at: i64
+/// [152..154]  = количество одноразовых ключей: u16
+/// [..]*N      = id: u32 + key: [u8; 32]
+pub struct DevicePrekeys {
+    pub user_id: u64,
+    pub device_pubkey: Pubkey,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: Pubkey,
+    pub signed_prekey_signature: [u8; 64],
+    pub signed_at: i64,
+    pub one_time_prekeys: Vec<OneTimePrekey>,
+}
+
+impl VersionedAccount for DevicePrekeys {
+    const LATEST_FORMAT: u32 = PREKEYS_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        if format != PREKEYS_FORMAT_V1 {
+            return Err(error!(ErrCode::UnsupportedFormat));
+        }
+        let user_id = reader.read_u64()?;
+        let device_pubkey = reader.read_pubkey()?;
+        let signed_prekey_id = reader.read_u32()?;
+        let signed_prekey = reader.read_pubkey()?;
+        let signed_prekey_signature = reader.read_array()?;
+        let signed_at = reader.read_i64()?;
+
+        let count = reader.read_u16()? as usize;
+        let mut one_time_prekeys = Vec::with_capacity(count);
+        for _ in 0..count {
+            one_time_prekeys.push(OneTimePrekey {
+                id: reader.read_u32()?,
+                key: reader.read_pubkey()?,
+            });
+        }
+
+        Ok(DevicePrekeys {
+            user_id,
+            device_pubkey,
+            signed_prekey_id,
+            signed_prekey,
+            signed_prekey_signature,
+            signed_at,
+            one_time_prekeys,
+        })
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.user_id);
+        writer.write_pubkey(&self.device_pubkey);
+        writer.write_u32(self.signed_prekey_id);
+        writer.write_pubkey(&self.signed_prekey);
+        writer.write_bytes(&self.signed_prekey_signature);
+        writer.write_i64(self.signed_at);
+        writer.write_u16(self.one_time_prekeys.len() as u16);
+        for prekey in &self.one_time_prekeys {
+            writer.write_u32(prekey.id);
+            writer.write_pubkey(&prekey.key);
+        }
+    }
+}
+
+
+/// Сообщение, которое ключ устройства подписывает для signed prekey
+pub fn signed_prekey_message(
+    user_id: u64,
+    device_pubkey: &Pubkey,
+    prekey_id: u32,
+    prekey: &Pubkey,
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(SIGNED_PREKEY_MESSAGE_DOMAIN.len() + 76);
+    msg.extend_from_slice(SIGNED_PREKEY_MESSAGE_DOMAIN);
+    msg.extend_from_slice(&user_id.to_le_bytes());
+    msg.extend_from_slice(device_pubkey.as_ref());
+    msg.extend_from_slice(&prekey_id.to_le_bytes());
+    msg.extend_from_slice(prekey.as_ref());
+    msg
+}
+
+/// Адрес PDA ["prekeys=", id, device_pubkey] и bump
+pub fn prekeys_pda_address(user_id: u64, device_pubkey: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    let id_string = user_id.to_string();
+    Pubkey::find_program_address(
+        &[PREKEYS_SEED_PREFIX.as_bytes(), id_string.as_bytes(), device_pubkey.as_ref()], program_id)
+}
+
+/// Проверяет адрес PDA ["prekeys=", id, device_pubkey], возвращает bump
+pub fn check_prekeys_pda(
+    pda_account: &AccountInfo,
+    user_id: u64,
+    device_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = prekeys_pda_address(user_id, device_pubkey, program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Читает ключи устройства (PDA должен существовать)
+fn read_prekeys_pda(
+    pda_account: &AccountInfo,
+    user_id: u64,
+    device_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<DevicePrekeys> {
+    check_prekeys_pda(pda_account, user_id, device_pubkey, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    DevicePrekeys::decode(&raw)
+}
+
+/// Закрывает PDA ключей устройства, если он есть (рента — `recipient`)
+pub(crate) fn close_prekeys_pda<'info>(
+    pda_account: &AccountInfo<'info>,
+    recipient: &AccountInfo<'info>,
+    user_id: u64,
+    device_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    check_prekeys_pda(pda_account, user_id, device_pubkey, program_id)?;
+    if pda_account.owner == program_id {
+        delete_pda_return_rent(pda_account, recipient, program_id)?;
+    }
+    Ok(())
+}
+
+/// Записывает ключи, увеличивая PDA при необходимости (пул при выдаче не сжимается —
+/// место остаётся под следующее пополнение)
+fn write_prekeys_pda<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    prekeys: &DevicePrekeys,
+) -> Result<()> {
+    let serialized = prekeys.encode();
+    if serialized.len() > pda_account.data_len() {
+        realloc_pda(pda_account, payer, system_program, serialized.len())?;
+    }
+    write_to_pda(pda_account, &serialized)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Загрузка ключей устройства
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов (общая для set_signed_prekey / add_one_time_prekeys)
+#[derive(Accounts)]
+pub struct UploadPrekeys<'info> {
+    /// CHECK: Мастер-ключ пользователя или ключ одного из его устройств. Проверяется вручную, платит за PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA ["prekeys=", id, device_pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub prekeys_pda: AccountInfo<'info>,
+
+    /// CHECK: Sysvar Instructions — для проверки подписи signed prekey. Адрес проверяется вручную
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Проверки, общие для загрузки: подписант, статус, устройство принадлежит пользователю
+fn check_upload(ctx: &Context<UploadPrekeys>, user_id: u64, device_pubkey: &Pubkey) -> Result<()> {
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(is_user_or_device_signer(&user, ctx.accounts.signer.key), ErrCode::InvalidSigner);
+    require_user_can_act(user.status)?;
+    require!(
+        user.devices.iter().any(|d| &d.device_pubkey == device_pubkey),
+        ErrCode::DeviceNotFound
+    );
+    Ok(())
+}
+
+/// ─────────────────────────────────────────────────────────────
+/// Установка (замена) signed prekey устройства
+/// ─────────────────────────────────────────────────────────────
+///
+/// В той же транзакции должна быть ed25519-инструкция, где `device_pubkey`
+/// подписал `signed_prekey_message(...)`. PDA создаётся при первом вызове.
+pub fn set_signed_prekey(
+    ctx: Context<UploadPrekeys>,
+    user_id: u64,
+    device_pubkey: Pubkey,
+    prekey_id: u32,
+    prekey: Pubkey,
+    signature: [u8; 64],
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Пользователь, подписант, устройство
+    check_upload(&ctx, user_id, &device_pubkey)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Ключ устройства должен подписать prekey
+    let message = signed_prekey_message(user_id, &device_pubkey, prekey_id, &prekey);
+    let signers = ed25519_signers_for_message(&ctx.accounts.instructions_sysvar, &message)?;
+    require!(signers.contains(&device_pubkey), ErrCode::InvalidEd25519Instruction);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Читаем или создаём PDA
+    let pda = &ctx.accounts.prekeys_pda;
+    let bump = check_prekeys_pda(pda, user_id, &device_pubkey, ctx.program_id)?;
+    let now = Clock::get()?.unix_timestamp;
+    let system_program = ctx.accounts.system_program.to_account_info();
+
+    let prekeys = if pda.owner == &Pubkey::default() {
+        let prekeys = DevicePrekeys {
+            user_id,
+            device_pubkey,
+            signed_prekey_id: prekey_id,
+            signed_prekey: prekey,
+            signed_prekey_signature: signature,
+            signed_at: now,
+            one_time_prekeys: Vec::new(),
+        };
+        let id_string = user_id.to_string();
+        create_pda(
+            pda,
+            &ctx.accounts.signer,
+            &system_program,
+            ctx.program_id,
+            &[PREKEYS_SEED_PREFIX.as_bytes(), id_string.as_bytes(), device_pubkey.as_ref(), &[bump]],
+            prekeys.encode().len() as u64,
+        )?;
+        prekeys
+    } else {
+        let mut prekeys = read_prekeys_pda(pda, user_id, &device_pubkey, ctx.program_id)?;
+        prekeys.signed_prekey_id = prekey_id;
+        prekeys.signed_prekey = prekey;
+        prekeys.signed_prekey_signature = signature;
+        prekeys.signed_at = now;
+        prekeys
+    };
+
+    // ───────────── ШАГ 4 ─────────────
+    // Сохраняем
+    write_prekeys_pda(pda, &ctx.accounts.signer, &system_program, &prekeys)?;
+
+    msg!("🔑 Устройство {} пользователя id={}: signed prekey #{}", device_pubkey, user_id, prekey_id);
+    Ok(())
+}
+
+/// ─────────────────────────────────────────────────────────────
+/// Пополнение пула одноразовых ключей
+/// ─────────────────────────────────────────────────────────────
+///
+/// Сначала должен быть установлен signed prekey (он создаёт PDA).
+/// Подписать должно само устройство: приватные части ключей есть только у него,
+/// чужие ключи в пуле выдавались бы его собеседникам.
+pub fn add_one_time_prekeys(
+    ctx: Context<UploadPrekeys>,
+    user_id: u64,
+    device_pubkey: Pubkey,
+    prekeys: Vec<OneTimePrekey>,
+) -> Result<()> {
+    require!(ctx.accounts.signer.key == &device_pubkey, ErrCode::InvalidSigner);
+    check_upload(&ctx, user_id, &device_pubkey)?;
+
+    let pda = &ctx.accounts.prekeys_pda;
+    let mut stored = read_prekeys_pda(pda, user_id, &device_pubkey, ctx.program_id)?;
+
+    require!(
+        stored.one_time_prekeys.len() + prekeys.len() <= MAX_ONE_TIME_PREKEYS,
+        ErrCode::TooManyPrekeys
+    );
+    // id одноразовых ключей не должны повторяться — по ним клиент находит приватную часть
+    for prekey in &prekeys {
+        require!(
+            !stored.one_time_prekeys.iter().any(|p| p.id == prekey.id),
+            ErrCode::PrekeyAlreadyExists
+        );
+        stored.one_time_prekeys.push(*prekey);
+    }
+
+    write_prekeys_pda(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &stored,
+    )?;
+
+    msg!("🔑 Устройство {} пользователя id={}: в пуле {} одноразовых ключей", device_pubkey, user_id, stored.one_time_prekeys.len());
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Получение одноразового ключа собеседником
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct ClaimOneTimePrekey<'info> {
+    /// CHECK: Любой подписант (тот, кто начинает сессию). Платит плату из настроек регистрации
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"] (плата за ключ). Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA ["prekeys=", id, device_pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub prekeys_pda: AccountInfo<'info>,
+
+    /// CHECK: Мастер-ключ владельца (UserById.pubkey) — получает плату. Проверяется вручную
+    #[account(mut)]
+    pub user_wallet: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Событие выдачи одноразового ключа (клиент берёт ключ отсюда или из return data)
+#[event]
+pub struct OneTimePrekeyClaimed {
+    pub user_id: u64,
+    pub device_pubkey: Pubkey,
+    pub prekey_id: u32,
+    pub prekey: Pubkey,
+    pub claimed_by: Pubkey,
+}
+
+/// Выдаёт первый ключ из пула и удаляет его.
+/// Подписант платит владельцу `prekey_claim_fee_lamports` из настроек регистрации.
+///
+/// Return data: prekey_id (u32 LE) + prekey (32 байта).
+/// Если пул пуст — ошибка NoPrekeysAvailable (X3DH можно провести
+/// только с signed prekey, прочитав его из PDA).
+pub fn claim_one_time_prekey(
+    ctx: Context<ClaimOneTimePrekey>,
+    user_id: u64,
+    device_pubkey: Pubkey,
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Пользователь должен быть активен, устройство — его
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require_user_can_act(user.status)?;
+    require!(
+        user.devices.iter().any(|d| d.device_pubkey == device_pubkey),
+        ErrCode::DeviceNotFound
+    );
+    require!(ctx.accounts.user_wallet.key == &user.pubkey, ErrCode::InvalidUserWallet);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Забираем ключ из пула
+    let pda = &ctx.accounts.prekeys_pda;
+    let mut stored = read_prekeys_pda(pda, user_id, &device_pubkey, ctx.program_id)?;
+    require!(!stored.one_time_prekeys.is_empty(), ErrCode::NoPrekeysAvailable);
+    let claimed = stored.one_time_prekeys.remove(0);
+
+    // Запись стала короче — пишем поверх, размер PDA не меняем
+    write_to_pda(pda, &stored.encode())?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Плата владельцу (если задана в настройках)
+    let fee_lamports = read_registration_config(&ctx.accounts.registration_config, ctx.program_id)?
+        .prekey_claim_fee_lamports;
+    if fee_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(
+                ctx.accounts.signer.key,
+                ctx.accounts.user_wallet.key,
+                fee_lamports,
+            ),
+            &[
+                ctx.accounts.signer.to_account_info(),
+                ctx.accounts.user_wallet.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    // ───────────── ШАГ 4 ─────────────
+    // Отдаём ключ клиенту
+    let mut data = Vec::with_capacity(36);
+    data.extend_from_slice(&claimed.id.to_le_bytes());
+    data.extend_from_slice(claimed.key.as_ref());
+    set_return_data(&data);
+
+    emit!(OneTimePrekeyClaimed {
+        user_id,
+        device_pubkey,
+        prekey_id: claimed.id,
+        prekey: claimed.key,
+        claimed_by: *ctx.accounts.signer.key,
+    });
+
+    msg!("🔑 Выдан одноразовый ключ #{} устройства {} (осталось {})", claimed.id, device_pubkey, stored.one_time_prekeys.len());
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use common::utils::realloc_pda;
+use common::utils::ErrCode;
+
+use crate::status::require_user_can_act;
+use crate::users::{
+    read_user_by_id_pda, serialize_user_by_id, write_user_by_id_pda, UserProfile,
+    MAX_AVATAR_URI_LEN, MAX_BIO_LEN, MAX_DISPLAY_NAME_LEN,
+};
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Изменение профиля пользователя
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct UpdateProfile<'info> {
+    /// CHECK: Мастер-ключ пользователя. Проверяется вручную, доплачивает / получает ренту при изменении размера
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция изменения профиля
+/// ─────────────────────────────────────────────────────────────
+///
+/// `None` — поле не меняется, `Some("")` / `Some([0; 32])` — поле очищается.
+/// Запись сохраняется в последнем формате, PDA увеличивается или уменьшается
+/// под новый размер (рента доплачивается / возвращается подписанту).
+pub fn update_profile(
+    ctx: Context<UpdateProfile>,
+    user_id: u64,
+    display_name: Option<String>,
+    avatar_uri: Option<String>,
+    bio: Option<String>,
+    content_hash: Option<[u8; 32]>,
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем пользователя, подписать должен мастер-ключ
+    let pda = &ctx.accounts.user_by_id_pda;
+    let mut user = read_user_by_id_pda(pda, user_id, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Проверка длины полей
+    let too_long = |value: &Option<String>, max: usize| value.as_ref().is_some_and(|v| v.len() > max);
+    require!(!too_long(&display_name, MAX_DISPLAY_NAME_LEN), ErrCode::InvalidProfile);
+    require!(!too_long(&avatar_uri, MAX_AVATAR_URI_LEN), ErrCode::InvalidProfile);
+    require!(!too_long(&bio, MAX_BIO_LEN), ErrCode::InvalidProfile);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Обновляем поля (у пользователей без профиля время создания неизвестно — 0)
+    let now = Clock::get()?.unix_timestamp;
+    let profile = user.profile.get_or_insert_with(|| UserProfile::new(0));
+    if let Some(value) = display_name {
+        profile.display_name = value;
+    }
+    if let Some(value) = avatar_uri {
+        profile.avatar_uri = value;
+    }
+    if let Some(value) = bio {
+        profile.bio = value;
+    }
+    if let Some(value) = content_hash {
+        profile.content_hash = value;
+    }
+    profile.updated_at = now;
+
+    // ───────────── ШАГ 4 ─────────────
+    // Сохраняем: увеличиваем PDA при необходимости, лишнее место возвращаем
+    let system_program = ctx.accounts.system_program.to_account_info();
+    write_user_by_id_pda(pda, &ctx.accounts.signer, &system_program, &user)?;
+    let new_len = serialize_user_by_id(&user).len();
+    if pda.data_len() > new_len {
+        realloc_pda(pda, &ctx.accounts.signer, &system_program, new_len)?;
+    }
+
+    msg!("📝 Профиль пользователя id={} обновлён", user_id);
+    Ok(())
+}
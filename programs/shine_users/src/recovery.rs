@@ -0,0 +1,669 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{
+    create_pda, delete_pda_return_rent, ed25519_signers_for_message, realloc_pda, safe_read_pda,
+    write_to_pda,
+};
+use common::utils::ErrCode;
+
+use crate::status::require_user_can_act;
+use crate::users::{
+    is_user_by_id_tombstone, read_user_by_id_pda, read_user_by_login_pda, serialize_user_by_login,
+    write_user_by_id_pda, USER_ID_SEED_PREFIX,
+};
+use crate::wallet_index::{wallet_index_add, wallet_index_can_add, wallet_index_remove};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Восстановление мастер-ключа пользователя
+// ───────────────────────────────────────────────────────────────────────
+//
+// Схема:
+// 1. Пользователь заранее (мастер-ключом) задаёт гарантов: ключи своих
+//    устройств восстановления и/или ID других пользователей Shine,
+//    а также порог подписей и задержку — `set_recovery_config`.
+// 2. Потеряв ключ, пользователь собирает подписи гарантов под сообщением
+//    (domain + user_id + new_pubkey + nonce) и вызывает `initiate_recovery`.
+//    Гарант подтверждает либо подписью транзакции, либо ed25519-инструкцией.
+// 3. Пока идёт задержка, старый ключ может отменить запрос — `cancel_recovery`.
+// 4. После задержки кто угодно вызывает `execute_recovery`, и ключ в
+//    записях "login=" и "userId=" меняется на новый.
+// 5. Запрос, который уже нельзя ни выполнить, ни отменить (пользователь
+//    удалён или новый ключ занят другим пользователем), закрывает кто угодно —
+//    `close_orphaned_recovery_request`, рента возвращается плательщику.
+
+/// Префикс PDA с настройками восстановления: ["recovery=", id]
+pub const RECOVERY_CONFIG_SEED_PREFIX: &str = "recovery=";
+
+/// Префикс PDA с активным запросом на восстановление: ["recoveryReq=", id]
+pub const RECOVERY_REQUEST_SEED_PREFIX: &str = "recoveryReq=";
+
+/// Версии форматов сериализации
+pub const RECOVERY_CONFIG_FORMAT_V1: u32 = 1;
+pub const RECOVERY_REQUEST_FORMAT_V1: u32 = 1;
+
+/// Максимум гарантов (ключи + ID пользователей вместе)
+pub const MAX_RECOVERY_GUARDIANS: usize = 10;
+
+/// Минимальная задержка перед сменой ключа — 1 сутки
+pub const MIN_RECOVERY_DELAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// Префикс подписываемого гарантами сообщения (чтобы подпись нельзя было
+/// переиспользовать в другом контексте)
+pub const RECOVERY_MESSAGE_DOMAIN: &[u8] = b"shine_users:recovery:v1";
+
+
+
+
+/// ───────────────────────────────────────────────────────────────────────
+///  Структура RecoveryConfig
+/// ───────────────────────────────────────────────────────────────────────
+///
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (RECOVERY_CONFIG_FORMAT_V1)
+/// [4..12]     = user_id: u64
+/// [12]        = threshold: u8
+/// [13..21]    = delay_seconds: i64
+/// [21..29]    = nonce: u64 (растёт при каждом запросе, защита от повтора подписей)
+/// [29]        = количество ключей-гарантов: u8
+/// [..]*N      = по 32 байта на ключ
+/// [...]       = количество гарантов-пользователей: u8
+/// [..]*M      = по 8 байт на ID
+pub struct RecoveryConfig {
+    pub user_id: u64,
+    pub threshold: u8,
+    pub delay_seconds: i64,
+    pub nonce: u64,
+    pub guardian_keys: Vec<Pubkey>,
+    pub guardian_user_ids: Vec<u64>,
+}
+
+/// 🔧 Сериализация / 🔄 десериализация RecoveryConfig (через VersionedAccount)
+impl VersionedAccount for RecoveryConfig {
+    const LATEST_FORMAT: u32 = RECOVERY_CONFIG_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            RECOVERY_CONFIG_FORMAT_V1 => {
+                let user_id = reader.read_u64()?;
+                let threshold = reader.read_u8()?;
+                let delay_seconds = reader.read_i64()?;
+                let nonce = reader.read_u64()?;
+
+                let keys_count = reader.read_u8()? as usize;
+                let guardian_keys = (0..keys_count)
+                    .map(|_| reader.read_pubkey())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let ids_count = reader.read_u8()? as usize;
+                let guardian_user_ids = (0..ids_count)
+                    .map(|_| reader.read_u64())
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(RecoveryConfig {
+                    user_id,
+                    threshold,
+                    delay_seconds,
+                    nonce,
+                    guardian_keys,
+                    guardian_user_ids,
+                })
+            }
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.user_id);
+        writer.write_u8(self.threshold);
+        writer.write_i64(self.delay_seconds);
+        writer.write_u64(self.nonce);
+
+        writer.write_u8(self.guardian_keys.len() as u8);
+        for key in &self.guardian_keys {
+            writer.write_pubkey(key);
+        }
+
+        writer.write_u8(self.guardian_user_ids.len() as u8);
+        for id in &self.guardian_user_ids {
+            writer.write_u64(*id);
+        }
+    }
+}
+
+/// Сериализует `RecoveryConfig` в последнем формате
+pub fn serialize_recovery_config(cfg: &RecoveryConfig) -> Vec<u8> {
+    cfg.encode()
+}
+
+/// Десериализует `RecoveryConfig`: определяет формат и читает нужную версию
+pub fn deserialize_recovery_config(data: &[u8]) -> Result<RecoveryConfig> {
+    RecoveryConfig::decode(data)
+}
+
+
+
+
+/// ───────────────────────────────────────────────────────────────────────
+///  Структура RecoveryRequest
+/// ───────────────────────────────────────────────────────────────────────
+///
+/// Формат сериализованных данных (всего 92 байта):
+/// [0..4]      = format_type: u32 (RECOVERY_REQUEST_FORMAT_V1)
+/// [4..12]     = user_id: u64
+/// [12..44]    = new_pubkey: [u8; 32]
+/// [44..76]    = payer: [u8; 32] (кто оплатил PDA — ему вернётся рента)
+/// [76..84]    = nonce: u64
+/// [84..92]    = executable_at: i64 (unix time, раньше выполнить нельзя)
+pub struct RecoveryRequest {
+    pub user_id: u64,
+    pub new_pubkey: Pubkey,
+    pub payer: Pubkey,
+    pub nonce: u64,
+    pub executable_at: i64,
+}
+
+/// Размер RecoveryRequest в байтах
+pub const RECOVERY_REQUEST_SPACE: u64 = 92;
+
+/// 🔧 Сериализация / 🔄 десериализация RecoveryRequest (через VersionedAccount)
+impl VersionedAccount for RecoveryRequest {
+    const LATEST_FORMAT: u32 = RECOVERY_REQUEST_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            RECOVERY_REQUEST_FORMAT_V1 => Ok(RecoveryRequest {
+                user_id: reader.read_u64()?,
+                new_pubkey: reader.read_pubkey()?,
+                payer: reader.read_pubkey()?,
+                nonce: reader.read_u64()?,
+                executable_at: reader.read_i64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.user_id);
+        writer.write_pubkey(&self.new_pubkey);
+        writer.write_pubkey(&self.payer);
+        writer.write_u64(self.nonce);
+        writer.write_i64(self.executable_at);
+    }
+}
+
+/// Сериализует `RecoveryRequest` в последнем формате
+pub fn serialize_recovery_request(req: &RecoveryRequest) -> Vec<u8> {
+    req.encode()
+}
+
+/// Десериализует `RecoveryRequest`: определяет формат и читает нужную версию
+pub fn deserialize_recovery_request(data: &[u8]) -> Result<RecoveryRequest> {
+    RecoveryRequest::decode(data)
+}
+
+
+/// Сообщение, которое подписывают гаранты:
+/// domain + user_id (8 байт LE) + new_pubkey (32 байта) + nonce (8 байт LE)
+pub fn recovery_message(user_id: u64, new_pubkey: &Pubkey, nonce: u64) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(RECOVERY_MESSAGE_DOMAIN.len() + 48);
+    msg.extend_from_slice(RECOVERY_MESSAGE_DOMAIN);
+    msg.extend_from_slice(&user_id.to_le_bytes());
+    msg.extend_from_slice(new_pubkey.as_ref());
+    msg.extend_from_slice(&nonce.to_le_bytes());
+    msg
+}
+
+/// Проверяет адрес PDA по сиду [prefix, id as string], возвращает bump
+pub(crate) fn check_recovery_pda(
+    pda_account: &AccountInfo,
+    prefix: &str,
+    user_id: u64,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let id_string = user_id.to_string();
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[prefix.as_bytes(), id_string.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Настройка гарантов восстановления
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct SetRecoveryConfig<'info> {
+    /// CHECK: Мастер-ключ пользователя. Проверяется вручную, платит за PDA
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек восстановления ["recovery=", id]. Проверяется вручную
+    #[account(mut)]
+    pub recovery_config_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Создаёт или перезаписывает настройки восстановления.
+/// nonce при перезаписи сохраняется (старые подписи гарантов остаются недействительными).
+pub fn set_recovery_config(
+    ctx: Context<SetRecoveryConfig>,
+    user_id: u64,
+    threshold: u8,               // сколько подтверждений нужно
+    delay_seconds: i64,          // задержка перед сменой ключа
+    guardian_keys: Vec<Pubkey>,  // ключи устройств восстановления
+    guardian_user_ids: Vec<u64>, // ID пользователей-гарантов
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Подписать должен мастер-ключ
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+    require_user_can_act(user.status)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Проверка параметров
+    let total = guardian_keys.len() + guardian_user_ids.len();
+    require!(total > 0 && total <= MAX_RECOVERY_GUARDIANS, ErrCode::InvalidRecoveryConfig);
+    require!(threshold > 0 && threshold as usize <= total, ErrCode::InvalidRecoveryConfig);
+    require!(delay_seconds >= MIN_RECOVERY_DELAY_SECONDS, ErrCode::InvalidRecoveryConfig);
+
+    for (i, key) in guardian_keys.iter().enumerate() {
+        require!(!guardian_keys[..i].contains(key), ErrCode::InvalidRecoveryConfig);
+    }
+    for (i, id) in guardian_user_ids.iter().enumerate() {
+        require!(*id != user_id, ErrCode::InvalidRecoveryConfig);
+        require!(!guardian_user_ids[..i].contains(id), ErrCode::InvalidRecoveryConfig);
+    }
+
+    // ───────────── ШАГ 3 ─────────────
+    // Создаём или обновляем PDA
+    let pda = &ctx.accounts.recovery_config_pda;
+    let bump = check_recovery_pda(pda, RECOVERY_CONFIG_SEED_PREFIX, user_id, ctx.program_id)?;
+
+    let raw = safe_read_pda(pda);
+    let nonce = if raw.is_empty() {
+        0
+    } else {
+        deserialize_recovery_config(&raw)?.nonce
+    };
+
+    let serialized = serialize_recovery_config(&RecoveryConfig {
+        user_id,
+        threshold,
+        delay_seconds,
+        nonce,
+        guardian_keys,
+        guardian_user_ids,
+    });
+
+    let system_program = ctx.accounts.system_program.to_account_info();
+    if raw.is_empty() {
+        let id_string = user_id.to_string();
+        create_pda(
+            pda,
+            &ctx.accounts.signer,
+            &system_program,
+            ctx.program_id,
+            &[RECOVERY_CONFIG_SEED_PREFIX.as_bytes(), id_string.as_bytes(), &[bump]],
+            serialized.len() as u64,
+        )?;
+    } else {
+        realloc_pda(pda, &ctx.accounts.signer, &system_program, serialized.len())?;
+    }
+    write_to_pda(pda, &serialized)?;
+
+    msg!("🛟 Настройки восстановления id={} сохранены: порог {} из {}", user_id, threshold, total);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Запрос на восстановление (подписи гарантов)
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов.
+///
+/// В `remaining_accounts` передаются:
+/// - гаранты-ключи, подписывающие транзакцию напрямую (signer);
+/// - PDA "userId=" гарантов-пользователей (чтобы узнать их текущий мастер-ключ);
+/// - сами мастер-ключи гарантов-пользователей, если они подписывают напрямую.
+#[derive(Accounts)]
+pub struct InitiateRecovery<'info> {
+    /// CHECK: Плательщик за PDA запроса (любой; подтверждением считается, только если он гарант)
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById восстанавливаемого пользователя. Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек восстановления ["recovery=", id]. Проверяется вручную
+    #[account(mut)]
+    pub recovery_config_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA запроса ["recoveryReq=", id]. Проверяется вручную, создаётся здесь
+    #[account(mut)]
+    pub recovery_request_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA индекса нового ключа ["wallet=", new_pubkey] — только чтение. Проверяется вручную
+    pub new_wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: Sysvar Instructions — для чтения ed25519-подписей. Адрес проверяется вручную
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initiate_recovery<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitiateRecovery<'info>>,
+    user_id: u64,
+    new_pubkey: Pubkey, // новый мастер-ключ
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем пользователя и настройки
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(new_pubkey != user.pubkey, ErrCode::InvalidRecoveryConfig);
+    require_user_can_act(user.status)?;
+
+    let config_pda = &ctx.accounts.recovery_config_pda;
+    check_recovery_pda(config_pda, RECOVERY_CONFIG_SEED_PREFIX, user_id, ctx.program_id)?;
+    let raw = safe_read_pda(config_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let mut config = deserialize_recovery_config(&raw)?;
+
+    // Новый ключ должен быть свободен в обратном индексе — иначе запрос
+    // нельзя будет выполнить после задержки
+    require!(
+        wallet_index_can_add(&ctx.accounts.new_wallet_index_pda, &new_pubkey, ctx.program_id, user_id)?,
+        ErrCode::WalletAlreadyRegistered
+    );
+
+    // ───────────── ШАГ 2 ─────────────
+    // Собираем всех, кто подписал: ed25519-подписи + прямые подписанты.
+    // Сам по себе подписант ничего не подтверждает — засчитываются только гаранты (ШАГ 3)
+    let message = recovery_message(user_id, &new_pubkey, config.nonce);
+    let mut signers = ed25519_signers_for_message(&ctx.accounts.instructions_sysvar, &message)?;
+    signers.push(*ctx.accounts.payer.key);
+    for acc in ctx.remaining_accounts.iter().filter(|a| a.is_signer) {
+        signers.push(*acc.key);
+    }
+
+    // ───────────── ШАГ 3 ─────────────
+    // Считаем подтверждения гарантов по итоговому ключу: один ключ — одно
+    // подтверждение, даже если он указан и как гарант-ключ, и как мастер-ключ
+    // гаранта-пользователя
+    let mut approvers: Vec<Pubkey> = Vec::new();
+    for key in &config.guardian_keys {
+        if signers.contains(key) && !approvers.contains(key) {
+            approvers.push(*key);
+        }
+    }
+
+    for guardian_id in &config.guardian_user_ids {
+        let id_string = guardian_id.to_string();
+        let (guardian_pda, _) = Pubkey::find_program_address(
+            &[USER_ID_SEED_PREFIX.as_bytes(), id_string.as_bytes()], ctx.program_id);
+        let Some(acc) = ctx.remaining_accounts.iter().find(|a| a.key == &guardian_pda) else {
+            continue;
+        };
+        let guardian = read_user_by_id_pda(acc, *guardian_id, ctx.program_id)?;
+        // Заблокированный гарант не считается
+        if require_user_can_act(guardian.status).is_err() {
+            continue;
+        }
+        if signers.contains(&guardian.pubkey) && !approvers.contains(&guardian.pubkey) {
+            approvers.push(guardian.pubkey);
+        }
+    }
+
+    require!(approvers.len() >= config.threshold as usize, ErrCode::RecoveryQuorumNotReached);
+
+    // ───────────── ШАГ 4 ─────────────
+    // Создаём запрос (если уже есть активный — create_pda вернёт ошибку)
+    let request_pda = &ctx.accounts.recovery_request_pda;
+    let bump = check_recovery_pda(request_pda, RECOVERY_REQUEST_SEED_PREFIX, user_id, ctx.program_id)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let request = RecoveryRequest {
+        user_id,
+        new_pubkey,
+        payer: *ctx.accounts.payer.key,
+        nonce: config.nonce,
+        executable_at: now
+            .checked_add(config.delay_seconds)
+            .ok_or(ErrCode::InvalidRecoveryConfig)?,
+    };
+
+    let id_string = user_id.to_string();
+    create_pda(
+        request_pda,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        &[RECOVERY_REQUEST_SEED_PREFIX.as_bytes(), id_string.as_bytes(), &[bump]],
+        RECOVERY_REQUEST_SPACE,
+    )?;
+    write_to_pda(request_pda, &serialize_recovery_request(&request))?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Сдвигаем nonce — эти подписи больше не сработают
+    config.nonce += 1;
+    write_to_pda(config_pda, &serialize_recovery_config(&config))?;
+
+    msg!("🛟 Запрос на восстановление id={} создан, выполнить можно после {}", user_id, request.executable_at);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Отмена и выполнение запроса
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Отмена: подписывает текущий (старый) мастер-ключ
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    /// CHECK: Текущий мастер-ключ пользователя. Проверяется вручную
+    #[account(signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById. Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA запроса ["recoveryReq=", id]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub recovery_request_pda: AccountInfo<'info>,
+
+    /// CHECK: Получатель ренты — тот, кто оплатил запрос. Проверяется по записи в запросе
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+pub fn cancel_recovery(ctx: Context<CancelRecovery>, user_id: u64) -> Result<()> {
+    let user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require!(ctx.accounts.signer.key == &user.pubkey, ErrCode::InvalidSigner);
+
+    let request_pda = &ctx.accounts.recovery_request_pda;
+    check_recovery_pda(request_pda, RECOVERY_REQUEST_SEED_PREFIX, user_id, ctx.program_id)?;
+    let raw = safe_read_pda(request_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let request = deserialize_recovery_request(&raw)?;
+    require!(ctx.accounts.rent_receiver.key == &request.payer, ErrCode::InvalidPdaAddress);
+
+    delete_pda_return_rent(request_pda, &ctx.accounts.rent_receiver, ctx.program_id)?;
+
+    msg!("🛟 Запрос на восстановление id={} отменён владельцем", user_id);
+    Ok(())
+}
+
+
+/// Выполнение: вызвать может кто угодно, но только после задержки
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    /// CHECK: Любой подписант. Платит за PDA индекса нового ключа, получает ренту индекса старого
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserByLogin по сиду ["login=", login]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA запроса ["recoveryReq=", id]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub recovery_request_pda: AccountInfo<'info>,
+
+    /// CHECK: Получатель ренты — тот, кто оплатил запрос. Проверяется по записи в запросе
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA индекса старого ключа ["wallet=", old_pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub old_wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA индекса нового ключа ["wallet=", new_pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub new_wallet_index_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+pub fn execute_recovery(ctx: Context<ExecuteRecovery>, user_id: u64) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем запрос и проверяем задержку
+    let request_pda = &ctx.accounts.recovery_request_pda;
+    check_recovery_pda(request_pda, RECOVERY_REQUEST_SEED_PREFIX, user_id, ctx.program_id)?;
+    let raw = safe_read_pda(request_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let request = deserialize_recovery_request(&raw)?;
+    require!(ctx.accounts.rent_receiver.key == &request.payer, ErrCode::InvalidPdaAddress);
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= request.executable_at, ErrCode::RecoveryDelayNotPassed);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Меняем ключ в записи по ID
+    let mut user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require_user_can_act(user.status)?;
+    let old_pubkey = user.pubkey;
+    user.pubkey = request.new_pubkey;
+    let system_program = ctx.accounts.system_program.to_account_info();
+    write_user_by_id_pda(&ctx.accounts.user_by_id_pda, &ctx.accounts.signer, &system_program, &user)?;
+
+    // ───────────── ШАГ 3 ─────────────
+    // Меняем ключ в записи по логину (размер записи не меняется)
+    let mut user_login = read_user_by_login_pda(&ctx.accounts.user_by_login_pda, &user.login, ctx.program_id)?;
+    require!(user_login.id == user_id, ErrCode::InvalidPdaAddress);
+    user_login.pubkey = request.new_pubkey;
+    write_to_pda(&ctx.accounts.user_by_login_pda, &serialize_user_by_login(&user_login))?;
+
+    // ───────────── ШАГ 4 ─────────────
+    // Переносим запись обратного индекса со старого ключа на новый.
+    // Если пользователь уже есть в индексе нового ключа — запись не дублируется;
+    // если ключ успели занять другим пользователем — запрос закрывается через
+    // `close_orphaned_recovery_request`
+    wallet_index_remove(
+        &ctx.accounts.old_wallet_index_pda,
+        &ctx.accounts.signer,
+        &system_program,
+        ctx.program_id,
+        &old_pubkey,
+        user_id,
+    )?;
+    wallet_index_add(
+        &ctx.accounts.new_wallet_index_pda,
+        &ctx.accounts.signer,
+        &system_program,
+        ctx.program_id,
+        &request.new_pubkey,
+        user_id,
+        &user.login,
+    )?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Закрываем запрос
+    delete_pda_return_rent(request_pda, &ctx.accounts.rent_receiver, ctx.program_id)?;
+
+    msg!("✅ Ключ пользователя id={} изменён: {} → {}", user_id, old_pubkey, request.new_pubkey);
+    Ok(())
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Закрытие запроса, который уже нельзя выполнить
+/// ─────────────────────────────────────────────────────────────
+///
+/// Вызвать может кто угодно, если:
+/// - пользователь удалён (в "userId=" надгробие) — отменить запрос больше некому;
+/// - или новый ключ уже привязан к другому пользователю и индекс его не примет.
+///
+/// Рента возвращается тому, кто оплатил запрос.
+#[derive(Accounts)]
+pub struct CloseOrphanedRecoveryRequest<'info> {
+    /// CHECK: Любой подписант
+    #[account(signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById (пользователь или надгробие). Проверяется вручную
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA запроса ["recoveryReq=", id]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub recovery_request_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA индекса нового ключа ["wallet=", new_pubkey] — только чтение. Проверяется вручную
+    pub new_wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: Получатель ренты — тот, кто оплатил запрос. Проверяется по записи в запросе
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+pub fn close_orphaned_recovery_request(
+    ctx: Context<CloseOrphanedRecoveryRequest>,
+    user_id: u64,
+) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Читаем запрос
+    let request_pda = &ctx.accounts.recovery_request_pda;
+    check_recovery_pda(request_pda, RECOVERY_REQUEST_SEED_PREFIX, user_id, ctx.program_id)?;
+    let raw = safe_read_pda(request_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    let request = deserialize_recovery_request(&raw)?;
+    require!(ctx.accounts.rent_receiver.key == &request.payer, ErrCode::InvalidPdaAddress);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Запрос должен быть «осиротевшим»
+    let deleted = is_user_by_id_tombstone(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    let orphaned = deleted
+        || !wallet_index_can_add(
+            &ctx.accounts.new_wallet_index_pda,
+            &request.new_pubkey,
+            ctx.program_id,
+            user_id,
+        )?;
+    require!(orphaned, ErrCode::RecoveryRequestStillActive);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Закрываем запрос
+    delete_pda_return_rent(request_pda, &ctx.accounts.rent_receiver, ctx.program_id)?;
+
+    msg!("🛟 Осиротевший запрос на восстановление id={} закрыт", user_id);
+    Ok(())
+}
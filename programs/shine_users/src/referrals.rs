@@ -0,0 +1,166 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_and_write_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::status::require_user_can_act;
+use crate::users::{read_user_by_id_pda, USER_ID_SEED_PREFIX};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Реферальные приглашения
+// ───────────────────────────────────────────────────────────────────────
+//
+// При регистрации можно указать id пригласившего пользователя (реферера).
+// Он сохраняется в UserById.referred_by нового пользователя, а счётчик
+// приглашений в PDA ["referrals=", referrer_id] увеличивается.
+// Если в настройках задана referral_fee_bps, часть комиссии уходит
+// на кошелёк реферера (его мастер-ключ) вместо fee_receiver.
+//
+// Аккаунты реферера передаются в необязательных именованных аккаунтах
+// (ReferrerAccounts) инструкций регистрации:
+// - referrer_id_pda: PDA ["userId=", referrer_id]
+// - referral_counter_pda: PDA ["referrals=", referrer_id] (mut)
+// - referrer_wallet: кошелёк реферера (mut) — только если referral_fee_bps > 0
+// Без реферера их можно не передавать.
+
+/// Префикс сида PDA счётчика приглашений
+pub const REFERRAL_COUNTER_SEED_PREFIX: &str = "referrals=";
+
+/// Версия формата сериализации ReferralCounter
+pub const REFERRAL_COUNTER_FORMAT_V1: u32 = 1;
+
+/// Размер записи V1: 4 + 8 + 8
+pub const REFERRAL_COUNTER_SIZE_V1: usize = 20;
+
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (REFERRAL_COUNTER_FORMAT_V1)
+/// [4..12]     = referrer_id: u64
+/// [12..20]    = referral_count: u64
+pub struct ReferralCounter {
+    pub referrer_id: u64,
+    pub referral_count: u64,
+}
+
+impl VersionedAccount for ReferralCounter {
+    const LATEST_FORMAT: u32 = REFERRAL_COUNTER_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        if format != REFERRAL_COUNTER_FORMAT_V1 {
+            return Err(error!(ErrCode::UnsupportedFormat));
+        }
+        Ok(ReferralCounter {
+            referrer_id: reader.read_u64()?,
+            referral_count: reader.read_u64()?,
+        })
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.referrer_id);
+        writer.write_u64(self.referral_count);
+    }
+}
+
+
+/// Необязательные аккаунты реферера в инструкциях регистрации
+#[derive(Accounts)]
+pub struct ReferrerAccounts<'info> {
+    /// CHECK: PDA UserById реферера ["userId=", referrer_id]. Проверяется вручную
+    pub referrer_id_pda: Option<AccountInfo<'info>>,
+
+    /// CHECK: PDA счётчика приглашений ["referrals=", referrer_id]. Проверяется вручную,
+    /// создаётся при первом приглашении
+    #[account(mut)]
+    pub referral_counter_pda: Option<AccountInfo<'info>>,
+
+    /// CHECK: Кошелёк реферера (его мастер-ключ), получает долю комиссии. Проверяется вручную;
+    /// нужен, только если в настройках задана referral_fee_bps
+    #[account(mut)]
+    pub referrer_wallet: Option<AccountInfo<'info>>,
+}
+
+/// Проверенные аккаунты реферера
+pub struct Referral<'a, 'info> {
+    pub referrer_id: u64,
+    pub counter_pda: &'a AccountInfo<'info>,
+    pub counter_bump: u8,
+    /// Кошелёк реферера (None, если доля рефереру не платится)
+    pub wallet: Option<&'a AccountInfo<'info>>,
+}
+
+/// Проверяет аккаунты реферера из `accounts`.
+/// Реферер должен существовать и иметь возможность действовать (не заблокирован).
+pub fn resolve_referral<'a, 'info>(
+    accounts: &'a ReferrerAccounts<'info>,
+    referrer_id: u64,
+    pays_referral_share: bool,
+    program_id: &Pubkey,
+) -> Result<Referral<'a, 'info>> {
+    // PDA реферера по ID
+    let id_string = referrer_id.to_string();
+    let (expected_id_pda, _) = Pubkey::find_program_address(
+        &[USER_ID_SEED_PREFIX.as_bytes(), id_string.as_bytes()], program_id);
+    let referrer_pda = accounts
+        .referrer_id_pda
+        .as_ref()
+        .ok_or_else(|| error!(ErrCode::ReferrerAccountNotFound))?;
+    require!(referrer_pda.key == &expected_id_pda, ErrCode::InvalidPdaAddress);
+    require!(referrer_pda.owner == program_id, ErrCode::InvalidReferrer);
+    let referrer = read_user_by_id_pda(referrer_pda, referrer_id, program_id)
+        .map_err(|_| error!(ErrCode::InvalidReferrer))?;
+    require_user_can_act(referrer.status).map_err(|_| error!(ErrCode::InvalidReferrer))?;
+
+    // Счётчик приглашений
+    let (expected_counter_pda, counter_bump) = Pubkey::find_program_address(
+        &[REFERRAL_COUNTER_SEED_PREFIX.as_bytes(), id_string.as_bytes()], program_id);
+    let counter_pda = accounts
+        .referral_counter_pda
+        .as_ref()
+        .ok_or_else(|| error!(ErrCode::ReferrerAccountNotFound))?;
+    require!(counter_pda.key == &expected_counter_pda, ErrCode::InvalidPdaAddress);
+
+    // Кошелёк реферера — его мастер-ключ
+    let wallet = if pays_referral_share {
+        let wallet = accounts
+            .referrer_wallet
+            .as_ref()
+            .ok_or_else(|| error!(ErrCode::ReferrerAccountNotFound))?;
+        require!(wallet.key == &referrer.pubkey, ErrCode::InvalidReferrer);
+        Some(wallet)
+    } else {
+        None
+    };
+
+    Ok(Referral { referrer_id, counter_pda, counter_bump, wallet })
+}
+
+/// Увеличивает счётчик приглашений реферера (PDA создаётся при первом приглашении)
+pub fn increment_referral_counter<'info>(
+    referral: &Referral<'_, 'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<u64> {
+    let pda = referral.counter_pda;
+    if pda.owner == &Pubkey::default() {
+        let counter = ReferralCounter { referrer_id: referral.referrer_id, referral_count: 1 };
+        let id_string = referral.referrer_id.to_string();
+        create_and_write_pda(
+            pda,
+            payer,
+            system_program,
+            program_id,
+            &[REFERRAL_COUNTER_SEED_PREFIX.as_bytes(), id_string.as_bytes(), &[referral.counter_bump]],
+            counter.encode(),
+            REFERRAL_COUNTER_SIZE_V1 as u64,
+        )?;
+        return Ok(1);
+    }
+
+    require!(pda.owner == program_id, ErrCode::InvalidPdaAddress);
+    let mut counter = ReferralCounter::decode(&safe_read_pda(pda))?;
+    counter.referral_count = counter.referral_count.saturating_add(1);
+    write_to_pda(pda, &counter.encode())?;
+    Ok(counter.referral_count)
+}
@@ -0,0 +1,331 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_pda, delete_pda_return_rent, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+use crate::config::read_registration_config;
+use crate::logins::take_over_login_hold;
+use crate::status::require_user_can_act;
+use crate::users::{
+    check_user_by_login_pda, read_user_by_id_pda, read_user_by_login_pda, serialize_user_by_login,
+    validate_login, write_user_by_id_pda, UserByLogin, LOGIN_SEED_PREFIX,
+};
+use crate::wallet_index::wallet_index_rename;
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Реестр зарезервированных логинов
+// ───────────────────────────────────────────────────────────────────────
+//
+// Один PDA на логин: ["reserved=", login]. Пока PDA существует, логин
+// нельзя занять ни регистрацией, ни переименованием. Добавляет и убирает
+// записи администратор из настроек регистрации; он же может выдать
+// зарезервированный логин конкретному пользователю (`grant_reserved_login`,
+// подписывает и сам пользователь).
+//
+// Служебные логины DEFAULT_RESERVED_LOGINS заносятся в реестр сразу при
+// создании настроек (`initialize_registration_config`), чтобы их нельзя было
+// занять между деплоем и первым вызовом администратора.
+
+/// Префикс PDA зарезервированного логина
+pub const RESERVED_LOGIN_SEED_PREFIX: &str = "reserved=";
+
+/// Версия формата сериализации ReservedLogin
+pub const RESERVED_LOGIN_FORMAT_V1: u32 = 1;
+
+/// Служебные логины, которые резервируются при создании настроек регистрации
+pub const DEFAULT_RESERVED_LOGINS: [&str; 3] = ["admin", "support", "solana"];
+
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (RESERVED_LOGIN_FORMAT_V1)
+/// [4..5]      = длина логина: u8
+/// [5..(5+len)] = логин
+/// [...]       = added_by: [u8; 32] (администратор, получит ренту при удалении)
+pub struct ReservedLogin {
+    pub login: String,
+    pub added_by: Pubkey,
+}
+
+/// 🔧 Сериализация / 🔄 десериализация ReservedLogin (через VersionedAccount)
+impl VersionedAccount for ReservedLogin {
+    const LATEST_FORMAT: u32 = RESERVED_LOGIN_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            RESERVED_LOGIN_FORMAT_V1 => Ok(ReservedLogin {
+                login: reader.read_short_string()?,
+                added_by: reader.read_pubkey()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_short_string(&self.login);
+        writer.write_pubkey(&self.added_by);
+    }
+}
+
+/// Сериализует `ReservedLogin` в последнем формате
+pub fn serialize_reserved_login(entry: &ReservedLogin) -> Vec<u8> {
+    entry.encode()
+}
+
+/// Десериализует `ReservedLogin`: определяет формат и читает нужную версию
+pub fn deserialize_reserved_login(data: &[u8]) -> Result<ReservedLogin> {
+    ReservedLogin::decode(data)
+}
+
+
+/// Проверяет адрес PDA ["reserved=", login], возвращает bump
+pub fn check_reserved_login_pda(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[RESERVED_LOGIN_SEED_PREFIX.as_bytes(), login.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверка при регистрации / переименовании: PDA резерва для логина
+/// передан и пуст (логин не зарезервирован)
+pub fn require_login_not_reserved(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<()> {
+    check_reserved_login_pda(pda_account, login, program_id)?;
+    require!(pda_account.owner != program_id || safe_read_pda(pda_account).is_empty(), ErrCode::LoginReserved);
+    Ok(())
+}
+
+/// Создаёт PDA ["reserved=", login] (платит и получит ренту при удалении `admin`)
+pub fn create_reserved_login_pda<'info>(
+    pda: &AccountInfo<'info>,
+    admin: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    login: &str,
+) -> Result<()> {
+    let bump = check_reserved_login_pda(pda, login, program_id)?;
+    require!(pda.owner == &Pubkey::default(), ErrCode::LoginReserved);
+
+    let serialized = serialize_reserved_login(&ReservedLogin {
+        login: login.to_string(),
+        added_by: *admin.key,
+    });
+    create_pda(
+        pda,
+        admin,
+        system_program,
+        program_id,
+        &[RESERVED_LOGIN_SEED_PREFIX.as_bytes(), login.as_bytes(), &[bump]],
+        serialized.len() as u64,
+    )?;
+    write_to_pda(pda, &serialized)
+}
+
+/// Проверяет, что подписант — администратор из настроек регистрации
+fn require_admin(admin: &AccountInfo, config_pda: &AccountInfo, program_id: &Pubkey) -> Result<()> {
+    let config = read_registration_config(config_pda, program_id)?;
+    require!(admin.key == &config.admin, ErrCode::InvalidSigner);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Добавление / удаление зарезервированного логина
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct ManageReservedLogin<'info> {
+    /// CHECK: Администратор из настроек регистрации. Проверяется вручную, платит за PDA / получает ренту
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA ["reserved=", login]. Проверяется вручную
+    #[account(mut)]
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Добавить логин в реестр. Уже занятый логин тоже можно зарезервировать —
+/// тогда он не освободится после переименования или удаления владельца.
+pub fn add_reserved_login(ctx: Context<ManageReservedLogin>, login: String) -> Result<()> {
+    require_admin(&ctx.accounts.admin, &ctx.accounts.registration_config, ctx.program_id)?;
+    validate_login(&login)?;
+
+    create_reserved_login_pda(
+        &ctx.accounts.reserved_login_pda,
+        &ctx.accounts.admin,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        &login,
+    )?;
+
+    msg!("🔒 Логин {} зарезервирован", login);
+    Ok(())
+}
+
+/// Убрать логин из реестра, рента — подписавшему администратору
+pub fn remove_reserved_login(ctx: Context<ManageReservedLogin>, login: String) -> Result<()> {
+    require_admin(&ctx.accounts.admin, &ctx.accounts.registration_config, ctx.program_id)?;
+
+    let pda = &ctx.accounts.reserved_login_pda;
+    check_reserved_login_pda(pda, &login, ctx.program_id)?;
+    let raw = safe_read_pda(pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    deserialize_reserved_login(&raw)?;
+
+    delete_pda_return_rent(pda, &ctx.accounts.admin, ctx.program_id)?;
+
+    msg!("🔓 Логин {} убран из резерва", login);
+    Ok(())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Выдача зарезервированного логина пользователю
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct GrantReservedLogin<'info> {
+    /// CHECK: Администратор из настроек регистрации. Проверяется вручную, платит за новый PDA логина
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA ["reserved=", login]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA текущего логина пользователя ["login=", old_login]. Проверяется вручную, закрывается
+    #[account(mut)]
+    pub old_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA выдаваемого логина ["login=", login]. Проверяется вручную
+    #[account(mut)]
+    pub new_login_pda: AccountInfo<'info>,
+
+    /// CHECK: Мастер-ключ пользователя (UserById.pubkey). Подтверждает смену логина,
+    /// получает ренту старого PDA логина
+    #[account(mut, signer)]
+    pub user_wallet: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: PDA удержания выдаваемого логина ["loginHold=", login]. Проверяется вручную
+    /// (пуст или удерживается за этим же пользователем — тогда закрывается)
+    #[account(mut)]
+    pub new_login_hold_pda: AccountInfo<'info>,
+}
+
+/// Меняет логин пользователя на зарезервированный (подписывают администратор
+/// и мастер-ключ пользователя):
+/// - создаёт PDA "login=" для нового логина (платит администратор)
+/// - закрывает старый PDA логина (рента пользователю)
+/// - обновляет "userId=" и обратный индекс
+/// - убирает логин из реестра (рента администратору)
+pub fn grant_reserved_login(ctx: Context<GrantReservedLogin>, user_id: u64, login: String) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Права администратора и сам резерв
+    require_admin(&ctx.accounts.admin, &ctx.accounts.registration_config, ctx.program_id)?;
+    let reserved_pda = &ctx.accounts.reserved_login_pda;
+    check_reserved_login_pda(reserved_pda, &login, ctx.program_id)?;
+    let raw = safe_read_pda(reserved_pda);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    deserialize_reserved_login(&raw)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Пользователь
+    let mut user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    require_user_can_act(user.status)?;
+    // Смену логина подтверждает сам пользователь
+    require!(ctx.accounts.user_wallet.key == &user.pubkey, ErrCode::InvalidSigner);
+    require!(login != user.login, ErrCode::InvalidLogin);
+
+    let old_pda = &ctx.accounts.old_login_pda;
+    let old_record = read_user_by_login_pda(old_pda, &user.login, ctx.program_id)?;
+    require!(old_record.id == user_id, ErrCode::InvalidPdaAddress);
+
+    // ───────────── ШАГ 3 ─────────────
+    // PDA нового логина должен быть свободен
+    let new_pda = &ctx.accounts.new_login_pda;
+    let bump_new = check_user_by_login_pda(new_pda, &login, ctx.program_id)?;
+    require!(new_pda.owner == &Pubkey::default(), ErrCode::UserAlreadyExists);
+    take_over_login_hold(
+        &ctx.accounts.new_login_hold_pda,
+        &ctx.accounts.user_wallet,
+        &login,
+        user_id,
+        ctx.program_id,
+    )?;
+
+    let new_record = UserByLogin {
+        login: login.clone(),
+        id: user_id,
+        pubkey: user.pubkey,
+        status: old_record.status,
+    };
+    let serialized_new = serialize_user_by_login(&new_record);
+    let system_program = ctx.accounts.system_program.to_account_info();
+    create_pda(
+        new_pda,
+        &ctx.accounts.admin,
+        &system_program,
+        ctx.program_id,
+        &[LOGIN_SEED_PREFIX.as_bytes(), login.as_bytes(), &[bump_new]],
+        serialized_new.len() as u64,
+    )?;
+    write_to_pda(new_pda, &serialized_new)?;
+
+    // ───────────── ШАГ 4 ─────────────
+    // Старый логин освобождается сразу
+    delete_pda_return_rent(old_pda, &ctx.accounts.user_wallet, ctx.program_id)?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Обновляем запись по ID и обратный индекс
+    let old_login = std::mem::replace(&mut user.login, login.clone());
+    write_user_by_id_pda(&ctx.accounts.user_by_id_pda, &ctx.accounts.admin, &system_program, &user)?;
+    wallet_index_rename(
+        &ctx.accounts.wallet_index_pda,
+        &ctx.accounts.admin,
+        &system_program,
+        ctx.program_id,
+        &user.pubkey,
+        user_id,
+        &login,
+    )?;
+
+    // ───────────── ШАГ 6 ─────────────
+    // Логин больше не в резерве — теперь его держит PDA "login="
+    delete_pda_return_rent(reserved_pda, &ctx.accounts.admin, ctx.program_id)?;
+
+    msg!("🎁 Пользователю id={} выдан логин {} (был {})", user_id, login, old_login);
+    Ok(())
+}
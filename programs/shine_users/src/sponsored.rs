@@ -0,0 +1,305 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{
+    create_and_write_pda, ed25519_signers_for_message, safe_read_pda, write_to_pda,
+};
+use common::utils::ErrCode;
+
+use crate::config::FeePayment;
+use crate::referrals::*; // ReferrerAccounts вместе с модулями, сгенерированными #[derive(Accounts)]
+use crate::token_fees::*; // TokenFeeAccounts — аналогично
+use crate::users::{register_one_dev_user, OneDevRegistrationAccounts};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Спонсируемая регистрация (режим релеера)
+// ───────────────────────────────────────────────────────────────────────
+//
+// Комиссию и ренту платит `payer` (релеер), а не пользователь.
+// Пользователь разрешает регистрацию ed25519-подписью своего ключа
+// над `sponsored_registration_message(...)`: подпись привязана к логину,
+// устройству, конкретному релееру, nonce и сроку действия.
+//
+// Nonce хранится в PDA ["sponsorNonce=", user_pubkey] и должен строго
+// возрастать — одну и ту же подпись нельзя использовать повторно
+// (например, после удаления пользователя и освобождения логина).
+// Вместе с nonce хранится срок действия последнего использованного
+// разрешения: `delete_user` закрывает PDA только после этого срока,
+// когда повторить старую подпись уже нельзя.
+
+/// Префикс сида PDA последнего nonce
+pub const SPONSOR_NONCE_SEED_PREFIX: &str = "sponsorNonce=";
+
+/// Версия формата сериализации SponsorNonce
+pub const SPONSOR_NONCE_FORMAT_V1: u32 = 1;
+
+/// Размер записи V1: 4 + 32 + 8 + 8
+pub const SPONSOR_NONCE_SIZE_V1: usize = 52;
+
+/// Максимальный срок действия разрешения — 1 час от текущего времени
+pub const MAX_SPONSORED_AUTH_TTL_SECONDS: i64 = 60 * 60;
+
+/// Домен подписи, которой пользователь разрешает спонсируемую регистрацию
+pub const SPONSORED_REGISTRATION_MESSAGE_DOMAIN: &[u8] = b"shine_users:sponsored_register:v1";
+
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (SPONSOR_NONCE_FORMAT_V1)
+/// [4..36]     = user_pubkey: [u8; 32]
+/// [36..44]    = last_nonce: u64
+/// [44..52]    = last_expires_at: i64
+pub struct SponsorNonce {
+    pub user_pubkey: Pubkey,
+    pub last_nonce: u64,
+    pub last_expires_at: i64,
+}
+
+impl SponsorNonce {
+    /// Истёк ли срок последнего использованного разрешения
+    pub fn is_replay_safe(&self, now: i64) -> bool {
+        now > self.last_expires_at
+    }
+}
+
+impl VersionedAccount for SponsorNonce {
+    const LATEST_FORMAT: u32 = SPONSOR_NONCE_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            SPONSOR_NONCE_FORMAT_V1 => Ok(SponsorNonce {
+                user_pubkey: reader.read_pubkey()?,
+                last_nonce: reader.read_u64()?,
+                last_expires_at: reader.read_i64()?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.user_pubkey);
+        writer.write_u64(self.last_nonce);
+        writer.write_i64(self.last_expires_at);
+    }
+}
+
+
+/// Сообщение, которое `user_pubkey` подписывает для релеера:
+/// домен + длина логина (u8) + логин + pubkey пользователя + ключ подписи устройства
+/// + X25519-ключ устройства + pubkey релеера + nonce (u64) + expires_at (i64)
+/// + есть ли реферер (u8: 0 / 1) + referrer_id (u64, 0 если нет)
+#[allow(clippy::too_many_arguments)]
+pub fn sponsored_registration_message(
+    login: &str,
+    user_pubkey: &Pubkey,
+    device_sign_pubkey: &Pubkey,
+    device_x25519_pubkey: &Pubkey,
+    payer: &Pubkey,
+    nonce: u64,
+    expires_at: i64,
+    referrer_id: Option<u64>,
+) -> Vec<u8> {
+    let mut writer = ByteWriter::with_capacity(SPONSORED_REGISTRATION_MESSAGE_DOMAIN.len() + 1 + login.len() + 128 + 25);
+    writer.write_bytes(SPONSORED_REGISTRATION_MESSAGE_DOMAIN);
+    writer.write_short_string(login);
+    writer.write_pubkey(user_pubkey);
+    writer.write_pubkey(device_sign_pubkey);
+    writer.write_pubkey(device_x25519_pubkey);
+    writer.write_pubkey(payer);
+    writer.write_u64(nonce);
+    writer.write_i64(expires_at);
+    writer.write_u8(referrer_id.is_some() as u8);
+    writer.write_u64(referrer_id.unwrap_or(0));
+    writer.into_inner()
+}
+
+
+/// Проверяет адрес PDA ["sponsorNonce=", user_pubkey], возвращает bump
+pub fn check_sponsor_nonce_pda(
+    pda: &AccountInfo,
+    user_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[SPONSOR_NONCE_SEED_PREFIX.as_bytes(), user_pubkey.as_ref()], program_id);
+    require!(pda.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверяет nonce и сохраняет его (и срок разрешения) как последний использованный.
+/// PDA создаётся при первой спонсируемой регистрации (платит `payer`).
+fn consume_sponsor_nonce<'info>(
+    pda: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    user_pubkey: &Pubkey,
+    nonce: u64,
+    expires_at: i64,
+) -> Result<()> {
+    let bump = check_sponsor_nonce_pda(pda, user_pubkey, program_id)?;
+
+    if pda.owner == &Pubkey::default() {
+        let record = SponsorNonce { user_pubkey: *user_pubkey, last_nonce: nonce, last_expires_at: expires_at };
+        return create_and_write_pda(
+            pda,
+            payer,
+            system_program,
+            program_id,
+            &[SPONSOR_NONCE_SEED_PREFIX.as_bytes(), user_pubkey.as_ref(), &[bump]],
+            record.encode(),
+            SPONSOR_NONCE_SIZE_V1 as u64,
+        );
+    }
+
+    require!(pda.owner == program_id, ErrCode::InvalidPdaAddress);
+    let mut record = SponsorNonce::decode(&safe_read_pda(pda))?;
+    require!(nonce > record.last_nonce, ErrCode::NonceAlreadyUsed);
+    record.last_nonce = nonce;
+    record.last_expires_at = expires_at;
+    write_to_pda(pda, &record.encode())
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Спонсируемая регистрация пользователя с одним устройством
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов (как у `RegisterUserWithOneDev`, но подписант — релеер)
+#[derive(Accounts)]
+pub struct RegisterUserSponsored<'info> {
+    /// CHECK: Релеер — платит комиссию и ренту. Может не совпадать с пользователем
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+
+    /// CHECK: PDA-счётчик количества пользователей (общий или любой из шардов). Проверяется вручную по сиду внутри функции
+    #[account(mut)]
+    pub user_counter: AccountInfo<'info>,
+
+    /// CHECK: PDA для UserByLogin: должен быть по сиду ["login=", login]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_login_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Получатель комиссии. Проверяется вручную по адресу из настроек регистрации
+    #[account(mut)]
+    pub fee_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA реестра ["reserved=", login]. Проверяется вручную (должен быть пуст)
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA удержания ["loginHold=", login]. Проверяется вручную (должен быть пуст)
+    pub login_hold_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA последнего nonce ["sponsorNonce=", user_pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub sponsor_nonce_pda: AccountInfo<'info>,
+
+    /// CHECK: Sysvar Instructions — для проверки подписей пользователя и устройства. Адрес проверяется вручную
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Необязательные аккаунты реферера (нужны, если передан referrer_id)
+    pub referrer: ReferrerAccounts<'info>,
+
+    /// Необязательные аккаунты для оплаты комиссии токеном (нужны при fee_payment = Token)
+    pub token_fee: TokenFeeAccounts<'info>,
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Инструкция спонсируемой регистрации
+/// ─────────────────────────────────────────────────────────────
+///
+/// В той же транзакции должны быть две ed25519-инструкции:
+/// - `user_pubkey` подписал `sponsored_registration_message(...)`;
+/// - `device_sign_pubkey` подписал `device_registration_message(...)`.
+#[allow(clippy::too_many_arguments)]
+pub fn register_user_sponsored<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RegisterUserSponsored<'info>>,
+    login: String,
+    user_pubkey: Pubkey,
+    device_sign_pubkey: Pubkey,
+    device_x25519_pubkey: Pubkey,
+    nonce: u64,
+    expires_at: i64,
+    referrer_id: Option<u64>,
+    fee_payment: FeePayment,
+) -> Result<()> {
+    msg!("🔐 Спонсируемая регистрация логина: {}", login);
+
+    // ───────────── ШАГ 1 ─────────────
+    // Срок действия разрешения: не истёк и не дальше MAX_SPONSORED_AUTH_TTL_SECONDS
+    let now = Clock::get()?.unix_timestamp;
+    require!(expires_at >= now, ErrCode::AuthorizationExpired);
+    require!(expires_at - now <= MAX_SPONSORED_AUTH_TTL_SECONDS, ErrCode::AuthorizationExpired);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Подпись пользователя над разрешением (привязана к этому релееру)
+    let auth_message = sponsored_registration_message(
+        &login,
+        &user_pubkey,
+        &device_sign_pubkey,
+        &device_x25519_pubkey,
+        ctx.accounts.payer.key,
+        nonce,
+        expires_at,
+        referrer_id,
+    );
+    let signers = ed25519_signers_for_message(&ctx.accounts.instructions_sysvar, &auth_message)?;
+    require!(signers.contains(&user_pubkey), ErrCode::InvalidEd25519Instruction);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Nonce должен быть больше последнего использованного
+    let system_program = ctx.accounts.system_program.to_account_info();
+    consume_sponsor_nonce(
+        &ctx.accounts.sponsor_nonce_pda,
+        &ctx.accounts.payer,
+        &system_program,
+        ctx.program_id,
+        &user_pubkey,
+        nonce,
+        expires_at,
+    )?;
+
+    // ───────────── ШАГ 4 ─────────────
+    // Общая часть регистрации: всё оплачивает релеер
+    let accounts = OneDevRegistrationAccounts {
+        payer: &ctx.accounts.payer,
+        user_counter: &ctx.accounts.user_counter,
+        user_by_login_pda: &ctx.accounts.user_by_login_pda,
+        system_program,
+        fee_receiver: &ctx.accounts.fee_receiver,
+        wallet_index_pda: &ctx.accounts.wallet_index_pda,
+        registration_config: &ctx.accounts.registration_config,
+        reserved_login_pda: &ctx.accounts.reserved_login_pda,
+        login_hold_pda: &ctx.accounts.login_hold_pda,
+        instructions_sysvar: &ctx.accounts.instructions_sysvar,
+        referrer: &ctx.accounts.referrer,
+        token_fee: &ctx.accounts.token_fee,
+        id_pda_candidates: ctx.remaining_accounts,
+    };
+    let new_id = register_one_dev_user(
+        &accounts,
+        ctx.program_id,
+        &login,
+        user_pubkey,
+        device_sign_pubkey,
+        device_x25519_pubkey,
+        referrer_id,
+        fee_payment,
+        false,
+    )?;
+
+    msg!("🤝 id={} зарегистрирован за счёт {}", new_id, ctx.accounts.payer.key);
+    Ok(())
+}
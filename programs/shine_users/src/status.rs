@@ -0,0 +1,186 @@
+use anchor_lang::prelude::*;
+use common::utils::write_to_pda;
+use common::utils::ErrCode;
+
+use crate::config::read_registration_config;
+use crate::users::{
+    read_user_by_id_pda, read_user_by_login_pda, serialize_user_by_login, write_user_by_id_pda,
+};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Статус пользователя и модерация
+// ───────────────────────────────────────────────────────────────────────
+//
+// Статус хранится в обеих записях: "login=" (UserByLogin) и "userId="
+// (UserById, начиная с формата V2). Меняет его модератор (ключ `moderator`
+// из настроек регистрации) инструкциями suspend_user / reinstate_user / ban_user,
+// каждая смена — событие UserStatusChanged.
+//
+// Разрешённые переходы:
+//   Pending   → Active, Suspended, Banned, Deleted
+//   Active    → Suspended, Banned, Deleted
+//   Suspended → Active, Banned
+//   Banned    — окончательный: бан не снимается, иначе он ничем не отличался
+//               бы от блокировки; удалиться забаненный тоже не может
+//   Deleted   — окончательный: delete_user заменяет запись "userId=" надгробием
+//               (USER_BY_ID_TOMBSTONE_FORMAT), в котором и хранится этот статус
+
+
+/// Статус пользователя (в записях хранится как u32)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserStatus {
+    Pending = 0,
+    Active = 1,
+    Suspended = 2,
+    Banned = 3,
+    Deleted = 4,
+}
+
+impl UserStatus {
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_u32(value: u32) -> Result<Self> {
+        match value {
+            0 => Ok(UserStatus::Pending),
+            1 => Ok(UserStatus::Active),
+            2 => Ok(UserStatus::Suspended),
+            3 => Ok(UserStatus::Banned),
+            4 => Ok(UserStatus::Deleted),
+            _ => Err(error!(ErrCode::DeserializationError)),
+        }
+    }
+
+    /// Можно ли перейти из текущего статуса в `next`
+    pub fn can_transition_to(self, next: UserStatus) -> bool {
+        use UserStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Active)
+                | (Pending, Suspended)
+                | (Pending, Banned)
+                | (Pending, Deleted)
+                | (Active, Suspended)
+                | (Active, Banned)
+                | (Active, Deleted)
+                | (Suspended, Active)
+                | (Suspended, Banned)
+        )
+    }
+}
+
+/// Отказ, если пользователь заблокирован, забанен или удалён.
+/// Вызывается всеми инструкциями, которые действуют от имени пользователя.
+pub fn require_user_can_act(status: UserStatus) -> Result<()> {
+    match status {
+        UserStatus::Suspended => Err(error!(ErrCode::UserSuspended)),
+        UserStatus::Banned => Err(error!(ErrCode::UserBanned)),
+        UserStatus::Deleted => Err(error!(ErrCode::UserDeleted)),
+        UserStatus::Pending | UserStatus::Active => Ok(()),
+    }
+}
+
+
+/// Событие смены статуса пользователя
+#[event]
+pub struct UserStatusChanged {
+    pub user_id: u64,
+    pub login: String,
+    pub old_status: u32,
+    pub new_status: u32,
+    pub moderator: Pubkey,
+    pub timestamp: i64,
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Модерация: смена статуса пользователя
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов (общая для suspend_user / reinstate_user / ban_user)
+#[derive(Accounts)]
+pub struct ModerateUser<'info> {
+    /// CHECK: Модератор из настроек регистрации. Проверяется вручную,
+    /// доплачивает ренту при переходе записи на последний формат
+    #[account(mut, signer)]
+    pub moderator: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA UserByLogin по сиду ["login=", login]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA UserById по сиду ["userId=", id]. Проверяется вручную
+    #[account(mut)]
+    pub user_by_id_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+
+/// Общая логика: проверка модератора и перехода, запись статуса в обе записи, событие
+fn set_user_status(ctx: Context<ModerateUser>, user_id: u64, new_status: UserStatus) -> Result<()> {
+    // ───────────── ШАГ 1 ─────────────
+    // Подписать должен модератор из настроек регистрации
+    let moderator = read_registration_config(&ctx.accounts.registration_config, ctx.program_id)?.moderator;
+    require!(ctx.accounts.moderator.key == &moderator, ErrCode::InvalidSigner);
+
+    // ───────────── ШАГ 2 ─────────────
+    // Читаем обе записи пользователя
+    let mut user = read_user_by_id_pda(&ctx.accounts.user_by_id_pda, user_id, ctx.program_id)?;
+    let mut user_login = read_user_by_login_pda(&ctx.accounts.user_by_login_pda, &user.login, ctx.program_id)?;
+    require!(user_login.id == user_id, ErrCode::InvalidPdaAddress);
+
+    // ───────────── ШАГ 3 ─────────────
+    // Проверяем переход
+    let old_status = user.status;
+    require!(old_status.can_transition_to(new_status), ErrCode::InvalidStatusTransition);
+
+    // ───────────── ШАГ 4 ─────────────
+    // Сохраняем статус в обе записи (размер записи по логину не меняется)
+    user.status = new_status;
+    user_login.status = new_status;
+    write_user_by_id_pda(
+        &ctx.accounts.user_by_id_pda,
+        &ctx.accounts.moderator,
+        &ctx.accounts.system_program.to_account_info(),
+        &user,
+    )?;
+    write_to_pda(&ctx.accounts.user_by_login_pda, &serialize_user_by_login(&user_login))?;
+
+    // ───────────── ШАГ 5 ─────────────
+    // Событие для индексаторов
+    emit!(UserStatusChanged {
+        user_id,
+        login: user.login.clone(),
+        old_status: old_status.as_u32(),
+        new_status: new_status.as_u32(),
+        moderator,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("🛡 Статус пользователя login={} id={}: {:?} → {:?}", user.login, user_id, old_status, new_status);
+    Ok(())
+}
+
+/// Временная блокировка пользователя
+pub fn suspend_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+    set_user_status(ctx, user_id, UserStatus::Suspended)
+}
+
+/// Снятие блокировки (бан не снимается)
+pub fn reinstate_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+    set_user_status(ctx, user_id, UserStatus::Active)
+}
+
+/// Бан пользователя
+pub fn ban_user(ctx: Context<ModerateUser>, user_id: u64) -> Result<()> {
+    set_user_status(ctx, user_id, UserStatus::Banned)
+}
@@ -0,0 +1,203 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use common::utils::ErrCode;
+use std::str::FromStr;
+
+use crate::config::{RegistrationConfig, REG_FLAG_REGISTRATION_OPEN};
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Оплата комиссии за регистрацию в SPL-токене
+// ───────────────────────────────────────────────────────────────────────
+//
+// Крейта spl-token в сборке нет, поэтому инструкция TransferChecked
+// собирается вручную, а токен-аккаунты и mint разбираются по их
+// фиксированной раскладке (поддерживается только классическая программа Token;
+// mint программы Token-2022 отклоняется ещё при настройке, в set_token_registration_fee).
+//
+// Аккаунты передаются в необязательных именованных аккаунтах (TokenFeeAccounts)
+// инструкций регистрации и сверяются с настройками регистрации:
+// - fee_mint: mint (`fee_mint`)
+// - fee_token_account: токен-аккаунт получателя (`fee_token_account`, mut)
+// - payer_token_account: токен-аккаунт плательщика этого mint (mut)
+// - token_program: программа Token
+// При оплате в SOL их можно не передавать.
+
+/// Программа SPL Token
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGysXZoRmJ3r6Kw1L9x4dAVx1tG";
+
+/// Программа SPL Token-2022 (не поддерживается)
+pub const SPL_TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+/// Номер инструкции TransferChecked в программе Token
+const TOKEN_IX_TRANSFER_CHECKED: u8 = 12;
+
+/// Размер токен-аккаунта и mint (без расширений)
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const MINT_LEN: usize = 82;
+
+/// Состояние токен-аккаунта «инициализирован» (байт 108)
+const TOKEN_ACCOUNT_STATE_INITIALIZED: u8 = 1;
+
+
+/// Необязательные аккаунты для оплаты комиссии токеном в инструкциях регистрации
+#[derive(Accounts)]
+pub struct TokenFeeAccounts<'info> {
+    /// CHECK: Mint из настроек регистрации (`fee_mint`). Проверяется вручную
+    pub fee_mint: Option<AccountInfo<'info>>,
+
+    /// CHECK: Токен-аккаунт получателя из настроек (`fee_token_account`). Проверяется вручную
+    #[account(mut)]
+    pub fee_token_account: Option<AccountInfo<'info>>,
+
+    /// CHECK: Токен-аккаунт плательщика этого mint. Проверяется вручную
+    #[account(mut)]
+    pub payer_token_account: Option<AccountInfo<'info>>,
+
+    /// CHECK: Программа Token. Адрес проверяется вручную
+    pub token_program: Option<AccountInfo<'info>>,
+}
+
+/// Отказ, если аккаунт принадлежит не классической программе Token
+/// (отдельное сообщение для Token-2022)
+pub fn require_classic_token_owner(account: &AccountInfo) -> Result<()> {
+    let token_program_id = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+    if account.owner != &token_program_id {
+        if account.owner == &Pubkey::from_str(SPL_TOKEN_2022_PROGRAM_ID).unwrap() {
+            msg!("Token-2022 не поддерживается: {}", account.key);
+        }
+        return Err(error!(ErrCode::InvalidTokenAccount));
+    }
+    Ok(())
+}
+
+/// Нужные нам поля токен-аккаунта:
+/// [0..32] mint, [32..64] owner, [64..72] amount, [108] state
+pub struct TokenAccountData {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Разбирает токен-аккаунт программы Token (только инициализированный)
+pub fn read_token_account(account: &AccountInfo, token_program_id: &Pubkey) -> Result<TokenAccountData> {
+    require!(account.owner == token_program_id, ErrCode::InvalidTokenAccount);
+    let data = account.try_borrow_data()?;
+    require!(data.len() == TOKEN_ACCOUNT_LEN, ErrCode::InvalidTokenAccount);
+    require!(data[108] == TOKEN_ACCOUNT_STATE_INITIALIZED, ErrCode::InvalidTokenAccount);
+    Ok(TokenAccountData {
+        mint: Pubkey::new_from_array(data[0..32].try_into().unwrap()),
+        owner: Pubkey::new_from_array(data[32..64].try_into().unwrap()),
+        amount: u64::from_le_bytes(data[64..72].try_into().unwrap()),
+    })
+}
+
+/// Возвращает decimals mint: [44] decimals, [45] is_initialized
+pub fn read_mint_decimals(mint: &AccountInfo, token_program_id: &Pubkey) -> Result<u8> {
+    require!(mint.owner == token_program_id, ErrCode::InvalidTokenAccount);
+    let data = mint.try_borrow_data()?;
+    require!(data.len() == MINT_LEN, ErrCode::InvalidTokenAccount);
+    require!(data[45] == 1, ErrCode::InvalidTokenAccount);
+    Ok(data[44])
+}
+
+/// Инструкция TransferChecked: тег + amount (u64) + decimals (u8).
+/// Аккаунты: источник (mut), mint, получатель (mut), владелец источника (signer)
+pub fn transfer_checked_instruction(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Instruction {
+    let mut data = Vec::with_capacity(10);
+    data.push(TOKEN_IX_TRANSFER_CHECKED);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Списание комиссии за регистрацию в токене
+/// ─────────────────────────────────────────────────────────────
+///
+/// Проверяет, что регистрация открыта и оплата токеном настроена,
+/// сверяет аккаунты из `accounts` с настройками, проверяет mint и владельцев
+/// токен-аккаунтов и переводит `fee_token_amount` от `payer`.
+pub fn charge_registration_fee_in_token<'info>(
+    config: &RegistrationConfig,
+    payer: &AccountInfo<'info>,
+    accounts: &TokenFeeAccounts<'info>,
+) -> Result<()> {
+    require!(config.is_enabled(REG_FLAG_REGISTRATION_OPEN), ErrCode::RegistrationDisabled);
+    require!(config.token_fee_enabled(), ErrCode::TokenFeeDisabled);
+
+    let token_program_id = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+    let require_account = |account: &Option<AccountInfo<'info>>, key: Option<&Pubkey>| {
+        let account = account.clone().ok_or_else(|| error!(ErrCode::InvalidTokenAccount))?;
+        if let Some(key) = key {
+            require!(account.key == key, ErrCode::InvalidTokenAccount);
+        }
+        Ok::<_, Error>(account)
+    };
+
+    // ───────────── ШАГ 1 ─────────────
+    // Программа Token и mint из настроек
+    let token_program = require_account(&accounts.token_program, Some(&token_program_id))?;
+    let mint = require_account(&accounts.fee_mint, Some(&config.fee_mint))?;
+    let decimals = read_mint_decimals(&mint, &token_program_id)?;
+
+    // ───────────── ШАГ 2 ─────────────
+    // Получатель — токен-аккаунт из настроек, того же mint
+    let destination = require_account(&accounts.fee_token_account, Some(&config.fee_token_account))?;
+    let destination_data = read_token_account(&destination, &token_program_id)?;
+    require!(destination_data.mint == config.fee_mint, ErrCode::InvalidTokenAccount);
+
+    if config.fee_token_amount == 0 {
+        return Ok(());
+    }
+
+    // ───────────── ШАГ 3 ─────────────
+    // Источник — токен-аккаунт плательщика этого mint с достаточным балансом
+    let source = require_account(&accounts.payer_token_account, None)?;
+    require!(source.key != destination.key, ErrCode::InvalidTokenAccount);
+    let source_data = read_token_account(&source, &token_program_id)?;
+    require!(
+        source_data.mint == config.fee_mint
+            && source_data.owner == *payer.key
+            && source_data.amount >= config.fee_token_amount,
+        ErrCode::InvalidTokenAccount
+    );
+
+    // ───────────── ШАГ 4 ─────────────
+    // CPI TransferChecked (плательщик — signer внешней транзакции)
+    let instruction = transfer_checked_instruction(
+        &token_program_id,
+        source.key,
+        mint.key,
+        destination.key,
+        payer.key,
+        config.fee_token_amount,
+        decimals,
+    );
+    invoke(
+        &instruction,
+        &[source, mint, destination, payer.clone(), token_program],
+    )?;
+
+    msg!("🪙 Комиссия {} единиц токена {} оплачена", config.fee_token_amount, config.fee_mint);
+    Ok(())
+}
@@ -1,24 +1,40 @@
-        use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{
-    program::invoke,
-    // program::invoke_signed,
-    system_instruction,
+use anchor_lang::prelude::*;
+use common::utils::{
+    create_and_write_pda, create_pda, ed25519_signers_for_message, realloc_pda, safe_read_pda,
+    write_to_pda,
 };
-use std::str::FromStr;
-use common::utils::{create_and_write_pda, create_pda, safe_read_pda, write_to_pda};
+use common::codec::{read_format_tag, ByteReader, ByteWriter, VersionedAccount};
 use common::utils::ErrCode;
 
+use crate::config::{
+    charge_registration_fee, read_registration_config, FeePayment, REG_FLAG_COMMIT_REQUIRED,
+    REG_FLAG_STEP_ONE_ENABLED,
+};
+use crate::counter_shards::allocate_user_id;
+use crate::logins::require_login_not_held;
+// Глобальный импорт: #[derive(Accounts)] у RegisterUserWithOneDev использует модули,
+// сгенерированные для вложенной структуры ReferrerAccounts
+use crate::referrals::*;
+use crate::reserved_logins::require_login_not_reserved;
+use crate::status::UserStatus;
+use crate::token_fees::*; // TokenFeeAccounts вместе с модулями, сгенерированными #[derive(Accounts)]
+use crate::wallet_index::wallet_index_add;
+
 
 // Префикс для PDA пользователей по логину
 const USER_SEED_PREFIX: &str = "u=";
-// Постоянный адрес получателя комиссии    key3
-pub const REGISTRATION_FEE_RECEIVER: &str = "6bFc5Gz5qF172GQhK5HpDbWs8F6qcSxdHn5XqAstf1fY";
 
 
 
 
 
 
+/// Версия формата сериализации UserByLogin
+pub const USER_BY_LOGIN_FORMAT_V1: u32 = 1;
+
+/// Последний формат UserByLogin (в нём пишет serialize_user_by_login)
+pub const USER_BY_LOGIN_FORMAT_LATEST: u32 = USER_BY_LOGIN_FORMAT_V1;
+
 /// ───────────────────────────────────────────────────────────────────────
 ///  Структура UserByLogin
 /// ───────────────────────────────────────────────────────────────────────
@@ -27,7 +43,7 @@ pub const REGISTRATION_FEE_RECEIVER: &str = "6bFc5Gz5qF172GQhK5HpDbWs8F6qcSxdHn5
 /// - login: String               — строка (до 255 байт, храним длину + содержимое)
 /// - id: u64                     — 8 байт (целое число)
 /// - pubkey: Pubkey             — 32 байта
-/// - status: u32                — 4 байта
+/// - status: UserStatus         — 4 байта (хранится как u32)
 ///
 /// Формат сериализованных данных:
 /// [0..4]      = format_type: u32 (всегда 1)
@@ -35,124 +51,50 @@ pub const REGISTRATION_FEE_RECEIVER: &str = "6bFc5Gz5qF172GQhK5HpDbWs8F6qcSxdHn5
 /// [5..(5+len)] = логин
 /// [...]       = id: u64
 /// [...]       = pubkey: [u8; 32]
-/// [...]       = status: u32
+/// [...]       = status: u32 (UserStatus)
 /// Всего: 4 + 1 + логин + 8 + 32 + 4 байта
 /// ------------------------------------------------------------------------
-
 pub struct UserByLogin {
     pub login: String,    // логин (строка)
     pub id: u64,          // числовой ID
     pub pubkey: Pubkey,   // публичный ключ
-    pub status: u32,      // статус
+    pub status: UserStatus, // статус
 }
 
 /// ───────────────────────────────────────────────────────────────────────
-/// 🔧 Сериализация serialize_user_by_login()
+/// 🔧 Сериализация / 🔄 десериализация UserByLogin (через VersionedAccount)
 /// ───────────────────────────────────────────────────────────────────────
-///
-/// Сериализует `UserByLogin` в `Vec<u8>`, начиная с format_type = 1
-pub fn serialize_user_by_login(user: &UserByLogin) -> Vec<u8> {
-    let mut result = Vec::new();
-
-    // ───────────────────────────────
-    // 1. format_type (4 байта)
-    // ───────────────────────────────
-    result.extend_from_slice(&1u32.to_le_bytes()); // формат 1
-
-    // ───────────────────────────────
-    // 2. login: длина (u8) + байты
-    // ───────────────────────────────
-    let login_bytes = user.login.as_bytes();
-    let login_len = login_bytes.len();
-    let login_len_u8 = login_len.min(255) as u8; // максимум 255 байт
-
-    result.push(login_len_u8); // длина
-    result.extend_from_slice(&login_bytes[..login_len_u8 as usize]);
-
-    // ───────────────────────────────
-    // 3. id (u64)
-    // ───────────────────────────────
-    result.extend_from_slice(&user.id.to_le_bytes());
-
-    // ───────────────────────────────
-    // 4. pubkey (32 байта)
-    // ───────────────────────────────
-    result.extend_from_slice(user.pubkey.as_ref());
-
-    // ───────────────────────────────
-    // 5. status (4 байта)
-    // ───────────────────────────────
-    result.extend_from_slice(&user.status.to_le_bytes());
-
-    result
-}
-
-/// ───────────────────────────────────────────────────────────────────────
-///🔄 Десериализация deserialize_user_by_login()
-/// ───────────────────────────────────────────────────────────────────────
-///
-/// Определяет формат и вызывает соответствующую реализацию
-pub fn deserialize_user_by_login(data: &[u8]) -> Result<UserByLogin> {
-    // Проверка длины
-    if data.len() < 4 {
-        return Err(error!(ErrCode::DeserializationError));
+impl VersionedAccount for UserByLogin {
+    const LATEST_FORMAT: u32 = USER_BY_LOGIN_FORMAT_LATEST;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            USER_BY_LOGIN_FORMAT_V1 => Ok(UserByLogin {
+                login: reader.read_short_string()?,
+                id: reader.read_u64()?,
+                pubkey: reader.read_pubkey()?,
+                status: UserStatus::from_u32(reader.read_u32()?)?,
+            }),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
     }
 
-    // Считываем format_type
-    let format_type = u32::from_le_bytes(data[0..4].try_into().unwrap());
-
-    match format_type {
-        1 => deserialize_user_by_login_format1(data),
-        _ => Err(error!(ErrCode::UnsupportedFormat)),
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_short_string(&self.login); // длина (u8) + байты, максимум 255
+        writer.write_u64(self.id);
+        writer.write_pubkey(&self.pubkey);
+        writer.write_u32(self.status.as_u32());
     }
 }
 
-/// ───────────────────────────────────────────────────────────────────────
-/// Распаковываем user_by_login формат 1:
-/// ───────────────────────────────────────────────────────────────────────
-fn deserialize_user_by_login_format1(data: &[u8]) -> Result<UserByLogin> {
-    let mut offset = 4; // пропускаем format_type
-
-    // 1. login (длина + строка)
-    let login_len = data.get(offset).ok_or(ErrCode::DeserializationError)? as &u8;
-    offset += 1;
-
-    let login_end = offset + (*login_len as usize);
-    if data.len() < login_end {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-
-    let login = std::str::from_utf8(&data[offset..login_end])
-        .map_err(|_| error!(ErrCode::DeserializationError))?
-        .to_string();
-    offset = login_end;
-
-    // 2. id (u64)
-    if data.len() < offset + 8 {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-    let id = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
-    offset += 8;
-
-    // 3. pubkey (32 байта)
-    if data.len() < offset + 32 {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-    let pubkey = Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
-    offset += 32;
-
-    // 4. status (u32)
-    if data.len() < offset + 4 {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-    let status = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+/// Сериализует `UserByLogin` в `Vec<u8>`, начиная с format_type = 1
+pub fn serialize_user_by_login(user: &UserByLogin) -> Vec<u8> {
+    user.encode()
+}
 
-    Ok(UserByLogin {
-        login,
-        id,
-        pubkey,
-        status,
-    })
+/// Десериализует `UserByLogin`: определяет формат и читает нужную версию
+pub fn deserialize_user_by_login(data: &[u8]) -> Result<UserByLogin> {
+    UserByLogin::decode(data)
 }
 
 
@@ -164,8 +106,6 @@ fn deserialize_user_by_login_format1(data: &[u8]) -> Result<UserByLogin> {
 /// ───────────────────────────────────────────────────────────────────────
 /// ───────────────────────────────────────────────────────────────────────
 ///
-
-
 /// Константа для сидов PDA-счётчика пользователей
 pub const USER_COUNTER_SEED: &str = "user_counter";
 
@@ -292,24 +232,22 @@ pub fn initialize_user_counter<'info>(
 /// ───────────────────────────────────────────────────────────────────────
 /// РЕГИСТРАЦИЯ пользователя (шаг ПЕРВЫЙ) по логину
 /// ───────────────────────────────────────────────────────────────────────
-
-
 pub fn register_user_step_one(
     ctx: Context<RegisterUserStepOne>,
     login: String,
     user_pubkey: Pubkey,
 ) -> Result<()> {
     // ───────────────────────────────────────────────
-    // 1. Проверка валидности логина
+    // 1. Подписать должен сам пользователь: иначе любой мог бы
+    //    занять логин за чужой ключ
+    require!(ctx.accounts.signer.key == &user_pubkey, ErrCode::InvalidSigner);
+
+    // Проверка валидности логина
     validate_login(&login)?; // вызывает функцию ниже
 
     // ───────────────────────────────────────────────
-    // 2. Проверяем, что логин не является "особым" (зарезервированным)
-    let reserved_logins = ["admin", "support", "solana"]; // можно расширить
-    require!(
-        !reserved_logins.contains(&login.as_str()),
-        ErrCode::InvalidLogin
-    );
+    // 2. Проверяем, что логин не зарезервирован (реестр "reserved=")
+    require_login_not_reserved(&ctx.accounts.reserved_login_pda, &login, ctx.program_id)?;
 
     // ───────────────────────────────────────────────
     // 3. Проверка PDA
@@ -328,39 +266,29 @@ pub fn register_user_step_one(
     }
 
     // ───────────────────────────────────────────────
-    // 5. Перевод 0.01 SOL комиссии за регистрацию
-    let expected_receiver = Pubkey::from_str(REGISTRATION_FEE_RECEIVER)
-        .map_err(|_| error!(ErrCode::InvalidLogin))?;
-    require!(
-        ctx.accounts.fee_receiver.key == &expected_receiver,
-        ErrCode::InvalidPdaAddress
-    );
-
-    let transfer_instruction = system_instruction::transfer(
-        ctx.accounts.signer.key,
-        ctx.accounts.fee_receiver.key,
-        10_000_000, // 0.01 SOL в лампортах
-    );
-    invoke(
-        &transfer_instruction,
-        &[
-            ctx.accounts.signer.clone(),
-            ctx.accounts.fee_receiver.clone(),
-            ctx.accounts.system_program.to_account_info(),
-        ],
+    // 5. Перевод комиссии за регистрацию (сумма и получатель — из настроек)
+    let config = read_registration_config(&ctx.accounts.registration_config, ctx.program_id)?;
+    require!(config.is_enabled(REG_FLAG_STEP_ONE_ENABLED), ErrCode::RegistrationDisabled);
+    require!(!config.is_enabled(REG_FLAG_COMMIT_REQUIRED), ErrCode::LoginCommitmentRequired);
+    charge_registration_fee(
+        &config,
+        &ctx.accounts.signer,
+        &ctx.accounts.fee_receiver,
+        None,
+        &ctx.accounts.system_program.to_account_info(),
     )?;
 
     // ───────────────────────────────────────────────
-    // 6. Получаем текущий счётчик
-    let current_id = read_user_counter_pda(&ctx.accounts.user_counter, ctx.program_id)?;
+    // 6. Получаем новый ID (из общего счётчика или шарда, счётчик сразу обновляется)
+    let new_id = allocate_user_id(&ctx.accounts.user_counter, ctx.program_id)?;
 
     // ───────────────────────────────────────────────
     // 7. Создаём структуру UserByLogin
     let user = UserByLogin {
         login: login.clone(),
-        id: current_id + 1,
+        id: new_id,
         pubkey: user_pubkey,
-        status: 0,
+        status: UserStatus::Pending,
     };
 
     let serialized_user = serialize_user_by_login(&user);
@@ -380,14 +308,6 @@ pub fn register_user_step_one(
 
     write_to_pda(&ctx.accounts.user_by_login_pda, &serialized_user)?;
 
-    // ───────────────────────────────────────────────
-    // 9. Обновляем счётчик пользователей
-    write_user_counter_pda(
-        &ctx.accounts.user_counter,
-        ctx.program_id,
-        current_id + 1,
-    )?;
-
     msg!("✅ Пользователь успешно зарегистрирован: {}", login);
     Ok(())
 }
@@ -402,7 +322,7 @@ pub struct RegisterUserStepOne<'info> {
     pub signer: AccountInfo<'info>,
 
     /// CHECK: это PDA, проверяется вручную через сиды и ключ
-    /// PDA счётчика пользователей
+    /// PDA счётчика пользователей (общий или любой из шардов)
     #[account(mut)]
     pub user_counter: AccountInfo<'info>,
 
@@ -415,9 +335,15 @@ pub struct RegisterUserStepOne<'info> {
     pub system_program: Program<'info, System>,
 
     /// Аккаунт получателя комиссии (проверяется по адресу)
-    /// CHECK: проверяется вручную по адресу
+    /// CHECK: проверяется вручную по адресу из настроек регистрации
     #[account(mut)]
     pub fee_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA реестра ["reserved=", login]. Проверяется вручную (должен быть пуст)
+    pub reserved_login_pda: AccountInfo<'info>,
 }
 
 /// Проверяет, что логин состоит из латинских строчных букв, цифр и "_"
@@ -470,11 +396,24 @@ pub fn validate_login(login: &str) -> Result<()> {
 /// ───────────────────────────────────────────────────────────────────────
 /// Структуры и сериализация UserById
 /// ───────────────────────────────────────────────────────────────────────
+///
+/// Константа для версии формата сериализации UserById
+pub const USER_BY_ID_FORMAT_V1: u32 = 1;
 
+/// V2 = V1 + статус пользователя, необязательный профиль и необязательный реферер
+pub const USER_BY_ID_FORMAT_V2: u32 = 2;
 
+/// Последний формат UserById (в нём пишет serialize_user_by_id)
+pub const USER_BY_ID_FORMAT_LATEST: u32 = USER_BY_ID_FORMAT_V2;
 
-/// Константа для версии формата сериализации UserById
-pub const USER_BY_ID_FORMAT_V1: u32 = 1;
+/// Ограничения полей профиля (в байтах UTF-8)
+pub const MAX_DISPLAY_NAME_LEN: usize = 64;
+pub const MAX_AVATAR_URI_LEN: usize = 200;
+pub const MAX_BIO_LEN: usize = 160;
+
+/// Особый формат «надгробия» в PDA "userId=" после удаления пользователя.
+/// Запись остаётся навсегда, чтобы этот ID больше никогда не был выдан.
+pub const USER_BY_ID_TOMBSTONE_FORMAT: u32 = 100;
 
 
 
@@ -499,12 +438,45 @@ pub struct DeviceInfo {
 /// - pubkey: подпись пользователя (32 байта)
 /// - device_count: количество устройств (1 байт)
 /// - devices: массив устройств (все устройства фиксированной длины)
+/// - status: статус пользователя (с V2; у записей V1 считается Active)
+/// - profile: профиль (с V2, может отсутствовать)
+/// - referred_by: id пригласившего пользователя (с V2, может отсутствовать)
 pub struct UserById {
     pub id: u64,
     pub login: String,
     pub pubkey: Pubkey,
     pub device_count: u8,
     pub devices: Vec<DeviceInfo>,
+    pub status: UserStatus,
+    pub profile: Option<UserProfile>,
+    pub referred_by: Option<u64>,
+}
+
+/// Профиль пользователя (блок записи V2).
+///
+/// Пустая строка / нулевой хеш — поле не задано.
+/// created_at = 0 — время создания неизвестно (пользователь зарегистрирован до V2).
+pub struct UserProfile {
+    pub display_name: String,
+    pub avatar_uri: String,
+    pub bio: String,
+    pub content_hash: [u8; 32], // хеш внешнего содержимого профиля (например, JSON в хранилище)
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl UserProfile {
+    /// Пустой профиль с временем создания
+    pub fn new(now: i64) -> Self {
+        UserProfile {
+            display_name: String::new(),
+            avatar_uri: String::new(),
+            bio: String::new(),
+            content_hash: [0u8; 32],
+            created_at: now,
+            updated_at: now,
+        }
+    }
 }
 
 
@@ -513,8 +485,7 @@ pub struct UserById {
 
 
 
-/// 🔧 Сериализация
-/// Сериализует структуру UserById в массив байт для хранения в PDA.
+/// 🔧 Сериализация / 🔄 десериализация UserById (через VersionedAccount)
 ///
 /// Формат:
 /// [0..4]      = format_type (u32)
@@ -524,129 +495,107 @@ pub struct UserById {
 /// [...]       = pubkey (32 байта)
 /// [...]       = количество устройств (1 байт)
 /// [..]*N      = по 65 байт на каждое устройство
-pub fn serialize_user_by_id(user: &UserById) -> Vec<u8> {
-    let mut result = Vec::new();
-
-    // 1. format_type (4 байта)
-    result.extend_from_slice(&USER_BY_ID_FORMAT_V1.to_le_bytes());
-
-    // 2. id (8 байт)
-    result.extend_from_slice(&user.id.to_le_bytes());
-
-    // 3. login (длина + строка)
-    let login_bytes = user.login.as_bytes();
-    let login_len = login_bytes.len().min(255) as u8;
-    result.push(login_len);
-    result.extend_from_slice(&login_bytes[..login_len as usize]);
-
-    // 4. pubkey (32 байта)
-    result.extend_from_slice(user.pubkey.as_ref());
-
-    // 5. количество устройств (1 байт)
-    result.push(user.device_count);
-
-    // 6. сериализуем каждое устройство (65 байт на устройство)
-    for device in &user.devices {
-        result.push(device.device_type);
-        result.extend_from_slice(device.device_pubkey.as_ref());
-        result.extend_from_slice(device.x25519_pubkey.as_ref());
-    }
-
-    result
-}
-
-
-
-
-
-
-/// 🔄 Общая десериализация
+/// --- V2 ---
+/// [...]       = status (u32)
+/// [...]       = есть ли профиль (u8: 0 / 1), если 1 — дальше:
+///               created_at (i64), updated_at (i64),
+///               display_name, avatar_uri, bio (каждое: длина u8 + байты),
+///               content_hash (32 байта)
+/// [...]       = есть ли реферер (u8: 0 / 1), если 1 — дальше referred_by (u64)
 ///
-/// Десериализует UserById по переданному массиву байт.
-///
-/// Сначала считывает первые 4 байта как `format_type`,
-/// затем вызывает нужную реализацию по формату.
-pub fn deserialize_user_by_id(data: &[u8]) -> Result<UserById> {
-    if data.len() < 4 {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-
-    let format_type = u32::from_le_bytes(data[0..4].try_into().unwrap());
-
-    match format_type {
-        USER_BY_ID_FORMAT_V1 => deserialize_user_by_id_format1(data),
-        _ => Err(error!(ErrCode::UnsupportedFormat)),
+/// Пишется всегда последний формат (V2). Записи V1 читаются со статусом Active,
+/// без профиля и реферера. Надгробие (USER_BY_ID_TOMBSTONE_FORMAT) — ошибка UserDeleted.
+impl VersionedAccount for UserById {
+    const LATEST_FORMAT: u32 = USER_BY_ID_FORMAT_LATEST;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            USER_BY_ID_FORMAT_V1 => decode_user_by_id_v1_body(reader),
+            USER_BY_ID_FORMAT_V2 => {
+                let mut user = decode_user_by_id_v1_body(reader)?;
+                user.status = UserStatus::from_u32(reader.read_u32()?)?;
+                // Флаги присутствия: 0 — блока нет
+                if reader.read_u8()? != 0 {
+                    let created_at = reader.read_i64()?;
+                    let updated_at = reader.read_i64()?;
+                    user.profile = Some(UserProfile {
+                        display_name: reader.read_short_string()?,
+                        avatar_uri: reader.read_short_string()?,
+                        bio: reader.read_short_string()?,
+                        content_hash: reader.read_array()?,
+                        created_at,
+                        updated_at,
+                    });
+                }
+                if reader.read_u8()? != 0 {
+                    user.referred_by = Some(reader.read_u64()?);
+                }
+                Ok(user)
+            }
+            USER_BY_ID_TOMBSTONE_FORMAT => Err(error!(ErrCode::UserDeleted)),
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
     }
-}
-
-
-
-
-
-
-
 
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        // V1-часть
+        writer.write_u64(self.id);
+        writer.write_short_string(&self.login);
+        writer.write_pubkey(&self.pubkey);
+        writer.write_u8(self.device_count);
+        for device in &self.devices {
+            writer.write_u8(device.device_type);
+            writer.write_pubkey(&device.device_pubkey);
+            writer.write_pubkey(&device.x25519_pubkey);
+        }
 
-/// 🧩 Десериализация первого формата
-///
-/// Десериализация UserById в формате V1 (основной формат).
-///
-/// См. структуру сериализации выше.
-fn deserialize_user_by_id_format1(data: &[u8]) -> Result<UserById> {
-    let mut offset = 4; // пропускаем формат
+        // V2: статус, необязательный профиль, необязательный реферер
+        writer.write_u32(self.status.as_u32());
+        match &self.profile {
+            None => writer.write_u8(0),
+            Some(profile) => {
+                writer.write_u8(1);
+                writer.write_i64(profile.created_at);
+                writer.write_i64(profile.updated_at);
+                writer.write_short_string(&profile.display_name);
+                writer.write_short_string(&profile.avatar_uri);
+                writer.write_short_string(&profile.bio);
+                writer.write_bytes(&profile.content_hash);
+            }
+        }
 
-    // 1. id
-    if data.len() < offset + 8 {
-        return Err(error!(ErrCode::DeserializationError));
+        match self.referred_by {
+            None => writer.write_u8(0),
+            Some(referrer_id) => {
+                writer.write_u8(1);
+                writer.write_u64(referrer_id);
+            }
+        }
     }
-    let id = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
-    offset += 8;
 
-    // 2. login
-    let login_len = data.get(offset).ok_or(ErrCode::DeserializationError)? as &u8;
-    offset += 1;
-
-    let login_end = offset + (*login_len as usize);
-    if data.len() < login_end {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-    let login = std::str::from_utf8(&data[offset..login_end])
-        .map_err(|_| error!(ErrCode::DeserializationError))?
-        .to_string();
-    offset = login_end;
-
-    // 3. pubkey
-    if data.len() < offset + 32 {
-        return Err(error!(ErrCode::DeserializationError));
+    fn upgrade_from(&mut self, _from_format: u32) {
+        // Единственный старый формат — V1: статуса не было (пользователь активен),
+        // профиля и реферера тоже
+        self.status = UserStatus::Active;
+        self.profile = None;
+        self.referred_by = None;
     }
-    let pubkey = Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
-    offset += 32;
+}
 
-    // 4. device_count
-    if data.len() < offset + 1 {
-        return Err(error!(ErrCode::DeserializationError));
-    }
-    let device_count = data[offset];
-    offset += 1;
+/// 🧩 Тело записи V1 (общая часть всех версий)
+fn decode_user_by_id_v1_body(reader: &mut ByteReader) -> Result<UserById> {
+    let id = reader.read_u64()?;
+    let login = reader.read_short_string()?;
+    let pubkey = reader.read_pubkey()?;
+    let device_count = reader.read_u8()?;
 
-    // 5. devices
-    let mut devices = Vec::new();
+    let mut devices = Vec::with_capacity(device_count as usize);
     for _ in 0..device_count {
-        if data.len() < offset + 65 {
-            return Err(error!(ErrCode::DeserializationError));
-        }
-
-        let device_type = data[offset];
-        let device_pubkey = Pubkey::new_from_array(data[offset + 1..offset + 33].try_into().unwrap());
-        let x25519_pubkey = Pubkey::new_from_array(data[offset + 33..offset + 65].try_into().unwrap());
-
         devices.push(DeviceInfo {
-            device_type,
-            device_pubkey,
-            x25519_pubkey,
+            device_type: reader.read_u8()?,
+            device_pubkey: reader.read_pubkey()?,
+            x25519_pubkey: reader.read_pubkey()?,
         });
-
-        offset += 65;
     }
 
     Ok(UserById {
@@ -655,9 +604,22 @@ fn deserialize_user_by_id_format1(data: &[u8]) -> Result<UserById> {
         pubkey,
         device_count,
         devices,
+        status: UserStatus::Active,
+        profile: None,
+        referred_by: None,
     })
 }
 
+/// Сериализует UserById в массив байт для хранения в PDA (последний формат)
+pub fn serialize_user_by_id(user: &UserById) -> Vec<u8> {
+    user.encode()
+}
+
+/// Десериализует UserById: определяет формат и читает нужную версию
+pub fn deserialize_user_by_id(data: &[u8]) -> Result<UserById> {
+    UserById::decode(data)
+}
+
 
 
 
@@ -671,11 +633,11 @@ fn deserialize_user_by_id_format1(data: &[u8]) -> Result<UserById> {
 /// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
 ///             Добавление нового пользователя с одним устройством
 /// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
-
+///
 /// ─────────────────────────────────────────────────────────────
 /// Константы для сидов PDA
 /// ─────────────────────────────────────────────────────────────
-
+///
 /// Префикс для PDA по логину
 pub const LOGIN_SEED_PREFIX: &str = "login=";
 
@@ -683,14 +645,99 @@ pub const LOGIN_SEED_PREFIX: &str = "login=";
 pub const USER_ID_SEED_PREFIX: &str = "userId=";
 
 
+/// Проверяет, что переданный аккаунт — это PDA по сиду ["userId=", id as string].
+/// Возвращает bump (нужен, если PDA будет подписывать через invoke_signed).
+pub fn check_user_by_id_pda(
+    pda_account: &AccountInfo,
+    user_id: u64,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let id_string = user_id.to_string();
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[USER_ID_SEED_PREFIX.as_bytes(), id_string.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверяет адрес PDA по ID и читает из него UserById.
+pub fn read_user_by_id_pda(
+    pda_account: &AccountInfo,
+    user_id: u64,
+    program_id: &Pubkey,
+) -> Result<UserById> {
+    check_user_by_id_pda(pda_account, user_id, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    deserialize_user_by_id(&raw)
+}
+
+/// Проверяет адрес PDA по ID и возвращает true, если в нём надгробие
+/// (пользователь удалён). Пустой PDA — ошибка EmptyPdaData.
+pub fn is_user_by_id_tombstone(
+    pda_account: &AccountInfo,
+    user_id: u64,
+    program_id: &Pubkey,
+) -> Result<bool> {
+    check_user_by_id_pda(pda_account, user_id, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    Ok(read_format_tag(&raw)? == USER_BY_ID_TOMBSTONE_FORMAT)
+}
+
+/// Записывает UserById в PDA, увеличивая его, если данные не помещаются
+/// (например, при переходе записи со старого формата на последний).
+pub fn write_user_by_id_pda<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    user: &UserById,
+) -> Result<()> {
+    let serialized = serialize_user_by_id(user);
+    if serialized.len() > pda_account.data_len() {
+        realloc_pda(pda_account, payer, system_program, serialized.len())?;
+    }
+    write_to_pda(pda_account, &serialized)
+}
+
+/// Проверяет, что переданный аккаунт — это PDA по сиду ["login=", login].
+/// Возвращает bump.
+pub fn check_user_by_login_pda(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[LOGIN_SEED_PREFIX.as_bytes(), login.as_bytes()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Проверяет адрес PDA по логину и читает из него UserByLogin.
+pub fn read_user_by_login_pda(
+    pda_account: &AccountInfo,
+    login: &str,
+    program_id: &Pubkey,
+) -> Result<UserByLogin> {
+    check_user_by_login_pda(pda_account, login, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    require!(!raw.is_empty(), ErrCode::EmptyPdaData);
+    deserialize_user_by_login(&raw)
+}
+
+
 /// Структура аккаунтов для регистрации пользователя с одним устройством
+///
+/// Кандидаты на PDA для UserById передаются через `remaining_accounts` (mut)
+/// в любом количестве: ["userId=", counter+1], ["userId=", counter+2], ...
+/// Программа выберет тот, что совпадёт со значением счётчика на момент
+/// выполнения. Сколько кандидатов передать — см. `recommended_id_pda_candidates`.
 #[derive(Accounts)]
 pub struct RegisterUserWithOneDev<'info> {
     /// CHECK: Подписант (владелец логина и устройства). Проверяется вручную через `.key == &user_pubkey`
     #[account(mut, signer)]
     pub signer: AccountInfo<'info>,
 
-    /// CHECK: PDA-счётчик количества пользователей. Проверяется вручную по сиду внутри функции
+    /// CHECK: PDA-счётчик количества пользователей (общий или любой из шардов). Проверяется вручную по сиду внутри функции
     #[account(mut)]
     pub user_counter: AccountInfo<'info>,
 
@@ -698,100 +745,223 @@ pub struct RegisterUserWithOneDev<'info> {
     #[account(mut)]
     pub user_by_login_pda: AccountInfo<'info>,
 
-    /// CHECK: Кандидаты на PDA для UserById (всего 5 штук). Один из них должен совпасть по рассчитанному адресу
-    #[account(mut)]
-    pub id_pda_1: AccountInfo<'info>,
-    /// CHECK: Кандидат на PDA по ID
-    #[account(mut)]
-    pub id_pda_2: AccountInfo<'info>,
-    /// CHECK: Кандидат на PDA по ID
-    #[account(mut)]
-    pub id_pda_3: AccountInfo<'info>,
-    /// CHECK: Кандидат на PDA по ID
-    #[account(mut)]
-    pub id_pda_4: AccountInfo<'info>,
-    /// CHECK: Кандидат на PDA по ID
-    #[account(mut)]
-    pub id_pda_5: AccountInfo<'info>,
-
     /// Стандартная системная программа
     pub system_program: Program<'info, System>,
 
-    /// CHECK: Получатель комиссии. Проверяется вручную по жёстко заданному адресу
+    /// CHECK: Получатель комиссии. Проверяется вручную по адресу из настроек регистрации
     #[account(mut)]
     pub fee_receiver: AccountInfo<'info>,
+
+    /// CHECK: PDA обратного индекса ["wallet=", pubkey]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA настроек регистрации ["registration_config"]. Проверяется вручную
+    pub registration_config: AccountInfo<'info>,
+
+    /// CHECK: PDA реестра ["reserved=", login]. Проверяется вручную (должен быть пуст)
+    pub reserved_login_pda: AccountInfo<'info>,
+
+    /// CHECK: PDA удержания ["loginHold=", login]. Проверяется вручную (должен быть пуст)
+    pub login_hold_pda: AccountInfo<'info>,
+
+    /// CHECK: Sysvar Instructions — для проверки подписи ключом устройства. Адрес проверяется вручную
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    /// Необязательные аккаунты реферера (нужны, если передан referrer_id)
+    pub referrer: ReferrerAccounts<'info>,
+
+    /// Необязательные аккаунты для оплаты комиссии токеном (нужны при fee_payment = Token)
+    pub token_fee: TokenFeeAccounts<'info>,
+}
+
+
+/// Домен подписи, которой устройство подтверждает владение ключом при регистрации
+pub const DEVICE_REGISTRATION_MESSAGE_DOMAIN: &[u8] = b"shine_users:register_device:v1";
+
+/// Сообщение, которое `device_sign_pubkey` подписывает при регистрации:
+/// домен + длина логина (u8) + логин + pubkey пользователя + X25519-ключ устройства
+pub fn device_registration_message(
+    login: &str,
+    user_pubkey: &Pubkey,
+    device_x25519_pubkey: &Pubkey,
+) -> Vec<u8> {
+    let login_bytes = login.as_bytes();
+    let login_len = login_bytes.len().min(255);
+    let mut msg = Vec::with_capacity(DEVICE_REGISTRATION_MESSAGE_DOMAIN.len() + 1 + login_len + 64);
+    msg.extend_from_slice(DEVICE_REGISTRATION_MESSAGE_DOMAIN);
+    msg.push(login_len as u8);
+    msg.extend_from_slice(&login_bytes[..login_len]);
+    msg.extend_from_slice(user_pubkey.as_ref());
+    msg.extend_from_slice(device_x25519_pubkey.as_ref());
+    msg
+}
+
+
+/// Аккаунты, общие для всех вариантов регистрации с одним устройством
+/// (обычной, спонсируемой и т.д.). `payer` платит комиссию и ренту.
+pub struct OneDevRegistrationAccounts<'a, 'info> {
+    pub payer: &'a AccountInfo<'info>,
+    pub user_counter: &'a AccountInfo<'info>,
+    pub user_by_login_pda: &'a AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub fee_receiver: &'a AccountInfo<'info>,
+    pub wallet_index_pda: &'a AccountInfo<'info>,
+    pub registration_config: &'a AccountInfo<'info>,
+    pub reserved_login_pda: &'a AccountInfo<'info>,
+    pub login_hold_pda: &'a AccountInfo<'info>,
+    pub instructions_sysvar: &'a AccountInfo<'info>,
+    pub referrer: &'a ReferrerAccounts<'info>,
+    pub token_fee: &'a TokenFeeAccounts<'info>,
+    pub id_pda_candidates: &'a [AccountInfo<'info>],
 }
 
 
 /// ─────────────────────────────────────────────────────────────
 /// Инструкция регистрации нового пользователя с одним устройством
 /// ─────────────────────────────────────────────────────────────
-pub fn register_user_with_one_dev(
-    ctx: Context<RegisterUserWithOneDev>,
+///
+/// В той же транзакции должна быть ed25519-инструкция, в которой
+/// `device_sign_pubkey` подписал `device_registration_message(...)`.
+pub fn register_user_with_one_dev<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RegisterUserWithOneDev<'info>>,
     login: String,                 // логин пользователя
     user_pubkey: Pubkey,          // публичная подпись пользователя (совпадает с signer)
     device_sign_pubkey: Pubkey,   // подпись устройства (Pubkey)
     device_x25519_pubkey: Pubkey, // ключ шифрования устройства (X25519)
+    referrer_id: Option<u64>,     // id пригласившего пользователя (необязательно)
+    fee_payment: FeePayment,      // чем платится комиссия: SOL или токеном
 ) -> Result<()> {
-    // ───────────── ШАГ 1 ─────────────
-    // Проверка: signer должен совпадать с переданным user_pubkey
-
     msg!("🔐 Регистрируем пользователя с логином: {}", login);
 
+    // Пользователь платит сам — signer должен совпадать с user_pubkey.
+    // Регистрацию за чужой счёт см. в sponsored::register_user_sponsored
     require!(ctx.accounts.signer.key == &user_pubkey, ErrCode::InvalidSigner);
 
+    let accounts = OneDevRegistrationAccounts {
+        payer: &ctx.accounts.signer,
+        user_counter: &ctx.accounts.user_counter,
+        user_by_login_pda: &ctx.accounts.user_by_login_pda,
+        system_program: ctx.accounts.system_program.to_account_info(),
+        fee_receiver: &ctx.accounts.fee_receiver,
+        wallet_index_pda: &ctx.accounts.wallet_index_pda,
+        registration_config: &ctx.accounts.registration_config,
+        reserved_login_pda: &ctx.accounts.reserved_login_pda,
+        login_hold_pda: &ctx.accounts.login_hold_pda,
+        instructions_sysvar: &ctx.accounts.instructions_sysvar,
+        referrer: &ctx.accounts.referrer,
+        token_fee: &ctx.accounts.token_fee,
+        id_pda_candidates: ctx.remaining_accounts,
+    };
+    register_one_dev_user(
+        &accounts,
+        ctx.program_id,
+        &login,
+        user_pubkey,
+        device_sign_pubkey,
+        device_x25519_pubkey,
+        referrer_id,
+        fee_payment,
+        false,
+    )?;
+    Ok(())
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Общая часть регистрации с одним устройством
+/// ─────────────────────────────────────────────────────────────
+///
+/// Кто авторизовал регистрацию (подпись пользователя) — проверяет вызывающий.
+/// `login_committed` — логин раскрыт из фиксации (commit-reveal); без неё
+/// регистрация отказывает, если в настройках включён REG_FLAG_COMMIT_REQUIRED.
+/// Здесь: ключ устройства, логин, резерв и удержание, реферер, комиссия, id, PDA и обратный индекс.
+/// Аккаунты реферера — в `accounts.referrer` (см. модуль referrals),
+/// токен-аккаунты для оплаты токеном — в `accounts.token_fee` (см. модуль token_fees).
+/// Возвращает id нового пользователя.
+#[allow(clippy::too_many_arguments)]
+pub fn register_one_dev_user(
+    accounts: &OneDevRegistrationAccounts,
+    program_id: &Pubkey,
+    login: &str,
+    user_pubkey: Pubkey,
+    device_sign_pubkey: Pubkey,
+    device_x25519_pubkey: Pubkey,
+    referrer_id: Option<u64>,
+    fee_payment: FeePayment,
+    login_committed: bool,
+) -> Result<u64> {
+    // ───────────── ШАГ 1 ─────────────
+    // Устройство должно подтвердить владение ключом подписи (ed25519-инструкция)
+    let device_message = device_registration_message(login, &user_pubkey, &device_x25519_pubkey);
+    let device_signers = ed25519_signers_for_message(accounts.instructions_sysvar, &device_message)?;
+    require!(device_signers.contains(&device_sign_pubkey), ErrCode::InvalidEd25519Instruction);
+
     // ───────────── ШАГ 2 ─────────────
     // Проверка валидности логина (длина и допустимые символы)
-    validate_login(&login)?;
+    validate_login(login)?;
 
     // ───────────── ШАГ 3 ─────────────
-    // Запрещённые логины
-    let reserved = ["admin", "support", "solana"];
-    require!(!reserved.contains(&login.as_str()), ErrCode::InvalidLogin);
+    // Зарезервированные логины (реестр "reserved=") и удержанные после переименования ("loginHold=")
+    require_login_not_reserved(accounts.reserved_login_pda, login, program_id)?;
+    require_login_not_held(accounts.login_hold_pda, login, program_id)?;
 
     // ───────────── ШАГ 4 ─────────────
     // Генерация PDA по логину ("login=", login)
     let login_seed_1 = LOGIN_SEED_PREFIX.as_bytes();
     let login_seed_2 = login.as_bytes();
     let (expected_login_pda, bump_login) = Pubkey::find_program_address(
-        &[login_seed_1, login_seed_2], ctx.program_id);
-    require!(ctx.accounts.user_by_login_pda.key == &expected_login_pda, ErrCode::InvalidPdaAddress);
+        &[login_seed_1, login_seed_2], program_id);
+    require!(accounts.user_by_login_pda.key == &expected_login_pda, ErrCode::InvalidPdaAddress);
 
     // ───────────── ШАГ 5 ─────────────
     // Проверка: PDA по логину должен быть пустым
-    if ctx.accounts.user_by_login_pda.owner != &Pubkey::default() {
+    if accounts.user_by_login_pda.owner != &Pubkey::default() {
         return Err(error!(ErrCode::UserAlreadyExists));
     }
 
     // ───────────── ШАГ 6 ─────────────
-    // Перевод комиссии 0.01 SOL (10_000_000 лампортов)
-    let expected_receiver = Pubkey::from_str(REGISTRATION_FEE_RECEIVER)
-        .map_err(|_| error!(ErrCode::InvalidLogin))?;
-    require!(ctx.accounts.fee_receiver.key == &expected_receiver, ErrCode::InvalidPdaAddress);
-
-    let ix = system_instruction::transfer(
-        ctx.accounts.signer.key,
-        ctx.accounts.fee_receiver.key,
-        10_000_000,
-    );
-    invoke(&ix, &[
-        ctx.accounts.signer.clone(),
-        ctx.accounts.fee_receiver.clone(),
-        ctx.accounts.system_program.to_account_info(),
-    ])?;
+    // Перевод комиссии (сумма и получатель — из настроек регистрации).
+    // Реферер проверяется заранее; его доля уходит на его кошелёк и только в SOL,
+    // поэтому оплата токеном с реферером при ненулевой доле отклоняется
+    let config = read_registration_config(accounts.registration_config, program_id)?;
+    if config.is_enabled(REG_FLAG_COMMIT_REQUIRED) {
+        require!(login_committed, ErrCode::LoginCommitmentRequired);
+    }
+    if fee_payment == FeePayment::Token && referrer_id.is_some() {
+        require!(config.referral_fee_bps == 0, ErrCode::ReferralShareRequiresSol);
+    }
+    let pays_referral_share =
+        fee_payment == FeePayment::Sol && config.referral_share(config.fee_lamports) > 0;
+    let referral = referrer_id
+        .map(|id| resolve_referral(accounts.referrer, id, pays_referral_share, program_id))
+        .transpose()?;
+    match fee_payment {
+        FeePayment::Sol => charge_registration_fee(
+            &config,
+            accounts.payer,
+            accounts.fee_receiver,
+            referral.as_ref().and_then(|r| r.wallet),
+            &accounts.system_program,
+        )?,
+        FeePayment::Token => charge_registration_fee_in_token(
+            &config,
+            accounts.payer,
+            accounts.token_fee,
+        )?,
+    }
 
     // ───────────── ШАГ 7 ─────────────
-    // Получаем текущий id пользователя (из PDA-счётчика)
-    let current_id = read_user_counter_pda(&ctx.accounts.user_counter, ctx.program_id)?;
-    let new_id = current_id + 1;
+    // Получаем новый id пользователя (из общего счётчика или шарда, счётчик сразу обновляется)
+    let new_id = allocate_user_id(accounts.user_counter, program_id)?;
 
     // ───────────── ШАГ 8 ─────────────
-    // Формируем структуру UserByLogin со статусом 1
+    // Формируем структуру UserByLogin со статусом Active
     let user_login = UserByLogin {
-        login: login.clone(),
+        login: login.to_string(),
         id: new_id,
         pubkey: user_pubkey,
-        status: 1,
+        status: UserStatus::Active,
     };
     let serialized_login = serialize_user_by_login(&user_login);
 
@@ -799,7 +969,7 @@ pub fn register_user_with_one_dev(
     // Формируем структуру UserById с одним устройством
     let user_id = UserById {
         id: new_id,
-        login: login.clone(),
+        login: login.to_string(),
         pubkey: user_pubkey,
         device_count: 1,
         devices: vec![DeviceInfo {
@@ -807,6 +977,9 @@ pub fn register_user_with_one_dev(
             device_pubkey: device_sign_pubkey,
             x25519_pubkey: device_x25519_pubkey,
         }],
+        status: UserStatus::Active,
+        profile: Some(UserProfile::new(Clock::get()?.unix_timestamp)),
+        referred_by: referrer_id,
     };
     let serialized_id = serialize_user_by_id(&user_id);
 
@@ -816,49 +989,96 @@ pub fn register_user_with_one_dev(
     let id_seed_2_string = new_id.to_string();            // Вначале сохраняем строку в памяти а потом преобразуем дальше
     let id_seed_2 = id_seed_2_string.as_bytes();
     let (expected_id_pda, bump_id) = Pubkey::find_program_address(
-        &[id_seed_1, id_seed_2], ctx.program_id);
-
-    let id_pdas = [
-        &ctx.accounts.id_pda_1,
-        &ctx.accounts.id_pda_2,
-        &ctx.accounts.id_pda_3,
-        &ctx.accounts.id_pda_4,
-        &ctx.accounts.id_pda_5,
-    ];
-    let target_id_pda = id_pdas
+        &[id_seed_1, id_seed_2], program_id);
+
+    let target_id_pda = accounts
+        .id_pda_candidates
         .iter()
         .find(|acc| acc.key == &expected_id_pda)
-        .ok_or_else(|| error!(ErrCode::NoSuitableIdPda))?; // ⚠️ в будущем можно расширить систему
+        .ok_or_else(|| error!(ErrCode::NoSuitableIdPda))?; // клиент передал слишком мало кандидатов
 
     // ───────────── ШАГ 11 ─────────────
     // Создаём PDA по логину и записываем туда данные
     create_pda(
-        &ctx.accounts.user_by_login_pda,
-        &ctx.accounts.signer,
-        &ctx.accounts.system_program.to_account_info(),
-        ctx.program_id,
+        accounts.user_by_login_pda,
+        accounts.payer,
+        &accounts.system_program,
+        program_id,
         &[login_seed_1, login_seed_2, &[bump_login]],
         serialized_login.len() as u64,
     )?;
-    write_to_pda(&ctx.accounts.user_by_login_pda, &serialized_login)?;
+    write_to_pda(accounts.user_by_login_pda, &serialized_login)?;
 
     // ───────────── ШАГ 12 ─────────────
     // Создаём PDA по ID и записываем туда UserById
     create_pda(
         target_id_pda,
-        &ctx.accounts.signer,
-        &ctx.accounts.system_program.to_account_info(),
-        ctx.program_id,
+        accounts.payer,
+        &accounts.system_program,
+        program_id,
         &[id_seed_1, id_seed_2, &[bump_id]],
-        200,
+        serialized_id.len().max(200) as u64,
     )?;
     write_to_pda(target_id_pda, &serialized_id)?;
 
     // ───────────── ШАГ 13 ─────────────
-    // Обновляем счётчик пользователей
-    write_user_counter_pda(&ctx.accounts.user_counter, ctx.program_id, new_id)?;
+    // Обратный индекс: кошелёк → пользователь
+    wallet_index_add(
+        accounts.wallet_index_pda,
+        accounts.payer,
+        &accounts.system_program,
+        program_id,
+        &user_pubkey,
+        new_id,
+        login,
+    )?;
+
+    // ───────────── ШАГ 14 ─────────────
+    // Счётчик приглашений реферера
+    if let Some(referral) = &referral {
+        let count = increment_referral_counter(referral, accounts.payer, &accounts.system_program, program_id)?;
+        msg!("🎁 Реферер id={} пригласил уже {}", referral.referrer_id, count);
+    }
 
     msg!("✅ Зарегистрирован login={} id={} с 1 устройством", login, new_id);
-    Ok(())
+    Ok(new_id)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Помощники для клиента: кандидаты на PDA по ID
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Верхняя граница кандидатов, которую имеет смысл передавать:
+/// транзакция ограничена 1232 байтами, а каждый аккаунт — это 32 байта ключа.
+pub const MAX_ID_PDA_CANDIDATES: u64 = 20;
+
+/// Сколько кандидатов передать при регистрации (для общего счётчика и для шарда).
+///
+/// - `observed_load`: сколько регистраций клиент видит «в полёте»
+///   (например, прирост счётчика за последние несколько слотов)
+///
+/// Берём нагрузку + 1 (своя регистрация) и четверть сверху про запас,
+/// но не больше MAX_ID_PDA_CANDIDATES.
+pub fn recommended_id_pda_candidates(observed_load: u64) -> u64 {
+    let base = observed_load.saturating_add(1);
+    let margin = base.div_ceil(4);
+    base.saturating_add(margin).clamp(1, MAX_ID_PDA_CANDIDATES)
 }
 
+/// Адреса кандидатов ["userId=", counter+1 .. counter+count] в порядке возрастания ID.
+/// Для шардов — см. `counter_shards::shard_id_pda_candidates`.
+///
+/// - `current_counter`: значение счётчика, прочитанное клиентом из PDA "user_counter"
+/// - `count`          : обычно результат `recommended_id_pda_candidates`
+pub fn id_pda_candidates(program_id: &Pubkey, current_counter: u64, count: u64) -> Vec<Pubkey> {
+    (1..=count)
+        .map(|offset| {
+            let id_string = current_counter.saturating_add(offset).to_string();
+            Pubkey::find_program_address(
+                &[USER_ID_SEED_PREFIX.as_bytes(), id_string.as_bytes()], program_id).0
+        })
+        .collect()
+}
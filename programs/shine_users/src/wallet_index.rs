@@ -0,0 +1,300 @@
+use anchor_lang::prelude::*;
+use common::codec::{ByteReader, ByteWriter, VersionedAccount};
+use common::utils::{create_pda, delete_pda_return_rent, realloc_pda, safe_read_pda, write_to_pda};
+use common::utils::ErrCode;
+
+
+// ───────────────────────────────────────────────────────────────────────
+//  Обратный индекс: кошелёк (pubkey) → пользователь (id, login)
+// ───────────────────────────────────────────────────────────────────────
+//
+// PDA по сиду ["wallet=", pubkey (32 байта)].
+// Создаётся при регистрации, обновляется при смене мастер-ключа,
+// переименовании логина и удалении пользователя.
+//
+// По умолчанию один кошелёк = один пользователь. Владелец кошелька может
+// явно разрешить несколько пользователей инструкцией `set_wallet_multi_user`.
+
+/// Префикс PDA обратного индекса
+pub const WALLET_INDEX_SEED_PREFIX: &str = "wallet=";
+
+/// Версия формата сериализации WalletIndex
+pub const WALLET_INDEX_FORMAT_V1: u32 = 1;
+
+/// Максимум пользователей на один кошелёк (если разрешено несколько)
+pub const MAX_USERS_PER_WALLET: usize = 10;
+
+
+/// Одна запись индекса
+pub struct WalletIndexEntry {
+    pub user_id: u64,
+    pub login: String,
+}
+
+/// Формат сериализованных данных:
+/// [0..4]      = format_type: u32 (WALLET_INDEX_FORMAT_V1)
+/// [4..36]     = pubkey: [u8; 32]
+/// [36]        = allow_multiple: u8 (0 / 1)
+/// [37]        = количество записей: u8
+/// [..]*N      = user_id: u64 + длина логина: u8 + логин
+pub struct WalletIndex {
+    pub pubkey: Pubkey,
+    pub allow_multiple: bool,
+    pub entries: Vec<WalletIndexEntry>,
+}
+
+/// 🔧 Сериализация / 🔄 десериализация WalletIndex (через VersionedAccount)
+impl VersionedAccount for WalletIndex {
+    const LATEST_FORMAT: u32 = WALLET_INDEX_FORMAT_V1;
+
+    fn decode_version(format: u32, reader: &mut ByteReader) -> Result<Self> {
+        match format {
+            WALLET_INDEX_FORMAT_V1 => {
+                let pubkey = reader.read_pubkey()?;
+                let allow_multiple = reader.read_u8()? != 0;
+                let count = reader.read_u8()? as usize;
+
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entries.push(WalletIndexEntry {
+                        user_id: reader.read_u64()?,
+                        login: reader.read_short_string()?,
+                    });
+                }
+
+                Ok(WalletIndex {
+                    pubkey,
+                    allow_multiple,
+                    entries,
+                })
+            }
+            _ => Err(error!(ErrCode::UnsupportedFormat)),
+        }
+    }
+
+    fn encode_latest(&self, writer: &mut ByteWriter) {
+        writer.write_pubkey(&self.pubkey);
+        writer.write_u8(self.allow_multiple as u8);
+        writer.write_u8(self.entries.len() as u8);
+
+        for entry in &self.entries {
+            writer.write_u64(entry.user_id);
+            writer.write_short_string(&entry.login);
+        }
+    }
+}
+
+/// Сериализует `WalletIndex` в последнем формате
+pub fn serialize_wallet_index(index: &WalletIndex) -> Vec<u8> {
+    index.encode()
+}
+
+/// Десериализует `WalletIndex`: определяет формат и читает нужную версию
+pub fn deserialize_wallet_index(data: &[u8]) -> Result<WalletIndex> {
+    WalletIndex::decode(data)
+}
+
+
+/// Проверяет адрес PDA индекса по сиду ["wallet=", pubkey], возвращает bump
+pub fn check_wallet_index_pda(
+    pda_account: &AccountInfo,
+    wallet: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8> {
+    let (expected_pda, bump) = Pubkey::find_program_address(
+        &[WALLET_INDEX_SEED_PREFIX.as_bytes(), wallet.as_ref()], program_id);
+    require!(pda_account.key == &expected_pda, ErrCode::InvalidPdaAddress);
+    Ok(bump)
+}
+
+/// Читает индекс; если PDA ещё нет — возвращает пустой индекс
+fn read_or_empty_wallet_index(pda_account: &AccountInfo, wallet: &Pubkey) -> Result<WalletIndex> {
+    let raw = safe_read_pda(pda_account);
+    if raw.is_empty() {
+        return Ok(WalletIndex {
+            pubkey: *wallet,
+            allow_multiple: false,
+            entries: Vec::new(),
+        });
+    }
+    deserialize_wallet_index(&raw)
+}
+
+/// Сохраняет индекс: создаёт PDA, если его нет, иначе меняет размер под данные
+fn save_wallet_index<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    bump: u8,
+    index: &WalletIndex,
+) -> Result<()> {
+    let serialized = serialize_wallet_index(index);
+    if pda_account.owner == &Pubkey::default() {
+        create_pda(
+            pda_account,
+            payer,
+            system_program,
+            program_id,
+            &[WALLET_INDEX_SEED_PREFIX.as_bytes(), index.pubkey.as_ref(), &[bump]],
+            serialized.len() as u64,
+        )?;
+    } else {
+        realloc_pda(pda_account, payer, system_program, serialized.len())?;
+    }
+    write_to_pda(pda_account, &serialized)
+}
+
+
+/// Можно ли добавить пользователя в индекс кошелька: индекса нет, пользователь
+/// уже в нём, или владелец разрешил несколько пользователей и есть место.
+pub fn wallet_index_can_add(
+    pda_account: &AccountInfo,
+    wallet: &Pubkey,
+    program_id: &Pubkey,
+    user_id: u64,
+) -> Result<bool> {
+    check_wallet_index_pda(pda_account, wallet, program_id)?;
+    let index = read_or_empty_wallet_index(pda_account, wallet)?;
+    Ok(index.entries.is_empty()
+        || index.entries.iter().any(|e| e.user_id == user_id)
+        || (index.allow_multiple && index.entries.len() < MAX_USERS_PER_WALLET))
+}
+
+
+/// ─────────────────────────────────────────────────────────────
+/// Добавить пользователя в индекс кошелька (при регистрации / смене ключа)
+/// ─────────────────────────────────────────────────────────────
+///
+/// Если пользователь уже есть в индексе — ничего не меняется.
+pub fn wallet_index_add<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    wallet: &Pubkey,
+    user_id: u64,
+    login: &str,
+) -> Result<()> {
+    let bump = check_wallet_index_pda(pda_account, wallet, program_id)?;
+    let mut index = read_or_empty_wallet_index(pda_account, wallet)?;
+
+    if index.entries.iter().any(|e| e.user_id == user_id) {
+        return Ok(());
+    }
+    if !index.entries.is_empty() {
+        require!(index.allow_multiple, ErrCode::WalletAlreadyRegistered);
+        require!(index.entries.len() < MAX_USERS_PER_WALLET, ErrCode::WalletAlreadyRegistered);
+    }
+
+    index.entries.push(WalletIndexEntry {
+        user_id,
+        login: login.to_string(),
+    });
+    save_wallet_index(pda_account, payer, system_program, program_id, bump, &index)
+}
+
+/// ─────────────────────────────────────────────────────────────
+/// Убрать пользователя из индекса (при смене ключа / удалении)
+/// ─────────────────────────────────────────────────────────────
+///
+/// Если записей не осталось и несколько пользователей не разрешено —
+/// PDA закрывается, рента уходит `payer`.
+pub fn wallet_index_remove<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    wallet: &Pubkey,
+    user_id: u64,
+) -> Result<()> {
+    let bump = check_wallet_index_pda(pda_account, wallet, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    if raw.is_empty() {
+        // Пользователь зарегистрирован до появления индекса — обновлять нечего
+        return Ok(());
+    }
+    let mut index = deserialize_wallet_index(&raw)?;
+
+    index.entries.retain(|e| e.user_id != user_id);
+
+    if index.entries.is_empty() && !index.allow_multiple {
+        return delete_pda_return_rent(pda_account, payer, program_id);
+    }
+    save_wallet_index(pda_account, payer, system_program, program_id, bump, &index)
+}
+
+/// ─────────────────────────────────────────────────────────────
+/// Обновить логин пользователя в индексе (при переименовании)
+/// ─────────────────────────────────────────────────────────────
+pub fn wallet_index_rename<'info>(
+    pda_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    wallet: &Pubkey,
+    user_id: u64,
+    new_login: &str,
+) -> Result<()> {
+    let bump = check_wallet_index_pda(pda_account, wallet, program_id)?;
+    let raw = safe_read_pda(pda_account);
+    if raw.is_empty() {
+        // Пользователь зарегистрирован до появления индекса — обновлять нечего
+        return Ok(());
+    }
+    let mut index = deserialize_wallet_index(&raw)?;
+
+    let Some(entry) = index.entries.iter_mut().find(|e| e.user_id == user_id) else {
+        return Ok(());
+    };
+    entry.login = new_login.to_string();
+
+    save_wallet_index(pda_account, payer, system_program, program_id, bump, &index)
+}
+
+
+
+
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///             Разрешить / запретить несколько пользователей на кошелёк
+/// ──────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────
+///
+/// Структура аккаунтов
+#[derive(Accounts)]
+pub struct SetWalletMultiUser<'info> {
+    /// CHECK: Сам кошелёк (владелец индекса). Платит за PDA, если его ещё нет
+    #[account(mut, signer)]
+    pub signer: AccountInfo<'info>,
+
+    /// CHECK: PDA ["wallet=", signer]. Проверяется вручную
+    #[account(mut)]
+    pub wallet_index_pda: AccountInfo<'info>,
+
+    /// Стандартная системная программа
+    pub system_program: Program<'info, System>,
+}
+
+/// Запретить можно только если к кошельку привязано не больше одного пользователя.
+pub fn set_wallet_multi_user(ctx: Context<SetWalletMultiUser>, allow: bool) -> Result<()> {
+    let wallet = *ctx.accounts.signer.key;
+    let pda = &ctx.accounts.wallet_index_pda;
+    let bump = check_wallet_index_pda(pda, &wallet, ctx.program_id)?;
+
+    let mut index = read_or_empty_wallet_index(pda, &wallet)?;
+    if !allow {
+        require!(index.entries.len() <= 1, ErrCode::WalletAlreadyRegistered);
+    }
+    index.allow_multiple = allow;
+
+    save_wallet_index(
+        pda,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program.to_account_info(),
+        ctx.program_id,
+        bump,
+        &index,
+    )?;
+
+    msg!("👛 Кошелёк {}: несколько пользователей {}", wallet, if allow { "разрешено" } else { "запрещено" });
+    Ok(())
+}
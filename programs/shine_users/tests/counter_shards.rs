@@ -0,0 +1,70 @@
+// ==============================================
+// Тесты шардированного счётчика: ID разных шардов не пересекаются.
+// ==============================================
+
+use std::collections::HashSet;
+
+use common::codec::VersionedAccount;
+use shine_users::counter_shards::*;
+
+/// Все шарды одной раскладки (shard_count, base) с нулевым счётчиком выдач
+fn shards(shard_count: u16, base: u64) -> Vec<UserCounterShard> {
+    (0..shard_count)
+        .map(|shard_index| UserCounterShard { shard_index, shard_count, base, allocated: 0 })
+        .collect()
+}
+
+#[test]
+fn shard_ids_never_collide() {
+    for shard_count in 1..=MAX_USER_COUNTER_SHARDS {
+        for base in [0u64, 1, 7, 1_000_000] {
+            let mut seen = HashSet::new();
+            for shard in shards(shard_count, base) {
+                for ahead in 0..50 {
+                    let id = shard.id_at(ahead).unwrap();
+                    assert!(id > base, "id {} не больше base {}", id, base);
+                    assert!(seen.insert(id), "id {} выдан дважды (шардов {}, base {})", id, shard_count, base);
+                }
+            }
+            // шарды вместе выдают подряд base+1 .. base+50*N без пропусков
+            let expected: HashSet<u64> = (base + 1..=base + 50 * shard_count as u64).collect();
+            assert_eq!(seen, expected);
+        }
+    }
+}
+
+#[test]
+fn id_at_continues_after_allocated() {
+    let mut shard = UserCounterShard { shard_index: 2, shard_count: 4, base: 10, allocated: 0 };
+    let planned: Vec<u64> = (0..5).map(|ahead| shard.id_at(ahead).unwrap()).collect();
+    assert_eq!(planned, vec![13, 17, 21, 25, 29]);
+
+    // после двух выдач ближайший ID — третий из запланированных
+    shard.allocated = 2;
+    assert_eq!(shard.id_at(0), Some(planned[2]));
+}
+
+#[test]
+fn id_at_reports_overflow() {
+    let shard = UserCounterShard { shard_index: 0, shard_count: 2, base: u64::MAX - 1, allocated: 0 };
+    assert_eq!(shard.id_at(0), Some(u64::MAX));
+    assert_eq!(shard.id_at(1), None);
+}
+
+#[test]
+fn counter_records_round_trip() {
+    let root = UserCounterRoot { base: 12_345, shard_count: 8 };
+    let data = root.encode();
+    assert_eq!(data.len(), USER_COUNTER_ROOT_SPACE);
+    let back = UserCounterRoot::decode(&data).unwrap();
+    assert_eq!((back.base, back.shard_count), (12_345, 8));
+
+    let shard = UserCounterShard { shard_index: 3, shard_count: 8, base: 12_345, allocated: 99 };
+    let data = shard.encode();
+    assert_eq!(data.len() as u64, USER_COUNTER_SHARD_SPACE);
+    let back = UserCounterShard::decode(&data).unwrap();
+    assert_eq!((back.shard_index, back.shard_count, back.base, back.allocated), (3, 8, 12_345, 99));
+
+    // старый 8-байтовый счётчик корнем не читается
+    assert!(UserCounterRoot::decode(&42u64.to_le_bytes()).is_err());
+}
@@ -0,0 +1,180 @@
+// ==============================================
+// Тесты сериализации записей shine_users: обратный индекс кошелька,
+// зарезервированный логин, UserByLogin / UserById и надгробие.
+// ==============================================
+
+use anchor_lang::prelude::*;
+use common::codec::VersionedAccount;
+use common::utils::ErrCode;
+use shine_users::deletion::UserTombstone;
+use shine_users::reserved_logins::*;
+use shine_users::status::UserStatus;
+use shine_users::users::*;
+use shine_users::wallet_index::*;
+
+fn device(device_type: u8) -> DeviceInfo {
+    DeviceInfo {
+        device_type,
+        device_pubkey: Pubkey::new_unique(),
+        x25519_pubkey: Pubkey::new_unique(),
+    }
+}
+
+fn user_by_id() -> UserById {
+    UserById {
+        id: 42,
+        login: "alice".to_string(),
+        pubkey: Pubkey::new_unique(),
+        device_count: 2,
+        devices: vec![device(1), device(2)],
+        status: UserStatus::Suspended,
+        profile: None,
+        referred_by: None,
+    }
+}
+
+#[test]
+fn wallet_index_round_trips() {
+    let index = WalletIndex {
+        pubkey: Pubkey::new_unique(),
+        allow_multiple: true,
+        entries: vec![
+            WalletIndexEntry { user_id: 1, login: "alice".to_string() },
+            WalletIndexEntry { user_id: 2, login: "bob_2".to_string() },
+        ],
+    };
+
+    let data = serialize_wallet_index(&index);
+    assert_eq!(data[..4], WALLET_INDEX_FORMAT_V1.to_le_bytes());
+    assert_eq!(data.len(), 4 + 32 + 1 + 1 + (8 + 1 + 5) * 2);
+
+    let back = deserialize_wallet_index(&data).unwrap();
+    assert_eq!(back.pubkey, index.pubkey);
+    assert!(back.allow_multiple);
+    let entries: Vec<(u64, &str)> = back.entries.iter().map(|e| (e.user_id, e.login.as_str())).collect();
+    assert_eq!(entries, vec![(1, "alice"), (2, "bob_2")]);
+
+    // обрезанная запись не читается
+    assert!(deserialize_wallet_index(&data[..data.len() - 1]).is_err());
+}
+
+#[test]
+fn reserved_login_round_trips() {
+    let entry = ReservedLogin { login: "support".to_string(), added_by: Pubkey::new_unique() };
+
+    let data = serialize_reserved_login(&entry);
+    assert_eq!(data[..4], RESERVED_LOGIN_FORMAT_V1.to_le_bytes());
+    assert_eq!(data.len(), 4 + 1 + 7 + 32);
+
+    let back = deserialize_reserved_login(&data).unwrap();
+    assert_eq!(back.login, "support");
+    assert_eq!(back.added_by, entry.added_by);
+
+    assert!(deserialize_reserved_login(&data[..data.len() - 1]).is_err());
+    let mut unknown = data.clone();
+    unknown[..4].copy_from_slice(&9u32.to_le_bytes());
+    assert_eq!(deserialize_reserved_login(&unknown).err(), Some(ErrCode::UnsupportedFormat.into()));
+}
+
+#[test]
+fn user_by_login_round_trips() {
+    let user = UserByLogin {
+        login: "alice".to_string(),
+        id: 42,
+        pubkey: Pubkey::new_unique(),
+        status: UserStatus::Pending,
+    };
+
+    let back = deserialize_user_by_login(&serialize_user_by_login(&user)).unwrap();
+    assert_eq!((back.login.as_str(), back.id), ("alice", 42));
+    assert_eq!(back.pubkey, user.pubkey);
+    assert_eq!(back.status, UserStatus::Pending);
+}
+
+#[test]
+fn user_by_id_round_trips_with_profile_and_referrer() {
+    let mut user = user_by_id();
+    user.profile = Some(UserProfile {
+        display_name: "Alice".to_string(),
+        avatar_uri: "ipfs://avatar".to_string(),
+        bio: "hi".to_string(),
+        content_hash: [7u8; 32],
+        created_at: 1_700_000_000,
+        updated_at: 1_700_000_500,
+    });
+    user.referred_by = Some(7);
+
+    let data = serialize_user_by_id(&user);
+    assert_eq!(data[..4], USER_BY_ID_FORMAT_LATEST.to_le_bytes());
+
+    let back = deserialize_user_by_id(&data).unwrap();
+    assert_eq!((back.id, back.login.as_str(), back.pubkey), (42, "alice", user.pubkey));
+    assert_eq!(back.device_count, 2);
+    let devices: Vec<(u8, Pubkey, Pubkey)> =
+        back.devices.iter().map(|d| (d.device_type, d.device_pubkey, d.x25519_pubkey)).collect();
+    let expected: Vec<(u8, Pubkey, Pubkey)> =
+        user.devices.iter().map(|d| (d.device_type, d.device_pubkey, d.x25519_pubkey)).collect();
+    assert_eq!(devices, expected);
+    assert_eq!(back.status, UserStatus::Suspended);
+    assert_eq!(back.referred_by, Some(7));
+
+    let profile = back.profile.unwrap();
+    assert_eq!(
+        (profile.display_name.as_str(), profile.avatar_uri.as_str(), profile.bio.as_str()),
+        ("Alice", "ipfs://avatar", "hi")
+    );
+    assert_eq!(profile.content_hash, [7u8; 32]);
+    assert_eq!((profile.created_at, profile.updated_at), (1_700_000_000, 1_700_000_500));
+}
+
+#[test]
+fn user_by_id_round_trips_without_optional_blocks() {
+    let user = user_by_id();
+    let data = serialize_user_by_id(&user);
+    let back = deserialize_user_by_id(&data).unwrap();
+    assert!(back.profile.is_none());
+    assert!(back.referred_by.is_none());
+
+    // без флагов присутствия запись V2 считается обрезанной
+    assert!(deserialize_user_by_id(&data[..data.len() - 1]).is_err());
+}
+
+#[test]
+fn user_by_id_v1_reads_as_active() {
+    let user = user_by_id();
+    let latest = serialize_user_by_id(&user);
+
+    // V1 — то же тело без статуса и флагов (4 + 1 + 1 байт в конце)
+    let mut v1 = latest[..latest.len() - 6].to_vec();
+    v1[..4].copy_from_slice(&USER_BY_ID_FORMAT_V1.to_le_bytes());
+
+    let back = deserialize_user_by_id(&v1).unwrap();
+    assert_eq!((back.id, back.login.as_str()), (42, "alice"));
+    assert_eq!(back.devices.len(), 2);
+    assert_eq!(back.status, UserStatus::Active);
+    assert!(back.profile.is_none() && back.referred_by.is_none());
+
+    // перезапись — уже в последнем формате, со статусом Active
+    let rewritten = serialize_user_by_id(&back);
+    assert_eq!(rewritten[..4], USER_BY_ID_FORMAT_LATEST.to_le_bytes());
+    assert_eq!(rewritten.len(), latest.len());
+}
+
+#[test]
+fn tombstone_round_trips_and_is_not_a_user() {
+    let tombstone = UserTombstone { id: 42, deleted_at: 1_700_000_000 };
+    let data = tombstone.encode();
+    assert_eq!(data.len(), 24);
+    assert_eq!(data[..4], USER_BY_ID_TOMBSTONE_FORMAT.to_le_bytes());
+
+    let back = UserTombstone::decode(&data).unwrap();
+    assert_eq!((back.id, back.deleted_at), (42, 1_700_000_000));
+
+    // по ID удалённый пользователь не читается как запись
+    assert_eq!(deserialize_user_by_id(&data).err(), Some(ErrCode::UserDeleted.into()));
+
+    // статус в надгробии — только Deleted
+    let mut corrupted = data.clone();
+    corrupted[12..16].copy_from_slice(&UserStatus::Active.as_u32().to_le_bytes());
+    assert_eq!(UserTombstone::decode(&corrupted).err(), Some(ErrCode::DeserializationError.into()));
+}